@@ -15,9 +15,17 @@ use nexus::{
     texture::{RawTextureReceiveCallback, load_texture_from_memory},
     texture_receive,
 };
-use std::fs;
+use std::{fs, path::PathBuf};
 use windows::Win32::{Foundation::HINSTANCE, System::LibraryLoader::GetModuleHandleW};
 
+/// Name of the directory Nexus keeps for this addon
+pub const ADDON_DIR_NAME: &str = "LOADER_public";
+
+/// The addon directory managed by Nexus, also used by the main crate as its data directory
+pub fn addon_dir() -> Option<PathBuf> {
+    get_addon_dir(ADDON_DIR_NAME)
+}
+
 /// Returns the HMODULE and casts it into HINSTANCE
 /// On modern systems, HMODULE is pretty much the same as HINSTANCE, and can be safely cast
 fn get_hinstance() -> HINSTANCE {
@@ -40,7 +48,7 @@ fn initialize_nexus_addon(attach_function: fn(HINSTANCE)) -> Result<()> {
     // Initialize the nexus menus and options
     // Create the addon dir if it doesn't exist
 
-    let addon_dir = get_addon_dir("LOADER_public").ok_or_else(|| {
+    let addon_dir = addon_dir().ok_or_else(|| {
         NexusError::ManagerInitialization("Failed to get addon directory".to_string())
     })?;
 
@@ -1,7 +1,7 @@
 pub mod init;
 pub mod ui;
 
-pub use init::{nexus_load, nexus_unload};
+pub use init::{addon_dir, nexus_load, nexus_unload};
 
 /// Consistent error types for the nexus addon
 #[derive(Debug)]
@@ -0,0 +1,166 @@
+use std::sync::atomic::Ordering;
+
+use super::{
+    Config, InputConfig,
+    changes::{Handler, Subsystem},
+};
+use crate::{
+    controls::{INPUT_SETTINGS, apply_input_transport, set_raw_input_enabled},
+    debug::{
+        DEBUG_FEATURES,
+        debug_overlay::{OVERLAY_MODE, refresh_overlay_buffer},
+    },
+    keybinds,
+    logging::{effective_filter, set_log_filter},
+    ui::swapchain::SWAPCHAIN_TARGET,
+};
+
+//Who applies what when overlay.toml changes, in that order.
+//Handlers only touch the fields that changed, so a setting toggled by a keybind
+//isn't reset when something else gets edited.
+pub static HANDLERS: &[(Subsystem, Handler<Config>)] = &[
+    (Subsystem::Logging, apply_logging),
+    (Subsystem::Rendering, apply_rendering),
+    (Subsystem::DebugOverlay, apply_debug_overlay),
+    (Subsystem::Swapchain, |_, new| {
+        SWAPCHAIN_TARGET.store(new.swapchain.target as u8, Ordering::Relaxed)
+    }),
+    (Subsystem::InputTransport, |old, new| {
+        apply_input_transport(&old.input, &new.input)
+    }),
+    (Subsystem::InputSettings, apply_input_settings),
+    (Subsystem::Keybinds, |_, new| {
+        keybinds::load_keybinds(&new.keybinds)
+    }),
+    (Subsystem::Profiles, apply_profile),
+];
+
+//Settings that can also be changed by keybinds, applied once at startup.
+pub fn apply_startup_settings(config: &Config) {
+    let rendering = &config.rendering;
+    DEBUG_FEATURES
+        .rendering_enabled
+        .store(rendering.enabled, Ordering::Relaxed);
+    DEBUG_FEATURES
+        .processing_enabled
+        .store(rendering.processing, Ordering::Relaxed);
+    OVERLAY_MODE.store(rendering.debug_overlay_mode, Ordering::Relaxed);
+    if rendering.debug_overlay {
+        DEBUG_FEATURES
+            .debug_overlay_enabled
+            .store(true, Ordering::Relaxed);
+    }
+
+    SWAPCHAIN_TARGET.store(config.swapchain.target as u8, Ordering::Relaxed);
+
+    //Raw input is registered when controls are initialized, the window doesn't exist yet.
+    let input = &config.input;
+    INPUT_SETTINGS
+        .raw_input_enabled
+        .store(input.raw_input, Ordering::Relaxed);
+    INPUT_SETTINGS
+        .raw_input_during_mouse_look
+        .store(input.raw_input_during_mouse_look, Ordering::Relaxed);
+    INPUT_SETTINGS
+        .focus_policy
+        .store(input.focus_policy as u8, Ordering::Relaxed);
+    INPUT_SETTINGS
+        .gamepad_enabled
+        .store(input.gamepad, Ordering::Relaxed);
+    apply_gamepad_config(input);
+}
+
+fn apply_logging(old: &Config, new: &Config) {
+    if old.logging.level != new.logging.level {
+        set_log_filter(effective_filter(&new.logging.level));
+    }
+}
+
+fn apply_rendering(old: &Config, new: &Config) {
+    let (old, new) = (&old.rendering, &new.rendering);
+    if old.enabled != new.enabled {
+        DEBUG_FEATURES
+            .rendering_enabled
+            .store(new.enabled, Ordering::Relaxed);
+    }
+    if old.processing != new.processing {
+        DEBUG_FEATURES
+            .processing_enabled
+            .store(new.processing, Ordering::Relaxed);
+    }
+}
+
+fn apply_debug_overlay(old: &Config, new: &Config) {
+    let (old, new) = (&old.rendering, &new.rendering);
+    if old.debug_overlay_mode != new.debug_overlay_mode {
+        OVERLAY_MODE.store(new.debug_overlay_mode, Ordering::Relaxed);
+    }
+    if old.debug_overlay != new.debug_overlay {
+        DEBUG_FEATURES
+            .debug_overlay_enabled
+            .store(new.debug_overlay, Ordering::Relaxed);
+    }
+    if DEBUG_FEATURES.debug_overlay_enabled.load(Ordering::Relaxed) {
+        refresh_overlay_buffer(None);
+    }
+}
+
+fn apply_profile(old: &Config, new: &Config) {
+    let name = |config: &Config| config.active_profile().map(|profile| profile.name.clone());
+    if name(old) != name(new) {
+        log_active_profile(new);
+    }
+}
+
+pub fn log_active_profile(config: &Config) {
+    match config.active_profile() {
+        Some(profile) => log::info!("Using profile \"{}\".", profile.name),
+        None => log::info!("No profile matches this game, using the top-level settings."),
+    }
+}
+
+fn apply_input_settings(old: &Config, new: &Config) {
+    let (old, new) = (&old.input, &new.input);
+    if old.raw_input != new.raw_input {
+        set_raw_input_enabled(new.raw_input);
+    }
+    if old.raw_input_during_mouse_look != new.raw_input_during_mouse_look {
+        INPUT_SETTINGS
+            .raw_input_during_mouse_look
+            .store(new.raw_input_during_mouse_look, Ordering::Relaxed);
+    }
+    if old.focus_policy != new.focus_policy {
+        INPUT_SETTINGS
+            .focus_policy
+            .store(new.focus_policy as u8, Ordering::Relaxed);
+    }
+    if old.gamepad != new.gamepad {
+        INPUT_SETTINGS
+            .gamepad_enabled
+            .store(new.gamepad, Ordering::Relaxed);
+    }
+    if old.gamepad_toggle_combo != new.gamepad_toggle_combo
+        || old.gamepad_deadzone != new.gamepad_deadzone
+        || old.gamepad_speed != new.gamepad_speed
+    {
+        apply_gamepad_config(new);
+    }
+}
+
+//Picked up by the gamepad thread on its next poll.
+fn apply_gamepad_config(input: &InputConfig) {
+    INPUT_SETTINGS.set_gamepad_config(
+        input.gamepad_toggle_combo,
+        input.gamepad_deadzone as f32,
+        input.gamepad_speed as f32,
+    );
+}
+
+///Logged for the subsystems no handler applies.
+pub fn unhandled_message(subsystem: Subsystem) -> Option<&'static str> {
+    match subsystem {
+        Subsystem::OnUse => Some("Paths and overlay names are used from now on."),
+        Subsystem::Restart => Some("Some changes only take effect after restarting the game."),
+        _ => None,
+    }
+}
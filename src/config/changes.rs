@@ -0,0 +1,366 @@
+use std::fmt;
+
+use serde::Serialize;
+use toml::Value;
+
+//What changed between two configurations, and who has to hear about it.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    //"input.focus_policy", "keybinds"...
+    pub path: String,
+    //None when the field didn't exist before, or doesn't anymore.
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: &Option<String>| value.clone().unwrap_or_else(|| "(none)".to_string());
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.path,
+            show(&self.old),
+            show(&self.new)
+        )
+    }
+}
+
+///Every field that differs, by dotted path. Arrays are compared as a whole.
+pub fn diff<T: Serialize>(old: &T, new: &T) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    match (Value::try_from(old), Value::try_from(new)) {
+        (Ok(old), Ok(new)) => diff_values("", Some(&old), Some(&new), &mut changes),
+        (Err(e), _) | (_, Err(e)) => log::error!("Could not compare configurations: {}", e),
+    }
+    changes
+}
+
+fn diff_values(path: &str, old: Option<&Value>, new: Option<&Value>, out: &mut Vec<FieldChange>) {
+    match (old, new) {
+        (Some(Value::Table(old)), Some(Value::Table(new))) => {
+            let mut keys: Vec<&String> = old.keys().collect();
+            keys.extend(new.keys().filter(|key| !old.contains_key(*key)));
+            for key in keys {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                diff_values(&path, old.get(key), new.get(key), out);
+            }
+        }
+        (old, new) if old == new => {}
+        (old, new) => out.push(FieldChange {
+            path: path.to_string(),
+            old: old.map(describe),
+            new: new.map(describe),
+        }),
+    }
+}
+
+//Short enough for a log line.
+fn describe(value: &Value) -> String {
+    match value {
+        Value::String(text) => format!("\"{}\"", text),
+        Value::Array(items) if items.iter().any(Value::is_table) => {
+            format!("{} entries", items.len())
+        }
+        other => other.to_string(),
+    }
+}
+
+//Parts of the DLL that apply settings on their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subsystem {
+    Logging,
+    Rendering,
+    DebugOverlay,
+    Swapchain,
+    //Addresses input events are sent to and subscriptions are received on.
+    InputTransport,
+    InputSettings,
+    Keybinds,
+    //The profiles themselves, their settings show up in the sections they replace.
+    Profiles,
+    //Read every time they are used, nothing to do.
+    OnUse,
+    //Only read at startup.
+    Restart,
+}
+
+///Which subsystem applies a field, by its dotted path.
+pub fn subsystem_of(path: &str) -> Subsystem {
+    let (section, field) = path.split_once('.').unwrap_or((path, ""));
+    match (section, field) {
+        ("logging", "level") => Subsystem::Logging,
+        ("logging", _) => Subsystem::Restart,
+        ("rendering", "debug_overlay" | "debug_overlay_mode") => Subsystem::DebugOverlay,
+        ("rendering", _) => Subsystem::Rendering,
+        ("swapchain", _) => Subsystem::Swapchain,
+        ("input", "legacy_address" | "subscribe_address") => Subsystem::InputTransport,
+        ("input", _) => Subsystem::InputSettings,
+        ("keybinds", _) => Subsystem::Keybinds,
+        ("profiles", _) => Subsystem::Profiles,
+        ("paths" | "overlay", _) => Subsystem::OnUse,
+        _ => Subsystem::Restart,
+    }
+}
+
+///The subsystems touched by `changes`, each once, in the order they first appear.
+pub fn affected(changes: &[FieldChange]) -> Vec<Subsystem> {
+    let mut subsystems = Vec::new();
+    for change in changes {
+        let subsystem = subsystem_of(&change.path);
+        if !subsystems.contains(&subsystem) {
+            subsystems.push(subsystem);
+        }
+    }
+    subsystems
+}
+
+//Applies the new settings of a subsystem. Gets the previous and the new configuration.
+pub type Handler<C> = fn(&C, &C);
+
+///Calls the handlers of the affected subsystems, in the order of `handlers`.
+///Returns the affected subsystems nobody handled.
+pub fn notify<C>(
+    handlers: &[(Subsystem, Handler<C>)],
+    affected: &[Subsystem],
+    old: &C,
+    new: &C,
+) -> Vec<Subsystem> {
+    for (subsystem, handler) in handlers {
+        if affected.contains(subsystem) {
+            handler(old, new);
+        }
+    }
+    affected
+        .iter()
+        .copied()
+        .filter(|subsystem| !handlers.iter().any(|(handled, _)| handled == subsystem))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Sample {
+        name: String,
+        level: u32,
+        input: Input,
+        keybinds: Vec<Keybind>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        profile: Option<String>,
+    }
+
+    #[derive(Serialize, Clone)]
+    struct Input {
+        raw_input: bool,
+        subscribe_address: String,
+    }
+
+    #[derive(Serialize, Clone)]
+    struct Keybind {
+        keys: String,
+    }
+
+    fn sample() -> Sample {
+        Sample {
+            name: "a".to_string(),
+            level: 1,
+            input: Input {
+                raw_input: false,
+                subscribe_address: "127.0.0.1:49153".to_string(),
+            },
+            keybinds: vec![Keybind {
+                keys: "F5".to_string(),
+            }],
+            profile: None,
+        }
+    }
+
+    fn change(path: &str, old: Option<&str>, new: Option<&str>) -> FieldChange {
+        FieldChange {
+            path: path.to_string(),
+            old: old.map(str::to_string),
+            new: new.map(str::to_string),
+        }
+    }
+
+    fn changes_of(changed: &[&str]) -> Vec<FieldChange> {
+        changed
+            .iter()
+            .map(|path| change(path, None, None))
+            .collect()
+    }
+
+    #[test]
+    fn nothing_changed() {
+        assert_eq!(diff(&sample(), &sample()), vec![]);
+    }
+
+    #[test]
+    fn changed_fields_by_path() {
+        let mut new = sample();
+        new.level = 2;
+        new.name = "b".to_string();
+        new.input.raw_input = true;
+        //Sorted, the order of the keys depends on toml's preserve_order feature.
+        let mut changes = diff(&sample(), &new);
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(
+            changes,
+            vec![
+                change("input.raw_input", Some("false"), Some("true")),
+                change("level", Some("1"), Some("2")),
+                change("name", Some("\"a\""), Some("\"b\"")),
+            ]
+        );
+    }
+
+    #[test]
+    fn added_and_removed_fields() {
+        let mut new = sample();
+        new.profile = Some("wvw".to_string());
+        assert_eq!(
+            diff(&sample(), &new),
+            vec![change("profile", None, Some("\"wvw\""))]
+        );
+        assert_eq!(
+            diff(&new, &sample()),
+            vec![change("profile", Some("\"wvw\""), None)]
+        );
+    }
+
+    #[test]
+    fn arrays_are_compared_as_a_whole() {
+        let mut new = sample();
+        new.keybinds[0].keys = "F6".to_string();
+        assert_eq!(
+            diff(&sample(), &new),
+            vec![change("keybinds", Some("1 entries"), Some("1 entries"))]
+        );
+        new.keybinds.push(new.keybinds[0].clone());
+        assert_eq!(
+            diff(&sample(), &new),
+            vec![change("keybinds", Some("1 entries"), Some("2 entries"))]
+        );
+        //Arrays of plain values are shown as they are.
+        assert_eq!(
+            describe(&Value::Array(vec![Value::Integer(1), Value::Integer(2)])),
+            "[1, 2]"
+        );
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            change("input.gamepad", Some("false"), Some("true")).to_string(),
+            "input.gamepad: false -> true"
+        );
+        assert_eq!(
+            change("profile", None, Some("\"wvw\"")).to_string(),
+            "profile: (none) -> \"wvw\""
+        );
+    }
+
+    #[test]
+    fn subsystems() {
+        let cases = [
+            ("logging.level", Subsystem::Logging),
+            ("logging.directory", Subsystem::Restart),
+            ("logging.keep_days", Subsystem::Restart),
+            ("rendering.enabled", Subsystem::Rendering),
+            ("rendering.processing", Subsystem::Rendering),
+            ("rendering.debug_overlay", Subsystem::DebugOverlay),
+            ("rendering.debug_overlay_mode", Subsystem::DebugOverlay),
+            ("swapchain.target", Subsystem::Swapchain),
+            ("input.legacy_address", Subsystem::InputTransport),
+            ("input.subscribe_address", Subsystem::InputTransport),
+            ("input.raw_input", Subsystem::InputSettings),
+            ("input.gamepad_speed", Subsystem::InputSettings),
+            ("keybinds", Subsystem::Keybinds),
+            ("profiles", Subsystem::Profiles),
+            ("paths.recordings", Subsystem::OnUse),
+            ("overlay.process_name", Subsystem::OnUse),
+            ("something_new", Subsystem::Restart),
+        ];
+        for (path, subsystem) in cases {
+            assert_eq!(subsystem_of(path), subsystem, "{}", path);
+        }
+    }
+
+    #[test]
+    fn affected_once_in_order() {
+        let changes = changes_of(&[
+            "input.raw_input",
+            "logging.level",
+            "input.focus_policy",
+            "input.subscribe_address",
+            "logging.level",
+        ]);
+        assert_eq!(
+            affected(&changes),
+            vec![
+                Subsystem::InputSettings,
+                Subsystem::Logging,
+                Subsystem::InputTransport,
+            ]
+        );
+        assert_eq!(affected(&[]), vec![]);
+    }
+
+    thread_local! {
+        static CALLED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn called(name: &str, old: &u32, new: &u32) {
+        CALLED.with(|called| {
+            called
+                .borrow_mut()
+                .push(format!("{} {}->{}", name, old, new))
+        });
+    }
+
+    static HANDLERS: &[(Subsystem, Handler<u32>)] = &[
+        (Subsystem::Logging, |old, new| called("logging", old, new)),
+        (Subsystem::InputTransport, |old, new| {
+            called("transport", old, new)
+        }),
+        (Subsystem::InputSettings, |old, new| {
+            called("settings", old, new)
+        }),
+    ];
+
+    #[test]
+    fn notify_calls_the_handlers_in_their_order() {
+        let unhandled = notify(
+            HANDLERS,
+            &[
+                Subsystem::InputSettings,
+                Subsystem::Restart,
+                Subsystem::Logging,
+                Subsystem::OnUse,
+            ],
+            &1,
+            &2,
+        );
+        assert_eq!(unhandled, vec![Subsystem::Restart, Subsystem::OnUse]);
+        assert_eq!(
+            CALLED.with(|called| called.take()),
+            ["logging 1->2", "settings 1->2"]
+        );
+    }
+
+    #[test]
+    fn notify_without_changes() {
+        assert_eq!(notify(HANDLERS, &[], &1, &2), vec![]);
+        assert!(CALLED.with(|called| called.take()).is_empty());
+    }
+}
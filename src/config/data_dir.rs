@@ -0,0 +1,233 @@
+use std::{
+    ffi::OsString,
+    fmt,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use crate::utils::get_dll_path;
+
+//Overrides every other way of finding the data directory.
+pub const DATA_DIR_ENV: &str = "DX11_OVERLAY_DATA_DIR";
+//Relative to the game's working directory, where everything was before the data directory.
+pub const LEGACY_DATA_DIR: &str = "addons/LOADER_public";
+
+//Where overlay.toml, logs, recordings... live.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataDir {
+    pub path: PathBuf,
+    pub source: DataDirSource,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataDirSource {
+    Environment,
+    Nexus,
+    DllLocation,
+    Legacy,
+}
+
+impl fmt::Display for DataDirSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataDirSource::Environment => write!(f, "from {}", DATA_DIR_ENV),
+            DataDirSource::Nexus => write!(f, "Nexus addon directory"),
+            DataDirSource::DllLocation => write!(f, "next to the DLL"),
+            DataDirSource::Legacy => write!(f, "default location"),
+        }
+    }
+}
+
+//What the data directory can be found from, missing ones are None.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DataDirCandidates {
+    pub env: Option<OsString>,
+    pub nexus: Option<PathBuf>,
+    pub dll: Option<PathBuf>,
+}
+
+impl DataDirCandidates {
+    pub fn current() -> DataDirCandidates {
+        DataDirCandidates {
+            env: std::env::var_os(DATA_DIR_ENV),
+            nexus: nexus_addon_dir(),
+            dll: get_dll_path(),
+        }
+    }
+}
+
+///The first usable of: the environment variable, the Nexus addon directory,
+///the directory of the DLL, and the legacy path.
+pub fn resolve(candidates: DataDirCandidates) -> DataDir {
+    let env = candidates
+        .env
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| (PathBuf::from(dir), DataDirSource::Environment));
+    let nexus = candidates.nexus.map(|dir| (dir, DataDirSource::Nexus));
+    let dll = candidates
+        .dll
+        .and_then(|dll| dll.parent().map(Path::to_path_buf))
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(|dir| (dir, DataDirSource::DllLocation));
+    let (path, source) = env
+        .or(nexus)
+        .or(dll)
+        .unwrap_or_else(|| (PathBuf::from(LEGACY_DATA_DIR), DataDirSource::Legacy));
+    DataDir { path, source }
+}
+
+static DATA_DIR: LazyLock<DataDir> = LazyLock::new(|| resolve(DataDirCandidates::current()));
+
+///Resolved once, the first time it is needed.
+pub fn data_dir() -> &'static DataDir {
+    &DATA_DIR
+}
+
+///Where a path from overlay.toml points to. Relative paths are relative to the data directory,
+///except the ones starting with addons/LOADER_public written by earlier versions.
+pub fn resolve_path(path: &Path) -> PathBuf {
+    resolve_path_in(&data_dir().path, path)
+}
+
+pub fn resolve_path_in(dir: &Path, path: &Path) -> PathBuf {
+    if path.is_absolute() || path.starts_with(LEGACY_DATA_DIR) {
+        path.to_path_buf()
+    } else {
+        dir.join(path)
+    }
+}
+
+#[cfg(feature = "nexus")]
+fn nexus_addon_dir() -> Option<PathBuf> {
+    nexus_integration::addon_dir()
+}
+
+#[cfg(not(feature = "nexus"))]
+fn nexus_addon_dir() -> Option<PathBuf> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir(path: &str) -> PathBuf {
+        PathBuf::from(path)
+    }
+
+    fn data_dir(path: &str, source: DataDirSource) -> DataDir {
+        DataDir {
+            path: dir(path),
+            source,
+        }
+    }
+
+    fn every_candidate() -> DataDirCandidates {
+        DataDirCandidates {
+            env: Some(OsString::from("env_dir")),
+            nexus: Some(dir("addons/nexus_dir")),
+            dll: Some(dir("dll_dir/overlay.dll")),
+        }
+    }
+
+    #[test]
+    fn environment_wins() {
+        assert_eq!(
+            resolve(every_candidate()),
+            data_dir("env_dir", DataDirSource::Environment)
+        );
+    }
+
+    #[test]
+    fn then_nexus() {
+        let candidates = DataDirCandidates {
+            env: None,
+            ..every_candidate()
+        };
+        assert_eq!(
+            resolve(candidates),
+            data_dir("addons/nexus_dir", DataDirSource::Nexus)
+        );
+    }
+
+    #[test]
+    fn then_next_to_the_dll() {
+        let candidates = DataDirCandidates {
+            dll: Some(dir("dll_dir/overlay.dll")),
+            ..Default::default()
+        };
+        assert_eq!(
+            resolve(candidates),
+            data_dir("dll_dir", DataDirSource::DllLocation)
+        );
+    }
+
+    #[test]
+    fn then_the_legacy_path() {
+        assert_eq!(
+            resolve(DataDirCandidates::default()),
+            data_dir(LEGACY_DATA_DIR, DataDirSource::Legacy)
+        );
+    }
+
+    #[test]
+    fn empty_environment_variable_is_ignored() {
+        let candidates = DataDirCandidates {
+            env: Some(OsString::new()),
+            ..every_candidate()
+        };
+        assert_eq!(resolve(candidates).source, DataDirSource::Nexus);
+        let candidates = DataDirCandidates {
+            env: Some(OsString::new()),
+            ..Default::default()
+        };
+        assert_eq!(resolve(candidates).source, DataDirSource::Legacy);
+    }
+
+    #[test]
+    fn dll_without_a_directory_falls_back() {
+        for dll in ["overlay.dll", ""] {
+            let candidates = DataDirCandidates {
+                dll: Some(dir(dll)),
+                ..Default::default()
+            };
+            assert_eq!(resolve(candidates).source, DataDirSource::Legacy, "{}", dll);
+        }
+    }
+
+    #[test]
+    fn relative_paths_are_in_the_data_directory() {
+        let data = dir("data");
+        assert_eq!(
+            resolve_path_in(&data, Path::new("logs")),
+            dir("data").join("logs")
+        );
+        assert_eq!(
+            resolve_path_in(&data, Path::new("recordings/old")),
+            dir("data").join("recordings/old")
+        );
+    }
+
+    #[test]
+    fn absolute_and_legacy_paths_are_kept() {
+        let absolute = std::env::temp_dir().join("logs");
+        assert_eq!(resolve_path_in(Path::new("data"), &absolute), absolute);
+        let legacy = Path::new(LEGACY_DATA_DIR).join("logs");
+        assert_eq!(resolve_path_in(Path::new("data"), &legacy), legacy);
+        //Only whole components count.
+        let similar = Path::new("addons/LOADER_public2/logs");
+        assert_eq!(
+            resolve_path_in(Path::new("data"), similar),
+            dir("data").join(similar)
+        );
+    }
+
+    #[test]
+    fn sources_in_the_log() {
+        assert_eq!(
+            DataDirSource::Environment.to_string(),
+            "from DX11_OVERLAY_DATA_DIR"
+        );
+        assert_eq!(DataDirSource::DllLocation.to_string(), "next to the DLL");
+    }
+}
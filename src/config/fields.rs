@@ -0,0 +1,131 @@
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use toml::{Table, Value};
+
+//Problem with one field of overlay.toml. The field keeps its default, everything else still loads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigDiagnostic {
+    //"input.focus_policy", "keybinds[2].action"...
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+///Reads the fields of one table, reporting what is missing the mark instead of failing.
+pub struct Fields<'a> {
+    path: String,
+    table: Option<&'a Table>,
+    read: Vec<&'static str>,
+    diagnostics: &'a mut Vec<ConfigDiagnostic>,
+}
+
+impl<'a> Fields<'a> {
+    pub fn new(
+        path: impl Into<String>,
+        value: Option<&'a Value>,
+        diagnostics: &'a mut Vec<ConfigDiagnostic>,
+    ) -> Self {
+        let path = path.into();
+        let table = match value {
+            Some(Value::Table(table)) => Some(table),
+            Some(other) => {
+                diagnostics.push(ConfigDiagnostic {
+                    field: path.clone(),
+                    message: format!("expected a table, found {}", other.type_str()),
+                });
+                None
+            }
+            None => None,
+        };
+        Self {
+            path,
+            table,
+            read: Vec::new(),
+            diagnostics,
+        }
+    }
+
+    ///The value of `key`, or `default` if it is missing or has the wrong type.
+    pub fn get<T: DeserializeOwned>(&mut self, key: &'static str, default: T) -> T {
+        self.parse(key, default, |value: T| Ok(value))
+    }
+
+    ///Like get(), then checks or converts the value with `parse`.
+    pub fn parse<T, U: DeserializeOwned>(
+        &mut self,
+        key: &'static str,
+        default: T,
+        parse: impl FnOnce(U) -> Result<T, String>,
+    ) -> T {
+        self.read.push(key);
+        let Some(value) = self.table.and_then(|table| table.get(key)) else {
+            return default;
+        };
+        let parsed = value
+            .clone()
+            .try_into::<U>()
+            .map_err(|e| e.message().to_string())
+            .and_then(parse);
+        match parsed {
+            Ok(value) => value,
+            Err(message) => {
+                self.report(key, message);
+                default
+            }
+        }
+    }
+
+    ///Marks a key as read elsewhere, so finish() doesn't report it.
+    pub fn skip(&mut self, key: &'static str) {
+        self.read.push(key);
+    }
+
+    pub fn report(&mut self, key: &str, message: String) {
+        self.diagnostics.push(ConfigDiagnostic {
+            field: format!("{}.{}", self.path, key),
+            message,
+        });
+    }
+
+    ///Reports the keys that were never read, most likely typos.
+    pub fn finish(self) {
+        let Some(table) = self.table else {
+            return;
+        };
+        for key in table.keys() {
+            if !self.read.contains(&key.as_str()) {
+                self.diagnostics.push(ConfigDiagnostic {
+                    field: format!("{}.{}", self.path, key),
+                    message: "unknown setting, ignored".to_string(),
+                });
+            }
+        }
+    }
+}
+
+pub fn non_empty(text: String) -> Result<String, String> {
+    if text.trim().is_empty() {
+        Err("must not be empty".to_string())
+    } else {
+        Ok(text)
+    }
+}
+
+pub fn one_of<T: Copy>(
+    names: &[&'static str],
+    values: &[T],
+) -> impl FnOnce(String) -> Result<T, String> {
+    move |text: String| {
+        names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(&text))
+            .map(|index| values[index])
+            .ok_or_else(|| format!("\"{}\" is not one of {}", text, names.join(", ")))
+    }
+}
@@ -0,0 +1,123 @@
+use std::{fs, io, path::Path};
+
+use super::KeybindEntry;
+
+//Where keybinds lived before overlay.toml. Only read once, to migrate them.
+pub const LEGACY_KEYBINDS_PATH: &str = "addons/LOADER_public/keybinds.conf";
+//keybinds.conf is renamed to that once migrated, so it doesn't look like it still matters.
+const RETIRED_KEYBINDS_PATH: &str = "addons/LOADER_public/keybinds.conf.migrated";
+
+///The keybinds of keybinds.conf, None if there is no such file.
+pub fn read_legacy_keybinds() -> Option<io::Result<Vec<KeybindEntry>>> {
+    if !Path::new(LEGACY_KEYBINDS_PATH).exists() {
+        return None;
+    }
+    Some(fs::read_to_string(LEGACY_KEYBINDS_PATH).map(|text| keybinds_from_conf(&text)))
+}
+
+pub fn retire_legacy_keybinds(notes: &mut Vec<String>) {
+    if let Err(e) = fs::rename(LEGACY_KEYBINDS_PATH, RETIRED_KEYBINDS_PATH) {
+        notes.push(format!(
+            "Could not rename {} to {}: {}",
+            LEGACY_KEYBINDS_PATH, RETIRED_KEYBINDS_PATH, e
+        ));
+    }
+}
+
+///Converts keybinds.conf ("<keybind> <actions>" lines under optional "[scope]" sections).
+///Lines are copied as written, problems are reported when overlay.toml gets loaded.
+pub fn keybinds_from_conf(text: &str) -> Vec<KeybindEntry> {
+    let mut entries = Vec::new();
+    let mut scope = String::new();
+    for line in text.lines() {
+        let content = line.split('#').next().unwrap_or("").trim();
+        if let Some(section) = content.strip_prefix('[') {
+            //An unclosed section is kept as is so it gets reported.
+            let section = section.strip_suffix(']').unwrap_or(content).trim();
+            scope = if section.eq_ignore_ascii_case("always") {
+                String::new()
+            } else {
+                section.to_string()
+            };
+            continue;
+        }
+        let mut parts = content.split_whitespace();
+        let Some(keys) = parts.next() else {
+            continue;
+        };
+        entries.push(KeybindEntry {
+            keys: keys.to_string(),
+            action: parts.collect::<Vec<_>>().join(" "),
+            scope: scope.clone(),
+        });
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(keys: &str, action: &str, scope: &str) -> KeybindEntry {
+        KeybindEntry {
+            keys: keys.to_string(),
+            action: action.to_string(),
+            scope: scope.to_string(),
+        }
+    }
+
+    #[test]
+    fn lines_become_entries() {
+        let text = "\
+# Keybinds of the overlay
+Ctrl+Alt+P toggle_debug_overlay
+
+F6@hold=1000   debug_overlay_mode statistics; set_debug_overlay on   # both at once
+F7
+";
+        assert_eq!(
+            keybinds_from_conf(text),
+            [
+                entry("Ctrl+Alt+P", "toggle_debug_overlay", ""),
+                entry(
+                    "F6@hold=1000",
+                    "debug_overlay_mode statistics; set_debug_overlay on",
+                    ""
+                ),
+                //Reported as missing its action when overlay.toml is loaded.
+                entry("F7", "", ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn scope_sections() {
+        let text = "\
+F1 reload_config
+[overlay_connected, game_not_typing]
+F2 restart_blish
+  [ debug_overlay_visible ]  # comment
+F3 toggle_debug_overlay
+[Always]
+F4 dump_debug_data
+[overlay_connected
+F5 reload_config
+";
+        assert_eq!(
+            keybinds_from_conf(text),
+            [
+                entry("F1", "reload_config", ""),
+                entry("F2", "restart_blish", "overlay_connected, game_not_typing"),
+                entry("F3", "toggle_debug_overlay", "debug_overlay_visible"),
+                entry("F4", "dump_debug_data", ""),
+                entry("F5", "reload_config", "[overlay_connected"),
+            ]
+        );
+    }
+
+    #[test]
+    fn nothing_to_migrate() {
+        assert!(keybinds_from_conf("").is_empty());
+        assert!(keybinds_from_conf("# only a comment\n\n   \n").is_empty());
+    }
+}
@@ -0,0 +1,1125 @@
+use std::{
+    fs,
+    net::SocketAddr,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use serde::{Serialize, Serializer};
+use toml::{Table, Value};
+
+use crate::{
+    controls::{
+        focus::FocusPolicy,
+        gamepad::{
+            DEFAULT_DEADZONE, DEFAULT_MAX_SPEED, DEFAULT_TOGGLE_COMBO, combo_name, parse_combo,
+        },
+    },
+    debug::debug_overlay::{overlay_mode, show_log_overlay},
+    keybinds::{self, DEFAULT_KEYBINDS},
+    logging::{LogFilter, LogFormat},
+    shutdown::{sleep_unless_stopping, spawn_worker},
+    ui::swapchain::SwapchainTarget,
+};
+use apply::{HANDLERS, apply_startup_settings, log_active_profile, unhandled_message};
+use changes::{affected, diff, notify};
+use data_dir::{LEGACY_DATA_DIR, data_dir};
+use fields::{ConfigDiagnostic, Fields, non_empty, one_of};
+use profiles::HostProcess;
+
+pub mod apply;
+pub mod changes;
+pub mod data_dir;
+pub mod fields;
+pub mod migrate;
+pub mod profiles;
+
+//Every setting of the DLL, in the data directory.
+pub const CONFIG_FILE: &str = "overlay.toml";
+//How often overlay.toml is checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+//Editors may save in several writes, give them a moment before reading.
+const SETTLE_DELAY: Duration = Duration::from_millis(100);
+
+const HEADER: &str = "\
+# Settings of the external DX11 overlay loader.
+# Missing settings use their default. Invalid ones are reported in the log and use their default too.
+# Changes are applied as soon as this file is saved, except the logging settings other than logging.level.
+# Relative paths are relative to the directory of this file.
+
+";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Config {
+    pub logging: LoggingConfig,
+    pub paths: PathsConfig,
+    pub overlay: OverlayConfig,
+    pub rendering: RenderingConfig,
+    pub swapchain: SwapchainConfig,
+    pub input: InputConfig,
+    pub keybinds: Vec<KeybindEntry>,
+    pub profiles: Vec<ProfileConfig>,
+    //Index of the profile matching the game, already applied to the sections above.
+    #[serde(skip)]
+    pub active_profile: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LoggingConfig {
+    //Most verbose level written, for everything and for some modules: "info,mmf=trace".
+    #[serde(serialize_with = "filter_text")]
+    pub level: LogFilter,
+    //Where the log files go, one per session.
+    pub directory: PathBuf,
+    //Also log to the console, if the game has one.
+    pub stdout: bool,
+    //text or json, for the log files and the console.
+    #[serde(serialize_with = "log_format_name")]
+    pub file_format: LogFormat,
+    #[serde(serialize_with = "log_format_name")]
+    pub stdout_format: LogFormat,
+    //Log files of older sessions are removed beyond that many sessions, 0 keeps them all.
+    pub keep_sessions: u32,
+    //And once they are that many days old, 0 keeps them whatever their age.
+    pub keep_days: u32,
+    //A session continues in a new file once its file reaches that size, 0 for no limit.
+    pub max_file_size_mb: u32,
+    //Gzip the log files of older sessions and the full ones of this session.
+    pub compress: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PathsConfig {
+    //Started by the restart_blish action.
+    pub blish_hud: PathBuf,
+    //Where toggle_input_recording writes and replay_last_recording reads.
+    pub recordings: PathBuf,
+}
+
+//Names the overlay process and its shared objects use, for forks that renamed them.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OverlayConfig {
+    //Killed by the restart_blish action.
+    pub process_name: String,
+    //File mapping holding the shared texture handles.
+    pub texture_mapping: String,
+    //Mutex held while the overlay is running.
+    pub alive_mutex: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RenderingConfig {
+    //Draw the overlay on top of the game.
+    pub enabled: bool,
+    //Copy new frames from the overlay.
+    pub processing: bool,
+    //Show the debug overlay at startup.
+    pub debug_overlay: bool,
+    //log or statistics.
+    #[serde(serialize_with = "overlay_mode_name")]
+    pub debug_overlay_mode: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SwapchainConfig {
+    //game_window or any.
+    #[serde(serialize_with = "swapchain_target_name")]
+    pub target: SwapchainTarget,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InputConfig {
+    //Mouse moves are always sent there, for overlays that don't subscribe.
+    pub legacy_address: SocketAddr,
+    //Where consumers subscribe to input events and send control requests.
+    pub subscribe_address: SocketAddr,
+    //Forward raw mouse motion and buttons.
+    pub raw_input: bool,
+    //Keep forwarding raw input while the game hides the cursor to rotate the camera.
+    pub raw_input_during_mouse_look: bool,
+    #[serde(serialize_with = "focus_policy_name")]
+    pub focus_policy: FocusPolicy,
+    //Let a controller drive the overlay cursor.
+    pub gamepad: bool,
+    //Buttons toggling overlay cursor mode, eg. "LB+RB+Back".
+    #[serde(serialize_with = "gamepad_combo_name")]
+    pub gamepad_toggle_combo: u16,
+    //Portion of the stick range that is ignored, 0.0 - 1.0.
+    pub gamepad_deadzone: f64,
+    //Cursor speed in pixels per second with the stick fully tilted.
+    pub gamepad_speed: f64,
+}
+
+//One keybind, see the Keybinds section of the README.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeybindEntry {
+    //Keybind with an optional activation, eg. "Ctrl+Alt+P" or "F6@hold=1000".
+    pub keys: String,
+    //Actions to run, separated by ';'.
+    pub action: String,
+    //Conditions that must hold for the keybind to fire, eg. "overlay_connected, game_not_typing".
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub scope: String,
+}
+
+//Settings for one game, used instead of the top-level ones when the DLL is loaded into it.
+//Sections left out of the profile keep the top-level values.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProfileConfig {
+    pub name: String,
+    //File name of the game's executable, eg. "Gw2-64.exe".
+    pub executable: String,
+    //Only use the profile if the game has a window of this class.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_class: Option<String>,
+    pub overlay: OverlayConfig,
+    pub paths: PathsConfig,
+    pub swapchain: SwapchainConfig,
+    pub input: InputConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            logging: LoggingConfig {
+                level: LogFilter::new(log::LevelFilter::Debug),
+                directory: PathBuf::from("logs"),
+                stdout: true,
+                file_format: LogFormat::Text,
+                stdout_format: LogFormat::Text,
+                keep_sessions: 20,
+                keep_days: 30,
+                max_file_size_mb: 10,
+                compress: false,
+            },
+            paths: PathsConfig {
+                blish_hud: PathBuf::from("Blish.HUD.1.2.0/Blish HUD.exe"),
+                recordings: PathBuf::from("recordings"),
+            },
+            overlay: OverlayConfig {
+                process_name: "Blish HUD.exe".to_string(),
+                texture_mapping: "BlishHUD_Header".to_string(),
+                alive_mutex: "Global\\blish_isalive_mutex".to_string(),
+            },
+            rendering: RenderingConfig {
+                enabled: true,
+                processing: true,
+                debug_overlay: false,
+                debug_overlay_mode: overlay_mode::LOG_MODE,
+            },
+            swapchain: SwapchainConfig {
+                target: SwapchainTarget::GameWindow,
+            },
+            input: InputConfig {
+                legacy_address: SocketAddr::from(([127, 0, 0, 1], 49152)),
+                subscribe_address: SocketAddr::from(([127, 0, 0, 1], 49153)),
+                raw_input: false,
+                raw_input_during_mouse_look: true,
+                focus_policy: FocusPolicy::Aggressive,
+                gamepad: false,
+                gamepad_toggle_combo: DEFAULT_TOGGLE_COMBO,
+                gamepad_deadzone: DEFAULT_DEADZONE,
+                gamepad_speed: DEFAULT_MAX_SPEED,
+            },
+            keybinds: DEFAULT_KEYBINDS
+                .iter()
+                .map(|(keys, action)| KeybindEntry {
+                    keys: keys.to_string(),
+                    action: action.to_string(),
+                    scope: String::new(),
+                })
+                .collect(),
+            profiles: Vec::new(),
+            active_profile: None,
+        }
+    }
+}
+
+impl Config {
+    ///Reads overlay.toml. Fields that are missing or invalid keep their default.
+    ///The first profile matching `host` replaces the top-level sections it bundles.
+    pub fn parse(
+        text: &str,
+        host: &HostProcess,
+    ) -> Result<(Config, Vec<ConfigDiagnostic>), toml::de::Error> {
+        let root: Table = text.parse()?;
+        let default = Config::default();
+        let mut diagnostics = Vec::new();
+        let logging = read_logging(
+            Fields::new("logging", root.get("logging"), &mut diagnostics),
+            default.logging,
+        );
+        let paths = read_paths(
+            Fields::new("paths", root.get("paths"), &mut diagnostics),
+            default.paths,
+        );
+        let overlay = read_overlay(
+            Fields::new("overlay", root.get("overlay"), &mut diagnostics),
+            default.overlay,
+        );
+        let rendering = read_rendering(
+            Fields::new("rendering", root.get("rendering"), &mut diagnostics),
+            default.rendering,
+        );
+        let swapchain = read_swapchain(
+            Fields::new("swapchain", root.get("swapchain"), &mut diagnostics),
+            default.swapchain,
+        );
+        let input = read_input(
+            Fields::new("input", root.get("input"), &mut diagnostics),
+            default.input,
+        );
+
+        let keybinds = match root.get("keybinds") {
+            None => default.keybinds,
+            Some(Value::Array(entries)) => entries
+                .iter()
+                .enumerate()
+                .filter_map(|(index, entry)| parse_keybind_entry(index, entry, &mut diagnostics))
+                .collect(),
+            Some(other) => {
+                diagnostics.push(ConfigDiagnostic {
+                    field: "keybinds".to_string(),
+                    message: format!("expected an array of tables, found {}", other.type_str()),
+                });
+                default.keybinds
+            }
+        };
+
+        let mut config = Config {
+            logging,
+            paths,
+            overlay,
+            rendering,
+            swapchain,
+            input,
+            keybinds,
+            profiles: Vec::new(),
+            active_profile: None,
+        };
+        config.profiles = match root.get("profiles") {
+            None => Vec::new(),
+            Some(Value::Array(entries)) => entries
+                .iter()
+                .enumerate()
+                .filter_map(|(index, entry)| parse_profile(index, entry, &config, &mut diagnostics))
+                .collect(),
+            Some(other) => {
+                diagnostics.push(ConfigDiagnostic {
+                    field: "profiles".to_string(),
+                    message: format!("expected an array of tables, found {}", other.type_str()),
+                });
+                Vec::new()
+            }
+        };
+        config.active_profile = profiles::select(&config.profiles, host);
+        if let Some(profile) = config.active_profile() {
+            let profile = profile.clone();
+            config.overlay = profile.overlay;
+            config.paths = profile.paths;
+            config.swapchain = profile.swapchain;
+            config.input = profile.input;
+        }
+
+        for key in root.keys() {
+            if !SECTIONS.contains(&key.as_str()) {
+                diagnostics.push(ConfigDiagnostic {
+                    field: key.clone(),
+                    message: "unknown section, ignored".to_string(),
+                });
+            }
+        }
+
+        Ok((config, diagnostics))
+    }
+
+    pub fn active_profile(&self) -> Option<&ProfileConfig> {
+        self.profiles.get(self.active_profile?)
+    }
+
+    ///The content of overlay.toml for this configuration.
+    pub fn to_toml(&self) -> String {
+        let body = toml::to_string_pretty(self).unwrap_or_else(|e| {
+            log::error!("Could not serialize the configuration: {}", e);
+            String::new()
+        });
+        format!("{}{}", HEADER, body)
+    }
+}
+
+const SECTIONS: [&str; 8] = [
+    "logging",
+    "paths",
+    "overlay",
+    "rendering",
+    "swapchain",
+    "input",
+    "keybinds",
+    "profiles",
+];
+
+//Each section is read with the values it falls back to, the defaults or the top-level
+//values for profiles.
+
+fn read_logging(mut fields: Fields, default: LoggingConfig) -> LoggingConfig {
+    let logging = LoggingConfig {
+        level: fields.parse("level", default.level, |filter: String| filter.parse()),
+        directory: fields.parse("directory", default.directory, |path: String| {
+            non_empty(path).map(PathBuf::from)
+        }),
+        stdout: fields.get("stdout", default.stdout),
+        file_format: fields.parse(
+            "file_format",
+            default.file_format,
+            one_of(&LogFormat::NAMES, &LogFormat::ALL),
+        ),
+        stdout_format: fields.parse(
+            "stdout_format",
+            default.stdout_format,
+            one_of(&LogFormat::NAMES, &LogFormat::ALL),
+        ),
+        keep_sessions: fields.get("keep_sessions", default.keep_sessions),
+        keep_days: fields.get("keep_days", default.keep_days),
+        max_file_size_mb: fields.get("max_file_size_mb", default.max_file_size_mb),
+        compress: fields.get("compress", default.compress),
+    };
+    fields.finish();
+    logging
+}
+
+fn read_paths(mut fields: Fields, default: PathsConfig) -> PathsConfig {
+    let paths = PathsConfig {
+        blish_hud: fields.parse("blish_hud", default.blish_hud, |path: String| {
+            non_empty(path).map(PathBuf::from)
+        }),
+        recordings: fields.parse("recordings", default.recordings, |path: String| {
+            non_empty(path).map(PathBuf::from)
+        }),
+    };
+    fields.finish();
+    paths
+}
+
+fn read_overlay(mut fields: Fields, default: OverlayConfig) -> OverlayConfig {
+    let overlay = OverlayConfig {
+        process_name: fields.parse("process_name", default.process_name, non_empty),
+        texture_mapping: fields.parse("texture_mapping", default.texture_mapping, non_empty),
+        alive_mutex: fields.parse("alive_mutex", default.alive_mutex, non_empty),
+    };
+    fields.finish();
+    overlay
+}
+
+fn read_rendering(mut fields: Fields, default: RenderingConfig) -> RenderingConfig {
+    let rendering = RenderingConfig {
+        enabled: fields.get("enabled", default.enabled),
+        processing: fields.get("processing", default.processing),
+        debug_overlay: fields.get("debug_overlay", default.debug_overlay),
+        debug_overlay_mode: fields.parse(
+            "debug_overlay_mode",
+            default.debug_overlay_mode,
+            one_of(&overlay_mode::NAMES, &overlay_mode::ALL),
+        ),
+    };
+    fields.finish();
+    rendering
+}
+
+fn read_swapchain(mut fields: Fields, default: SwapchainConfig) -> SwapchainConfig {
+    let swapchain = SwapchainConfig {
+        target: fields.parse(
+            "target",
+            default.target,
+            one_of(&SwapchainTarget::NAMES, &SwapchainTarget::ALL),
+        ),
+    };
+    fields.finish();
+    swapchain
+}
+
+fn read_input(mut fields: Fields, default: InputConfig) -> InputConfig {
+    let input = InputConfig {
+        legacy_address: fields.parse("legacy_address", default.legacy_address, address),
+        subscribe_address: fields.parse("subscribe_address", default.subscribe_address, address),
+        raw_input: fields.get("raw_input", default.raw_input),
+        raw_input_during_mouse_look: fields.get(
+            "raw_input_during_mouse_look",
+            default.raw_input_during_mouse_look,
+        ),
+        focus_policy: fields.parse(
+            "focus_policy",
+            default.focus_policy,
+            one_of(&FocusPolicy::NAMES, &FocusPolicy::ALL),
+        ),
+        gamepad: fields.get("gamepad", default.gamepad),
+        gamepad_toggle_combo: fields.parse(
+            "gamepad_toggle_combo",
+            default.gamepad_toggle_combo,
+            |text: String| parse_combo(&text),
+        ),
+        gamepad_deadzone: fields.parse(
+            "gamepad_deadzone",
+            default.gamepad_deadzone,
+            |value: f64| in_range(value, 0.0..1.0),
+        ),
+        gamepad_speed: fields.parse("gamepad_speed", default.gamepad_speed, |value: f64| {
+            in_range(value, 1.0..100_000.0)
+        }),
+    };
+    if input.legacy_address == input.subscribe_address {
+        fields.report(
+            "subscribe_address",
+            "must differ from legacy_address".to_string(),
+        );
+    }
+    fields.finish();
+    input
+}
+
+//The sections a profile doesn't set keep the top-level values of `base`.
+fn parse_profile(
+    index: usize,
+    entry: &Value,
+    base: &Config,
+    diagnostics: &mut Vec<ConfigDiagnostic>,
+) -> Option<ProfileConfig> {
+    let field = format!("profiles[{}]", index);
+    if !entry.is_table() {
+        diagnostics.push(ConfigDiagnostic {
+            field,
+            message: format!("expected a table, found {}", entry.type_str()),
+        });
+        return None;
+    }
+    let section = |name: &str| format!("{}.{}", field, name);
+    let overlay = read_overlay(
+        Fields::new(section("overlay"), entry.get("overlay"), diagnostics),
+        base.overlay.clone(),
+    );
+    let paths = read_paths(
+        Fields::new(section("paths"), entry.get("paths"), diagnostics),
+        base.paths.clone(),
+    );
+    let swapchain = read_swapchain(
+        Fields::new(section("swapchain"), entry.get("swapchain"), diagnostics),
+        base.swapchain.clone(),
+    );
+    let input = read_input(
+        Fields::new(section("input"), entry.get("input"), diagnostics),
+        base.input.clone(),
+    );
+
+    let mut fields = Fields::new(field.clone(), Some(entry), diagnostics);
+    for name in ["overlay", "paths", "swapchain", "input"] {
+        fields.skip(name);
+    }
+    let executable = fields.parse("executable", None, |executable: String| {
+        non_empty(executable).map(Some)
+    });
+    let name = fields.parse("name", None, |name: String| non_empty(name).map(Some));
+    let window_class = fields.parse("window_class", None, |class: String| {
+        non_empty(class).map(Some)
+    });
+    if executable.is_none() && entry.get("executable").is_none() {
+        fields.report("executable", "missing, profile ignored".to_string());
+    }
+    fields.finish();
+    let executable = executable?;
+    Some(ProfileConfig {
+        name: name.unwrap_or_else(|| executable.clone()),
+        executable,
+        window_class,
+        overlay,
+        paths,
+        swapchain,
+        input,
+    })
+}
+
+//Only the keys and action are checked here, their content is checked when loading the keybinds.
+fn parse_keybind_entry(
+    index: usize,
+    entry: &Value,
+    diagnostics: &mut Vec<ConfigDiagnostic>,
+) -> Option<KeybindEntry> {
+    let field = format!("keybinds[{}]", index);
+    if !entry.is_table() {
+        diagnostics.push(ConfigDiagnostic {
+            field,
+            message: format!("expected a table, found {}", entry.type_str()),
+        });
+        return None;
+    }
+    let mut fields = Fields::new(field, Some(entry), diagnostics);
+    let keys = fields.parse("keys", None, |keys: String| non_empty(keys).map(Some));
+    let action = fields.parse("action", None, |action: String| non_empty(action).map(Some));
+    let scope = fields.get("scope", String::new());
+    for (key, value) in [("keys", &keys), ("action", &action)] {
+        if value.is_none() && entry.get(key).is_none() {
+            fields.report(key, "missing, keybind ignored".to_string());
+        }
+    }
+    fields.finish();
+    Some(KeybindEntry {
+        keys: keys?,
+        action: action?,
+        scope,
+    })
+}
+
+fn address(text: String) -> Result<SocketAddr, String> {
+    text.parse()
+        .map_err(|_| format!("\"{}\" is not an address like 127.0.0.1:49152", text))
+}
+
+fn in_range(value: f64, range: Range<f64>) -> Result<f64, String> {
+    if range.contains(&value) {
+        Ok(value)
+    } else {
+        Err(format!(
+            "{} is not between {} and {}",
+            value, range.start, range.end
+        ))
+    }
+}
+
+fn filter_text<S: Serializer>(filter: &LogFilter, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(filter)
+}
+fn log_format_name<S: Serializer>(format: &LogFormat, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(format.name())
+}
+fn overlay_mode_name<S: Serializer>(mode: &u8, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(overlay_mode::name(*mode))
+}
+fn swapchain_target_name<S: Serializer>(
+    target: &SwapchainTarget,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(target.name())
+}
+fn gamepad_combo_name<S: Serializer>(combo: &u16, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&combo_name(*combo))
+}
+fn focus_policy_name<S: Serializer>(
+    policy: &FocusPolicy,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(policy.name())
+}
+
+//------------------------------------------ Loading ----------------------------------------
+
+//The configuration in use. Replaced as a whole when overlay.toml is reloaded.
+static CONFIG: LazyLock<RwLock<Arc<Config>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(Config::default())));
+
+///The configuration in use. Cheap, but don't keep it around: it gets replaced on reload.
+pub fn config() -> Arc<Config> {
+    CONFIG.read().unwrap().clone()
+}
+
+///config(), for code that must not block or panic, like the panic hook.
+pub fn try_config() -> Option<Arc<Config>> {
+    CONFIG.try_read().ok().map(|config| config.clone())
+}
+
+pub fn config_path() -> PathBuf {
+    data_dir().path.join(CONFIG_FILE)
+}
+
+pub struct LoadedConfig {
+    pub config: Config,
+    pub diagnostics: Vec<ConfigDiagnostic>,
+    //What happened to the file, logged once logging is up.
+    pub notes: Vec<String>,
+}
+
+///Reads overlay.toml, creating it (from keybinds.conf if there is one) when it doesn't exist.
+///Runs before logging is set up, so nothing is logged here, see install_config().
+pub fn load_config() -> LoadedConfig {
+    let data_dir = data_dir();
+    let mut notes = vec![format!(
+        "Data directory: {} ({}).",
+        data_dir.path.display(),
+        data_dir.source
+    )];
+    let path = config_path();
+    copy_legacy_config(&path, &mut notes);
+    if !path.exists() {
+        let mut config = Config::default();
+        let mut migrated = false;
+        match migrate::read_legacy_keybinds() {
+            Some(Ok(keybinds)) => {
+                config.keybinds = keybinds;
+                migrated = true;
+                notes.push(format!(
+                    "Keybinds migrated from {} to {}.",
+                    migrate::LEGACY_KEYBINDS_PATH,
+                    path.display()
+                ));
+            }
+            Some(Err(e)) => notes.push(format!(
+                "Could not migrate {}: {}",
+                migrate::LEGACY_KEYBINDS_PATH,
+                e
+            )),
+            None => (),
+        }
+        match write_config(&config) {
+            Ok(()) => {
+                notes.push(format!("Created {}.", path.display()));
+                if migrated {
+                    migrate::retire_legacy_keybinds(&mut notes);
+                }
+            }
+            Err(e) => notes.push(format!("Could not write {}: {}", path.display(), e)),
+        }
+        return LoadedConfig {
+            config,
+            diagnostics: Vec::new(),
+            notes,
+        };
+    }
+
+    match read_config() {
+        Ok((config, diagnostics)) => LoadedConfig {
+            config,
+            diagnostics,
+            notes,
+        },
+        Err(e) => {
+            notes.push(format!(
+                "Could not read {} ({}), using the default settings.",
+                path.display(),
+                e
+            ));
+            LoadedConfig {
+                config: Config::default(),
+                diagnostics: Vec::new(),
+                notes,
+            }
+        }
+    }
+}
+
+///Reports how loading went, makes the configuration current and applies it.
+///Called once at startup, after logging is set up.
+pub fn install_config(loaded: LoadedConfig) {
+    for note in &loaded.notes {
+        log::info!("{}", note);
+    }
+    report_diagnostics(&loaded.diagnostics);
+    let config = Arc::new(loaded.config);
+    *CONFIG.write().unwrap() = config.clone();
+    log_active_profile(&config);
+    apply_startup_settings(&config);
+    keybinds::load_keybinds(&config.keybinds);
+    start_config_watcher();
+}
+
+static RELOAD: Mutex<()> = Mutex::new(());
+
+///Reads overlay.toml again, swaps the new configuration in and tells the subsystems
+///whose settings changed. The current configuration is kept if the file can't be read.
+pub fn reload_config() {
+    //Held until the handlers ran, so a second reload can't diff against a configuration
+    //whose changes are still being applied, or swap its own in between.
+    let _reloading = RELOAD.lock().unwrap_or_else(|e| e.into_inner());
+    let (new, diagnostics) = match read_config() {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            log::error!(
+                "Could not reload {} ({}), keeping the current settings.",
+                CONFIG_FILE,
+                e
+            );
+            return;
+        }
+    };
+    report_diagnostics(&diagnostics);
+
+    let old = config();
+    let changes = diff(&*old, &new);
+    if changes.is_empty() {
+        log::info!("{} reloaded, nothing changed.", CONFIG_FILE);
+        return;
+    }
+    for change in &changes {
+        log::info!("Setting changed: {}", change);
+    }
+    let new = Arc::new(new);
+    *CONFIG.write().unwrap() = new.clone();
+    for subsystem in notify(HANDLERS, &affected(&changes), &old, &new) {
+        if let Some(message) = unhandled_message(subsystem) {
+            log::info!("{}", message);
+        }
+    }
+}
+
+fn read_config() -> Result<(Config, Vec<ConfigDiagnostic>), String> {
+    let text = fs::read_to_string(config_path()).map_err(|e| e.to_string())?;
+    Config::parse(&text, &HostProcess::current()).map_err(|e| e.to_string())
+}
+
+fn write_config(config: &Config) -> std::io::Result<()> {
+    fs::create_dir_all(&data_dir().path)?;
+    fs::write(config_path(), config.to_toml())
+}
+
+//overlay.toml used to always be in addons/LOADER_public. Copied over when the data directory
+//is somewhere else and doesn't have one yet, so the settings aren't lost.
+fn copy_legacy_config(path: &Path, notes: &mut Vec<String>) {
+    let legacy = Path::new(LEGACY_DATA_DIR).join(CONFIG_FILE);
+    if path.exists() || !legacy.exists() || data_dir().path == Path::new(LEGACY_DATA_DIR) {
+        return;
+    }
+    let copied = fs::create_dir_all(&data_dir().path).and_then(|_| fs::copy(&legacy, path));
+    notes.push(match copied {
+        Ok(_) => format!("Copied {} to {}.", legacy.display(), path.display()),
+        Err(e) => format!(
+            "Could not copy {} to {}: {}",
+            legacy.display(),
+            path.display(),
+            e
+        ),
+    });
+}
+
+//Logs the problems and shows them on the debug overlay.
+fn report_diagnostics(diagnostics: &[ConfigDiagnostic]) {
+    if diagnostics.is_empty() {
+        return;
+    }
+    for diagnostic in diagnostics {
+        log::warn!("overlay.toml {}", diagnostic);
+    }
+    log::warn!(
+        "overlay.toml has {} problem(s), the default is used for those settings.",
+        diagnostics.len()
+    );
+    show_log_overlay();
+}
+
+//Reloads the configuration whenever overlay.toml gets modified.
+fn start_config_watcher() {
+    spawn_worker("config watcher", || {
+        let path = config_path();
+        let mut last = file_version(&path);
+        while sleep_unless_stopping(WATCH_INTERVAL) {
+            let current = file_version(&path);
+            if current != last && current.is_some() {
+                log::info!("{} changed, reloading.", CONFIG_FILE);
+                std::thread::sleep(SETTLE_DELAY);
+                reload_config();
+            }
+            last = current;
+        }
+    });
+}
+
+//Modification time and size, enough to notice a save.
+fn file_version(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> (Config, Vec<String>) {
+        let (config, diagnostics) = Config::parse(text, &HostProcess::default()).unwrap();
+        (config, diagnostics.iter().map(|d| d.to_string()).collect())
+    }
+
+    //Only the fields the diagnostics are about, the messages come from toml for some.
+    fn fields(text: &str) -> Vec<String> {
+        let (_, diagnostics) = Config::parse(text, &HostProcess::default()).unwrap();
+        diagnostics.into_iter().map(|d| d.field).collect()
+    }
+
+    #[test]
+    fn default_round_trip() {
+        let default = Config::default();
+        let text = default.to_toml();
+        assert!(text.starts_with(HEADER));
+        assert_eq!(parse(&text), (default, Vec::new()));
+    }
+
+    #[test]
+    fn empty_file() {
+        assert_eq!(parse(""), (Config::default(), Vec::new()));
+    }
+
+    #[test]
+    fn not_toml() {
+        assert!(Config::parse("[logging", &HostProcess::default()).is_err());
+    }
+
+    #[test]
+    fn values_are_read() {
+        let (config, diagnostics) = parse(
+            r#"
+            [logging]
+            level = "info,mmf=trace"
+            keep_sessions = 5
+            file_format = "JSON"
+            [input]
+            focus_policy = "passive"
+            subscribe_address = "127.0.0.1:50153"
+            "#,
+        );
+        assert_eq!(diagnostics, Vec::<String>::new());
+        assert_eq!(config.logging.level.to_string(), "info,mmf=trace");
+        assert_eq!(config.logging.keep_sessions, 5);
+        assert_eq!(config.logging.file_format, LogFormat::Json);
+        assert_eq!(config.input.focus_policy, FocusPolicy::Passive);
+        assert_eq!(
+            config.input.subscribe_address,
+            SocketAddr::from(([127, 0, 0, 1], 50153))
+        );
+        //Untouched settings keep their default.
+        assert_eq!(config.paths, Config::default().paths);
+        assert_eq!(config.logging.keep_days, 30);
+    }
+
+    #[test]
+    fn unknown_sections_and_fields() {
+        let (config, diagnostics) = parse(
+            r#"
+            [loging]
+            level = "trace"
+            [logging]
+            keep_sessions = 3
+            keep_session = 4
+            [input]
+            raw_imput = true
+            "#,
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                "logging.keep_session: unknown setting, ignored",
+                "input.raw_imput: unknown setting, ignored",
+                "loging: unknown section, ignored",
+            ]
+        );
+        assert_eq!(config.logging.keep_sessions, 3);
+        assert!(!config.input.raw_input);
+    }
+
+    #[test]
+    fn wrong_types_keep_the_default() {
+        let text = r#"
+            rendering = 3
+            [logging]
+            stdout = "yes"
+            level = "loud"
+            keep_days = -1
+            max_file_size_mb = 20
+            [input]
+            raw_input = 1
+            legacy_address = "nowhere"
+            focus_policy = "sometimes"
+            gamepad_toggle_combo = "LB+Select"
+            gamepad_deadzone = 1.5
+            gamepad_speed = "fast"
+        "#;
+        assert_eq!(
+            fields(text),
+            [
+                "logging.level",
+                "logging.stdout",
+                "logging.keep_days",
+                "rendering",
+                "input.legacy_address",
+                "input.raw_input",
+                "input.focus_policy",
+                "input.gamepad_toggle_combo",
+                "input.gamepad_deadzone",
+                "input.gamepad_speed",
+            ]
+        );
+        let (config, diagnostics) = parse(text);
+        let default = Config::default();
+        assert_eq!(config.logging.max_file_size_mb, 20);
+        assert_eq!(config.logging.stdout, default.logging.stdout);
+        assert_eq!(config.rendering, default.rendering);
+        assert_eq!(config.input, default.input);
+        assert!(diagnostics.contains(&"rendering: expected a table, found integer".to_string()));
+        assert!(
+            diagnostics.contains(
+                &"logging.level: \"loud\" is not one of off, error, warn, info, debug, trace"
+                    .to_string()
+            )
+        );
+        assert!(diagnostics.contains(
+            &"input.focus_policy: \"sometimes\" is not one of aggressive, passive, never_capture, capture_while_interactive"
+                .to_string()
+        ));
+        assert!(
+            diagnostics.contains(&"input.gamepad_deadzone: 1.5 is not between 0 and 1".to_string())
+        );
+    }
+
+    #[test]
+    fn empty_strings() {
+        let text = r#"
+            [paths]
+            recordings = " "
+            [overlay]
+            process_name = ""
+        "#;
+        let (config, diagnostics) = parse(text);
+        assert_eq!(
+            diagnostics,
+            [
+                "paths.recordings: must not be empty",
+                "overlay.process_name: must not be empty",
+            ]
+        );
+        assert_eq!(config.paths.recordings, PathBuf::from("recordings"));
+    }
+
+    #[test]
+    fn same_input_addresses() {
+        let (config, diagnostics) = parse(
+            r#"
+            [input]
+            legacy_address = "127.0.0.1:49153"
+            "#,
+        );
+        assert_eq!(
+            diagnostics,
+            ["input.subscribe_address: must differ from legacy_address"]
+        );
+        assert_eq!(config.input.legacy_address, config.input.subscribe_address);
+    }
+
+    #[test]
+    fn keybinds() {
+        let (config, diagnostics) = parse(
+            r#"
+            [[keybinds]]
+            keys = "Ctrl+Alt+P"
+            action = "toggle_debug_overlay"
+
+            [[keybinds]]
+            keys = "F6@hold=1000"
+            action = "restart_blish"
+            scope = "overlay_connected"
+
+            [[keybinds]]
+            action = "reload_config"
+
+            [[keybinds]]
+            keys = "F7"
+
+            [[keybinds]]
+            keys = "F8"
+            action = " "
+            actions = "typo"
+
+            [[keybinds]]
+            keys = 8
+            action = "reload_config"
+            "#,
+        );
+        assert_eq!(
+            config.keybinds,
+            [
+                KeybindEntry {
+                    keys: "Ctrl+Alt+P".to_string(),
+                    action: "toggle_debug_overlay".to_string(),
+                    scope: String::new(),
+                },
+                KeybindEntry {
+                    keys: "F6@hold=1000".to_string(),
+                    action: "restart_blish".to_string(),
+                    scope: "overlay_connected".to_string(),
+                },
+            ]
+        );
+        let fields: Vec<&str> = diagnostics
+            .iter()
+            .map(|d| d.split(':').next().unwrap())
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                "keybinds[2].keys",
+                "keybinds[3].action",
+                "keybinds[4].action",
+                "keybinds[4].actions",
+                "keybinds[5].keys",
+            ]
+        );
+        assert_eq!(diagnostics[0], "keybinds[2].keys: missing, keybind ignored");
+    }
+
+    #[test]
+    fn keybinds_not_an_array() {
+        let (config, diagnostics) = parse("keybinds = \"F5 reload_config\"");
+        assert_eq!(
+            diagnostics,
+            ["keybinds: expected an array of tables, found string"]
+        );
+        assert_eq!(config.keybinds, Config::default().keybinds);
+
+        let (config, diagnostics) = parse("keybinds = [1]");
+        assert_eq!(
+            diagnostics,
+            ["keybinds[0]: expected a table, found integer"]
+        );
+        assert!(config.keybinds.is_empty());
+    }
+
+    #[test]
+    fn profiles() {
+        let (config, diagnostics) = parse(
+            r#"
+            [input]
+            raw_input = true
+
+            [[profiles]]
+            executable = "Gw2-64.exe"
+            [profiles.input]
+            legacy_address = "127.0.0.1:50152"
+
+            [[profiles]]
+            name = "No executable"
+
+            [[profiles]]
+            executable = "Other.exe"
+            executible = "typo"
+            "#,
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                "profiles[1].executable: missing, profile ignored",
+                "profiles[2].executible: unknown setting, ignored",
+            ]
+        );
+        assert_eq!(config.profiles.len(), 2);
+        let profile = &config.profiles[0];
+        assert_eq!(profile.name, "Gw2-64.exe");
+        assert_eq!(
+            profile.input.legacy_address,
+            SocketAddr::from(([127, 0, 0, 1], 50152))
+        );
+        //Sections a profile leaves out come from the top level.
+        assert!(profile.input.raw_input);
+        assert_eq!(profile.overlay, config.overlay);
+        //Nothing matches the empty host.
+        assert_eq!(config.active_profile, None);
+        assert!(config.input.raw_input);
+        assert_eq!(
+            config.input.legacy_address,
+            Config::default().input.legacy_address
+        );
+    }
+}
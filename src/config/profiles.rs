@@ -0,0 +1,176 @@
+use crate::utils::get_mainwindow_classes;
+
+use super::ProfileConfig;
+
+//What profiles are matched against.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostProcess {
+    //Full path of the executable the DLL was loaded into.
+    pub executable: String,
+    //Classes of its main window candidates, empty until the game created its window.
+    pub window_classes: Vec<String>,
+}
+
+impl HostProcess {
+    pub fn current() -> HostProcess {
+        HostProcess {
+            executable: std::env::current_exe()
+                .map(|path| path.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            window_classes: get_mainwindow_classes(),
+        }
+    }
+
+    pub fn executable_name(&self) -> &str {
+        executable_name(&self.executable)
+    }
+}
+
+//Paths may use either separator under Wine.
+fn executable_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path).trim()
+}
+
+///Whether a profile applies to this process.
+///Executable names and window classes are compared case insensitively, like Windows does.
+pub fn matches(profile: &ProfileConfig, host: &HostProcess) -> bool {
+    let executable = executable_name(&profile.executable);
+    if executable.is_empty() || !executable.eq_ignore_ascii_case(host.executable_name()) {
+        return false;
+    }
+    match &profile.window_class {
+        Some(class) => host
+            .window_classes
+            .iter()
+            .any(|candidate| candidate.eq_ignore_ascii_case(class)),
+        None => true,
+    }
+}
+
+///Index of the profile to use: profiles with a window class win over the ones without,
+///then the first one in the file.
+pub fn select(profiles: &[ProfileConfig], host: &HostProcess) -> Option<usize> {
+    profiles
+        .iter()
+        .enumerate()
+        .filter(|(_, profile)| matches(profile, host))
+        .min_by_key(|(_, profile)| profile.window_class.is_none())
+        .map(|(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Config;
+    use super::*;
+
+    fn profile(name: &str, executable: &str, window_class: Option<&str>) -> ProfileConfig {
+        let defaults = Config::default();
+        ProfileConfig {
+            name: name.to_string(),
+            executable: executable.to_string(),
+            window_class: window_class.map(str::to_string),
+            overlay: defaults.overlay,
+            paths: defaults.paths,
+            swapchain: defaults.swapchain,
+            input: defaults.input,
+        }
+    }
+
+    fn host(executable: &str, window_classes: &[&str]) -> HostProcess {
+        HostProcess {
+            executable: executable.to_string(),
+            window_classes: window_classes
+                .iter()
+                .map(|class| class.to_string())
+                .collect(),
+        }
+    }
+
+    const GW2: &str = "C:\\Program Files\\Guild Wars 2\\Gw2-64.exe";
+
+    #[test]
+    fn executable_names() {
+        let cases = [
+            (GW2, "Gw2-64.exe"),
+            ("/home/deck/Games/gw2/Gw2-64.exe", "Gw2-64.exe"),
+            ("Z:\\home/deck/Gw2-64.exe", "Gw2-64.exe"),
+            ("Gw2-64.exe", "Gw2-64.exe"),
+            ("  Gw2-64.exe ", "Gw2-64.exe"),
+            ("C:\\Games\\", ""),
+            ("", ""),
+        ];
+        for (path, name) in cases {
+            assert_eq!(executable_name(path), name, "{}", path);
+            assert_eq!(host(path, &[]).executable_name(), name);
+        }
+    }
+
+    #[test]
+    fn executable_case_doesnt_matter() {
+        let host = host(GW2, &[]);
+        for executable in ["Gw2-64.exe", "GW2-64.EXE", "gw2-64.exe"] {
+            assert!(matches(&profile("gw2", executable, None), &host));
+        }
+        assert!(!matches(&profile("gw2", "Gw2.exe", None), &host));
+        assert!(!matches(&profile("gw2", "Gw2-64", None), &host));
+    }
+
+    #[test]
+    fn profile_executable_may_be_a_path() {
+        let host = host("/opt/gw2/Gw2-64.exe", &[]);
+        assert!(matches(
+            &profile("gw2", "D:\\Other\\Gw2-64.exe", None),
+            &host
+        ));
+        assert!(matches(&profile("gw2", "./Gw2-64.exe", None), &host));
+    }
+
+    #[test]
+    fn empty_executable_never_matches() {
+        assert!(!matches(&profile("any", "", None), &host("", &[])));
+        assert!(!matches(&profile("any", "  ", None), &host(GW2, &[])));
+        assert!(!matches(&profile("any", "C:\\", None), &host(GW2, &[])));
+    }
+
+    #[test]
+    fn window_class_must_be_present() {
+        let profile = profile("dx11", "Gw2-64.exe", Some("arenanet_dx_window_class"));
+        assert!(matches(
+            &profile,
+            &host(GW2, &["Other", "ArenaNet_Dx_Window_Class"])
+        ));
+        assert!(!matches(&profile, &host(GW2, &["Other"])));
+        //The window wasn't created yet.
+        assert!(!matches(&profile, &host(GW2, &[])));
+    }
+
+    #[test]
+    fn select_prefers_window_class_matches() {
+        let profiles = [
+            profile("other game", "Blish HUD.exe", None),
+            profile("any window", "Gw2-64.exe", None),
+            profile("launcher", "Gw2-64.exe", Some("ArenaNet_Launcher")),
+            profile("game", "Gw2-64.exe", Some("ArenaNet_Dx_Window_Class")),
+            profile("game again", "Gw2-64.exe", Some("ArenaNet_Dx_Window_Class")),
+        ];
+        let game = host(GW2, &["ArenaNet_Dx_Window_Class"]);
+        assert_eq!(select(&profiles, &game), Some(3));
+        let launcher = host(GW2, &["ArenaNet_Launcher", "ArenaNet_Dx_Window_Class"]);
+        assert_eq!(select(&profiles, &launcher), Some(2));
+        let starting = host(GW2, &[]);
+        assert_eq!(select(&profiles, &starting), Some(1));
+        let blish = host("C:\\Blish\\Blish HUD.exe", &["ArenaNet_Dx_Window_Class"]);
+        assert_eq!(select(&profiles, &blish), Some(0));
+    }
+
+    #[test]
+    fn select_takes_the_first_one_otherwise() {
+        let profiles = [
+            profile("first", "gw2-64.exe", None),
+            profile("second", "GW2-64.EXE", None),
+        ];
+        assert_eq!(select(&profiles, &host(GW2, &[])), Some(0));
+        assert_eq!(select(&profiles, &host("notepad.exe", &[])), None);
+        assert_eq!(select(&[], &host(GW2, &[])), None);
+    }
+}
@@ -0,0 +1,309 @@
+//Requests an overlay can send to the DLL on the subscription socket.
+//Every request is [id, sequence: u32 LE, payload...] and is answered with an ack
+//carrying the same sequence number.
+pub mod request_id {
+    //[id, seq]
+    pub const CAPTURE_KEYBOARD: u8 = 0xE0;
+    //[id, seq]
+    pub const RELEASE_KEYBOARD: u8 = 0xE1;
+    //[id, seq, shape: u8] See CursorShape.
+    pub const SET_CURSOR: u8 = 0xE2;
+    //[id, seq, hidden: u8]
+    pub const HIDE_OVERLAY: u8 = 0xE3;
+    //[id, seq]
+    pub const REQUEST_REDRAW: u8 = 0xE4;
+    //[id, seq] Acked once the eject started, the DLL is gone shortly after.
+    pub const EJECT: u8 = 0xE5;
+}
+
+//[id, seq: u32 LE, request id: u8, status: u8]
+pub const ACK: u8 = 0xE8;
+
+pub fn is_control_packet(data: &[u8]) -> bool {
+    matches!(data.first(), Some(0xE0..=0xE7))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AckStatus {
+    Ok = 0,
+    //The request was understood but could not be applied (eg. no game window yet).
+    Failed = 1,
+    //Unknown request or bad payload.
+    Malformed = 2,
+    //Only registered subscribers can send requests.
+    NotRegistered = 3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CursorShape {
+    //Stop overriding, let the game pick its cursor.
+    Default = 0,
+    Arrow = 1,
+    Hand = 2,
+    IBeam = 3,
+    Wait = 4,
+    Cross = 5,
+    SizeAll = 6,
+    No = 7,
+}
+
+impl CursorShape {
+    pub fn from_u8(value: u8) -> Option<CursorShape> {
+        Some(match value {
+            0 => CursorShape::Default,
+            1 => CursorShape::Arrow,
+            2 => CursorShape::Hand,
+            3 => CursorShape::IBeam,
+            4 => CursorShape::Wait,
+            5 => CursorShape::Cross,
+            6 => CursorShape::SizeAll,
+            7 => CursorShape::No,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlRequest {
+    CaptureKeyboard,
+    ReleaseKeyboard,
+    SetCursor(CursorShape),
+    HideOverlay(bool),
+    RequestRedraw,
+    Eject,
+}
+
+impl ControlRequest {
+    ///Parses a control packet into (sequence, request).
+    ///Returns Err(sequence) if the header is valid but the request isn't, so it can still be acked.
+    pub fn parse(data: &[u8]) -> Result<(u32, ControlRequest), Option<u32>> {
+        let id = *data.first().ok_or(None)?;
+        let seq = data
+            .get(1..5)
+            .and_then(|b| b.try_into().ok())
+            .map(u32::from_le_bytes)
+            .ok_or(None)?;
+        let payload = &data[5..];
+
+        let request = match id {
+            request_id::CAPTURE_KEYBOARD => ControlRequest::CaptureKeyboard,
+            request_id::RELEASE_KEYBOARD => ControlRequest::ReleaseKeyboard,
+            request_id::SET_CURSOR => {
+                let shape = payload.first().copied().and_then(CursorShape::from_u8);
+                ControlRequest::SetCursor(shape.ok_or(Some(seq))?)
+            }
+            request_id::HIDE_OVERLAY => {
+                ControlRequest::HideOverlay(*payload.first().ok_or(Some(seq))? != 0)
+            }
+            request_id::REQUEST_REDRAW => ControlRequest::RequestRedraw,
+            request_id::EJECT => ControlRequest::Eject,
+            _ => return Err(Some(seq)),
+        };
+        Ok((seq, request))
+    }
+}
+
+pub fn ack_packet(seq: u32, request: u8, status: AckStatus) -> [u8; 7] {
+    let mut packet = [0u8; 7];
+    packet[0] = ACK;
+    packet[1..5].copy_from_slice(&seq.to_le_bytes());
+    packet[5] = request;
+    packet[6] = status as u8;
+    packet
+}
+
+///Everything a control request can do to the game window.
+///Each method returns false if it could not be applied.
+pub trait WindowBackend {
+    fn capture_keyboard(&self) -> bool;
+    fn release_keyboard(&self) -> bool;
+    fn set_cursor(&self, shape: CursorShape) -> bool;
+    fn set_overlay_hidden(&self, hidden: bool) -> bool;
+    fn request_redraw(&self) -> bool;
+    fn eject(&self) -> bool;
+}
+
+pub fn dispatch(backend: &impl WindowBackend, request: ControlRequest) -> AckStatus {
+    let applied = match request {
+        ControlRequest::CaptureKeyboard => backend.capture_keyboard(),
+        ControlRequest::ReleaseKeyboard => backend.release_keyboard(),
+        ControlRequest::SetCursor(shape) => backend.set_cursor(shape),
+        ControlRequest::HideOverlay(hidden) => backend.set_overlay_hidden(hidden),
+        ControlRequest::RequestRedraw => backend.request_redraw(),
+        ControlRequest::Eject => backend.eject(),
+    };
+    if applied {
+        AckStatus::Ok
+    } else {
+        AckStatus::Failed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    //Records every call, and applies them or not.
+    struct MockBackend {
+        applies: bool,
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl MockBackend {
+        fn new(applies: bool) -> Self {
+            Self {
+                applies,
+                calls: RefCell::new(Vec::new()),
+            }
+        }
+        fn call(&self, call: String) -> bool {
+            self.calls.borrow_mut().push(call);
+            self.applies
+        }
+    }
+
+    impl WindowBackend for MockBackend {
+        fn capture_keyboard(&self) -> bool {
+            self.call("capture_keyboard".to_string())
+        }
+        fn release_keyboard(&self) -> bool {
+            self.call("release_keyboard".to_string())
+        }
+        fn set_cursor(&self, shape: CursorShape) -> bool {
+            self.call(format!("set_cursor {:?}", shape))
+        }
+        fn set_overlay_hidden(&self, hidden: bool) -> bool {
+            self.call(format!("set_overlay_hidden {}", hidden))
+        }
+        fn request_redraw(&self) -> bool {
+            self.call("request_redraw".to_string())
+        }
+        fn eject(&self) -> bool {
+            self.call("eject".to_string())
+        }
+    }
+
+    fn packet(id: u8, seq: u32, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![id];
+        packet.extend_from_slice(&seq.to_le_bytes());
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    //Parses and dispatches a packet like the subscription thread does.
+    fn handle(backend: &MockBackend, data: &[u8]) -> Result<(u32, AckStatus), Option<u32>> {
+        let (seq, request) = ControlRequest::parse(data)?;
+        Ok((seq, dispatch(backend, request)))
+    }
+
+    #[test]
+    fn dispatches_every_request() {
+        let cases: [(Vec<u8>, &str); 8] = [
+            (
+                packet(request_id::CAPTURE_KEYBOARD, 1, &[]),
+                "capture_keyboard",
+            ),
+            (
+                packet(request_id::RELEASE_KEYBOARD, 2, &[]),
+                "release_keyboard",
+            ),
+            (
+                packet(request_id::SET_CURSOR, 3, &[0]),
+                "set_cursor Default",
+            ),
+            (packet(request_id::SET_CURSOR, 4, &[2]), "set_cursor Hand"),
+            (
+                packet(request_id::HIDE_OVERLAY, 5, &[1]),
+                "set_overlay_hidden true",
+            ),
+            (
+                packet(request_id::HIDE_OVERLAY, 6, &[0]),
+                "set_overlay_hidden false",
+            ),
+            (packet(request_id::REQUEST_REDRAW, 7, &[]), "request_redraw"),
+            (packet(request_id::EJECT, 8, &[]), "eject"),
+        ];
+        for (data, call) in cases {
+            let backend = MockBackend::new(true);
+            let seq = u32::from_le_bytes(data[1..5].try_into().unwrap());
+            assert_eq!(
+                handle(&backend, &data),
+                Ok((seq, AckStatus::Ok)),
+                "{}",
+                call
+            );
+            assert_eq!(*backend.calls.borrow(), vec![call.to_string()]);
+        }
+    }
+
+    #[test]
+    fn failed_requests_are_acked_as_failed() {
+        let backend = MockBackend::new(false);
+        assert_eq!(
+            handle(&backend, &packet(request_id::CAPTURE_KEYBOARD, 9, &[])),
+            Ok((9, AckStatus::Failed))
+        );
+        //The eject couldn't start, eg. loaded by Nexus.
+        assert_eq!(
+            handle(&backend, &packet(request_id::EJECT, 10, &[])),
+            Ok((10, AckStatus::Failed))
+        );
+    }
+
+    #[test]
+    fn eject_ignores_payload() {
+        let backend = MockBackend::new(true);
+        assert_eq!(
+            handle(&backend, &packet(request_id::EJECT, 11, &[0xFF])),
+            Ok((11, AckStatus::Ok))
+        );
+        assert_eq!(*backend.calls.borrow(), vec!["eject".to_string()]);
+    }
+
+    #[test]
+    fn malformed_packets() {
+        let backend = MockBackend::new(true);
+        //No usable header: nothing to ack.
+        assert_eq!(handle(&backend, &[]), Err(None));
+        assert_eq!(
+            handle(&backend, &[request_id::REQUEST_REDRAW, 1, 0]),
+            Err(None)
+        );
+        //Header fine, request not: acked as malformed with its sequence.
+        assert_eq!(
+            handle(&backend, &packet(request_id::SET_CURSOR, 12, &[])),
+            Err(Some(12))
+        );
+        assert_eq!(
+            handle(&backend, &packet(request_id::SET_CURSOR, 13, &[8])),
+            Err(Some(13))
+        );
+        assert_eq!(
+            handle(&backend, &packet(request_id::HIDE_OVERLAY, 14, &[])),
+            Err(Some(14))
+        );
+        assert_eq!(handle(&backend, &packet(0xE7, 15, &[])), Err(Some(15)));
+        assert!(backend.calls.borrow().is_empty());
+    }
+
+    #[test]
+    fn control_packet_range() {
+        assert!(is_control_packet(&[request_id::CAPTURE_KEYBOARD]));
+        assert!(is_control_packet(&[request_id::REQUEST_REDRAW]));
+        assert!(is_control_packet(&[0xE7]));
+        //Acks and subscription packets aren't requests.
+        assert!(!is_control_packet(&[ACK]));
+        assert!(!is_control_packet(&[0xF0]));
+        assert!(!is_control_packet(&[]));
+    }
+
+    #[test]
+    fn ack_layout() {
+        let ack = ack_packet(0x0102_0304, request_id::HIDE_OVERLAY, AckStatus::Failed);
+        assert_eq!(ack, [ACK, 4, 3, 2, 1, request_id::HIDE_OVERLAY, 1]);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::ui::OverlayGeometry;
+
+//Client area of the game window, in the same space as the coordinates of mouse messages.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClientGeometry {
+    pub width: u32,
+    pub height: u32,
+    //Physical pixels per client pixel. Above 1.0 when Windows DPI-virtualizes the window,
+    //in which case mouse messages are in logical pixels but the backbuffer is not.
+    pub dpi_scale: f32,
+}
+
+impl ClientGeometry {
+    pub const EMPTY: ClientGeometry = ClientGeometry {
+        width: 0,
+        height: 0,
+        dpi_scale: 1.0,
+    };
+}
+
+///Translates client coordinates (from lparam) into overlay texture coordinates.
+///
+///client -> backbuffer: scaled by backbuffer / client size. If the backbuffer isn't known yet
+///(nothing rendered), it is assumed to be the client area in physical pixels.
+///backbuffer -> texture: relative to the viewport the overlay is stretched over, then scaled
+///to the texture size. Points outside of the viewport end up outside of the texture.
+///
+///Returns the coordinates untouched if the client area is unknown.
+pub fn client_to_overlay(
+    x: i32,
+    y: i32,
+    client: &ClientGeometry,
+    overlay: &OverlayGeometry,
+) -> (i32, i32) {
+    if client.width == 0 || client.height == 0 {
+        return (x, y);
+    }
+    let client_w = client.width as f32;
+    let client_h = client.height as f32;
+
+    //Client -> backbuffer
+    let (backbuffer_w, backbuffer_h) =
+        if overlay.backbuffer_width != 0 && overlay.backbuffer_height != 0 {
+            (
+                overlay.backbuffer_width as f32,
+                overlay.backbuffer_height as f32,
+            )
+        } else {
+            (client_w * client.dpi_scale, client_h * client.dpi_scale)
+        };
+    let bx = x as f32 * backbuffer_w / client_w;
+    let by = y as f32 * backbuffer_h / client_h;
+
+    //Backbuffer -> viewport. An empty viewport means the whole backbuffer.
+    let (vx, vy, vw, vh) = if overlay.viewport_width > 0.0 && overlay.viewport_height > 0.0 {
+        (
+            overlay.viewport_x,
+            overlay.viewport_y,
+            overlay.viewport_width,
+            overlay.viewport_height,
+        )
+    } else {
+        (0.0, 0.0, backbuffer_w, backbuffer_h)
+    };
+
+    //Viewport -> texture. An unknown texture size means one texel per viewport pixel.
+    let (tw, th) = if overlay.texture_width != 0 && overlay.texture_height != 0 {
+        (overlay.texture_width as f32, overlay.texture_height as f32)
+    } else {
+        (vw, vh)
+    };
+
+    let tx = (bx - vx) * tw / vw;
+    let ty = (by - vy) * th / vh;
+    (tx.floor() as i32, ty.floor() as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(width: u32, height: u32, dpi_scale: f32) -> ClientGeometry {
+        ClientGeometry {
+            width,
+            height,
+            dpi_scale,
+        }
+    }
+
+    //Backbuffer of that size, overlay stretched over the viewport.
+    fn overlay(
+        backbuffer: (u32, u32),
+        viewport: (f32, f32, f32, f32),
+        texture: (u32, u32),
+    ) -> OverlayGeometry {
+        OverlayGeometry {
+            backbuffer_width: backbuffer.0,
+            backbuffer_height: backbuffer.1,
+            viewport_x: viewport.0,
+            viewport_y: viewport.1,
+            viewport_width: viewport.2,
+            viewport_height: viewport.3,
+            texture_width: texture.0,
+            texture_height: texture.1,
+        }
+    }
+
+    #[test]
+    fn unknown_client_is_untouched() {
+        let overlay = overlay((1920, 1080), (0.0, 0.0, 1920.0, 1080.0), (1920, 1080));
+        assert_eq!(
+            client_to_overlay(12, 34, &ClientGeometry::EMPTY, &overlay),
+            (12, 34)
+        );
+    }
+
+    #[test]
+    fn nothing_rendered_yet() {
+        let empty = OverlayGeometry::EMPTY;
+        assert_eq!(
+            client_to_overlay(400, 300, &client(800, 600, 1.0), &empty),
+            (400, 300)
+        );
+        //DPI-virtualized: the backbuffer is assumed to be in physical pixels.
+        assert_eq!(
+            client_to_overlay(400, 300, &client(800, 600, 1.5), &empty),
+            (600, 450)
+        );
+    }
+
+    #[test]
+    fn identity() {
+        let overlay = overlay((1920, 1080), (0.0, 0.0, 1920.0, 1080.0), (1920, 1080));
+        let client = client(1920, 1080, 1.0);
+        for (x, y) in [(0, 0), (1, 1), (960, 540), (1919, 1079)] {
+            assert_eq!(client_to_overlay(x, y, &client, &overlay), (x, y));
+        }
+    }
+
+    #[test]
+    fn backbuffer_larger_than_client() {
+        let overlay = overlay((2560, 1440), (0.0, 0.0, 2560.0, 1440.0), (2560, 1440));
+        let client = client(1280, 720, 2.0);
+        assert_eq!(client_to_overlay(640, 360, &client, &overlay), (1280, 720));
+        assert_eq!(
+            client_to_overlay(1279, 719, &client, &overlay),
+            (2558, 1438)
+        );
+    }
+
+    #[test]
+    fn scaled_and_offset_viewport() {
+        //Texture twice the size of the viewport it is drawn in, at (100, 50).
+        let overlay = overlay((1920, 1080), (100.0, 50.0, 960.0, 540.0), (1920, 1080));
+        let client = client(1920, 1080, 1.0);
+        assert_eq!(client_to_overlay(100, 50, &client, &overlay), (0, 0));
+        assert_eq!(client_to_overlay(580, 320, &client, &overlay), (960, 540));
+        assert_eq!(
+            client_to_overlay(1059, 589, &client, &overlay),
+            (1918, 1078)
+        );
+        //Outside of the viewport is outside of the texture.
+        assert_eq!(client_to_overlay(99, 49, &client, &overlay), (-2, -2));
+        assert_eq!(
+            client_to_overlay(1060, 590, &client, &overlay),
+            (1920, 1080)
+        );
+    }
+
+    #[test]
+    fn unknown_texture_size_is_one_texel_per_pixel() {
+        let overlay = overlay((1920, 1080), (100.0, 50.0, 960.0, 540.0), (0, 0));
+        let client = client(1920, 1080, 1.0);
+        assert_eq!(client_to_overlay(580, 320, &client, &overlay), (480, 270));
+    }
+
+    #[test]
+    fn letterbox_bars() {
+        //A 1920x800 texture centered in a 1920x1080 backbuffer, bars above and below.
+        let overlay = overlay((1920, 1080), (0.0, 140.0, 1920.0, 800.0), (1920, 800));
+        let client = client(1920, 1080, 1.0);
+        assert_eq!(client_to_overlay(10, 140, &client, &overlay), (10, 0));
+        assert_eq!(client_to_overlay(10, 939, &client, &overlay), (10, 799));
+        let (_, top) = client_to_overlay(10, 139, &client, &overlay);
+        let (_, bottom) = client_to_overlay(10, 940, &client, &overlay);
+        assert!(top < 0 && bottom >= 800);
+    }
+
+    #[test]
+    fn pillarbox_bars_with_scaled_client() {
+        //A 4:3 texture in a 16:9 backbuffer, the window at half the backbuffer size.
+        let overlay = overlay((1920, 1080), (240.0, 0.0, 1440.0, 1080.0), (1440, 1080));
+        let client = client(960, 540, 1.0);
+        assert_eq!(client_to_overlay(120, 0, &client, &overlay), (0, 0));
+        assert_eq!(client_to_overlay(480, 270, &client, &overlay), (720, 540));
+        assert!(client_to_overlay(119, 270, &client, &overlay).0 < 0);
+        assert!(client_to_overlay(840, 270, &client, &overlay).0 >= 1440);
+    }
+}
@@ -0,0 +1,207 @@
+//How hard the DLL fights for the game window's focus and mouse capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum FocusPolicy {
+    //Brings the window to the foreground, focuses it and captures the mouse whenever it gets activated.
+    Aggressive = 0,
+    //Captures the mouse when the window gets activated, but never steals focus.
+    Passive = 1,
+    //Leaves focus and capture entirely to the game.
+    NeverCapture = 2,
+    //Only captures the mouse while the overlay is interactive (eg. it captured the keyboard).
+    CaptureWhileInteractive = 3,
+}
+
+impl FocusPolicy {
+    pub const ALL: [FocusPolicy; 4] = [
+        FocusPolicy::Aggressive,
+        FocusPolicy::Passive,
+        FocusPolicy::NeverCapture,
+        FocusPolicy::CaptureWhileInteractive,
+    ];
+
+    pub fn from_u8(value: u8) -> Option<FocusPolicy> {
+        Self::ALL.get(value as usize).copied()
+    }
+
+    //Same order as ALL.
+    pub const NAMES: [&'static str; 4] = [
+        "aggressive",
+        "passive",
+        "never_capture",
+        "capture_while_interactive",
+    ];
+
+    pub fn name(self) -> &'static str {
+        Self::NAMES[self as usize]
+    }
+
+    pub fn from_name(name: &str) -> Option<FocusPolicy> {
+        Self::ALL.into_iter().find(|p| p.name() == name)
+    }
+
+    pub fn next(self) -> FocusPolicy {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocusEvent {
+    SetFocus,
+    KillFocus,
+    //WM_ACTIVATE / WM_ACTIVATEAPP, true when the window becomes active.
+    Activate(bool),
+    //The overlay became interactive or stopped being interactive.
+    InteractiveChanged { window_active: bool },
+}
+
+//What has to be done in response to a focus event. Applied in that order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FocusActions {
+    pub set_foreground: bool,
+    pub set_focus: bool,
+    pub set_capture: bool,
+    pub release_capture: bool,
+}
+
+impl FocusActions {
+    const NONE: FocusActions = FocusActions {
+        set_foreground: false,
+        set_focus: false,
+        set_capture: false,
+        release_capture: false,
+    };
+    const RELEASE: FocusActions = FocusActions {
+        release_capture: true,
+        ..Self::NONE
+    };
+    const CAPTURE: FocusActions = FocusActions {
+        set_capture: true,
+        ..Self::NONE
+    };
+    const GRAB: FocusActions = FocusActions {
+        set_foreground: true,
+        set_focus: true,
+        set_capture: true,
+        release_capture: false,
+    };
+}
+
+///Decides what to do with a focus event. Pure so every policy can be reasoned about in isolation.
+pub fn decide(policy: FocusPolicy, event: FocusEvent, overlay_interactive: bool) -> FocusActions {
+    let gained = match event {
+        FocusEvent::SetFocus | FocusEvent::Activate(true) => true,
+        FocusEvent::KillFocus | FocusEvent::Activate(false) => false,
+        FocusEvent::InteractiveChanged { window_active } => {
+            return match policy {
+                FocusPolicy::CaptureWhileInteractive if window_active && overlay_interactive => {
+                    FocusActions::CAPTURE
+                }
+                FocusPolicy::CaptureWhileInteractive => FocusActions::RELEASE,
+                _ => FocusActions::NONE,
+            };
+        }
+    };
+
+    //Whatever the policy, never keep the capture of a window that lost focus.
+    if !gained {
+        return match policy {
+            FocusPolicy::NeverCapture => FocusActions::NONE,
+            _ => FocusActions::RELEASE,
+        };
+    }
+
+    match policy {
+        FocusPolicy::Aggressive => FocusActions::GRAB,
+        FocusPolicy::Passive => FocusActions::CAPTURE,
+        FocusPolicy::NeverCapture => FocusActions::NONE,
+        FocusPolicy::CaptureWhileInteractive if overlay_interactive => FocusActions::CAPTURE,
+        FocusPolicy::CaptureWhileInteractive => FocusActions::NONE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const N: FocusActions = FocusActions::NONE;
+    const R: FocusActions = FocusActions::RELEASE;
+    const C: FocusActions = FocusActions::CAPTURE;
+    const G: FocusActions = FocusActions::GRAB;
+
+    #[test]
+    fn decide_table() {
+        use FocusEvent::*;
+        use FocusPolicy::*;
+        let active = InteractiveChanged {
+            window_active: true,
+        };
+        let inactive = InteractiveChanged {
+            window_active: false,
+        };
+        //Policy, event, then what to do while the overlay is not interactive / interactive.
+        let table = [
+            (Aggressive, SetFocus, G, G),
+            (Aggressive, Activate(true), G, G),
+            (Aggressive, KillFocus, R, R),
+            (Aggressive, Activate(false), R, R),
+            (Aggressive, active, N, N),
+            (Aggressive, inactive, N, N),
+            (Passive, SetFocus, C, C),
+            (Passive, Activate(true), C, C),
+            (Passive, KillFocus, R, R),
+            (Passive, Activate(false), R, R),
+            (Passive, active, N, N),
+            (Passive, inactive, N, N),
+            (NeverCapture, SetFocus, N, N),
+            (NeverCapture, Activate(true), N, N),
+            (NeverCapture, KillFocus, N, N),
+            (NeverCapture, Activate(false), N, N),
+            (NeverCapture, active, N, N),
+            (NeverCapture, inactive, N, N),
+            (CaptureWhileInteractive, SetFocus, N, C),
+            (CaptureWhileInteractive, Activate(true), N, C),
+            (CaptureWhileInteractive, KillFocus, R, R),
+            (CaptureWhileInteractive, Activate(false), R, R),
+            (CaptureWhileInteractive, active, R, C),
+            (CaptureWhileInteractive, inactive, R, R),
+        ];
+        assert_eq!(table.len(), FocusPolicy::ALL.len() * 6);
+        for (policy, event, idle, interactive) in table {
+            assert_eq!(
+                decide(policy, event, false),
+                idle,
+                "{:?} {:?} not interactive",
+                policy,
+                event
+            );
+            assert_eq!(
+                decide(policy, event, true),
+                interactive,
+                "{:?} {:?} interactive",
+                policy,
+                event
+            );
+        }
+    }
+
+    #[test]
+    fn names() {
+        for (index, policy) in FocusPolicy::ALL.into_iter().enumerate() {
+            assert_eq!(FocusPolicy::from_u8(index as u8), Some(policy));
+            assert_eq!(FocusPolicy::from_name(policy.name()), Some(policy));
+        }
+        assert_eq!(FocusPolicy::from_u8(4), None);
+        assert_eq!(FocusPolicy::from_name("Aggressive"), None);
+    }
+
+    #[test]
+    fn next_cycles_through_every_policy() {
+        let mut policy = FocusPolicy::Aggressive;
+        for expected in FocusPolicy::ALL.into_iter().skip(1) {
+            policy = policy.next();
+            assert_eq!(policy, expected);
+        }
+        assert_eq!(policy.next(), FocusPolicy::Aggressive);
+    }
+}
@@ -0,0 +1,711 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use windows::Win32::{
+    Foundation::{LPARAM, POINT, WPARAM},
+    Graphics::Gdi::ScreenToClient,
+    UI::{
+        Input::XboxController::{
+            XINPUT_GAMEPAD_A, XINPUT_GAMEPAD_B, XINPUT_GAMEPAD_BACK, XINPUT_GAMEPAD_BUTTON_FLAGS,
+            XINPUT_GAMEPAD_DPAD_DOWN, XINPUT_GAMEPAD_DPAD_LEFT, XINPUT_GAMEPAD_DPAD_RIGHT,
+            XINPUT_GAMEPAD_DPAD_UP, XINPUT_GAMEPAD_LEFT_SHOULDER, XINPUT_GAMEPAD_LEFT_THUMB,
+            XINPUT_GAMEPAD_RIGHT_SHOULDER, XINPUT_GAMEPAD_RIGHT_THUMB, XINPUT_GAMEPAD_START,
+            XINPUT_GAMEPAD_X, XINPUT_GAMEPAD_Y, XINPUT_STATE, XInputGetState, XUSER_MAX_COUNT,
+        },
+        WindowsAndMessaging::{GetCursorPos, PostMessageW},
+    },
+};
+
+use super::{CLIENT_GEOMETRY, INPUT_SETTINGS, WM_SYNTHETIC_INPUT, event_kind};
+use crate::{
+    globals::GAME_HWND,
+    shutdown::{sleep_unless_stopping, spawn_worker, stopping},
+};
+
+//Set while the gamepad drives the overlay cursor.
+pub static OVERLAY_CURSOR_MODE: AtomicBool = AtomicBool::new(false);
+
+//How often controllers are polled while gamepad support is enabled.
+const POLL_INTERVAL: Duration = Duration::from_millis(8);
+//How often we check if gamepad support got enabled.
+const IDLE_INTERVAL: Duration = Duration::from_millis(250);
+//XInput returns this when no controller is plugged in that slot.
+const ERROR_DEVICE_NOT_CONNECTED: u32 = 1167;
+
+//L1 + R1 + View on a Steam Deck with the default Steam Input layout.
+pub const DEFAULT_TOGGLE_COMBO: u16 =
+    XINPUT_GAMEPAD_LEFT_SHOULDER.0 | XINPUT_GAMEPAD_RIGHT_SHOULDER.0 | XINPUT_GAMEPAD_BACK.0;
+//f64 like the config, so overlay.toml gets 0.24 rather than 0.23999999463558197.
+pub const DEFAULT_DEADZONE: f64 = 0.24;
+pub const DEFAULT_MAX_SPEED: f64 = 1400.0;
+
+//Button names used in input.gamepad_toggle_combo, Xbox style. Same order as the XINPUT bits.
+const BUTTONS: [(&str, XINPUT_GAMEPAD_BUTTON_FLAGS); 14] = [
+    ("Up", XINPUT_GAMEPAD_DPAD_UP),
+    ("Down", XINPUT_GAMEPAD_DPAD_DOWN),
+    ("Left", XINPUT_GAMEPAD_DPAD_LEFT),
+    ("Right", XINPUT_GAMEPAD_DPAD_RIGHT),
+    ("Start", XINPUT_GAMEPAD_START),
+    ("Back", XINPUT_GAMEPAD_BACK),
+    ("LS", XINPUT_GAMEPAD_LEFT_THUMB),
+    ("RS", XINPUT_GAMEPAD_RIGHT_THUMB),
+    ("LB", XINPUT_GAMEPAD_LEFT_SHOULDER),
+    ("RB", XINPUT_GAMEPAD_RIGHT_SHOULDER),
+    ("A", XINPUT_GAMEPAD_A),
+    ("B", XINPUT_GAMEPAD_B),
+    ("X", XINPUT_GAMEPAD_X),
+    ("Y", XINPUT_GAMEPAD_Y),
+];
+
+///Parses a toggle combo like "LB+RB+Back". Button names are case insensitive.
+pub fn parse_combo(text: &str) -> Result<u16, String> {
+    let mut combo = 0;
+    for name in text.split('+').map(str::trim) {
+        let Some((_, button)) = BUTTONS
+            .iter()
+            .find(|(button, _)| button.eq_ignore_ascii_case(name))
+        else {
+            let names: Vec<&str> = BUTTONS.iter().map(|(name, _)| *name).collect();
+            return Err(format!("\"{}\" is not one of {}", name, names.join(", ")));
+        };
+        if combo & button.0 != 0 {
+            return Err(format!("{} is used twice", name));
+        }
+        combo |= button.0;
+    }
+    Ok(combo)
+}
+
+///The text parse_combo() reads back.
+pub fn combo_name(combo: u16) -> String {
+    let names: Vec<&str> = BUTTONS
+        .iter()
+        .filter(|(_, button)| combo & button.0 != 0)
+        .map(|(name, _)| *name)
+        .collect();
+    names.join("+")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GamepadConfig {
+    //XINPUT_GAMEPAD_* buttons that all have to be pressed to enter/leave overlay cursor mode.
+    pub toggle_combo: u16,
+    //Portion of the stick range (0.0 - 1.0) that is ignored.
+    pub deadzone: f32,
+    //Cursor speed in pixels per second with the stick fully tilted, after ramping up.
+    pub max_speed: f32,
+    //Response curve. 1.0 is linear, higher values give more precision near the center.
+    pub curve: f32,
+    //The cursor starts at ramp_start of its speed and reaches full speed after ramp_time seconds.
+    pub ramp_start: f32,
+    pub ramp_time: f32,
+    //Trigger values are 0-255. A click starts above press and ends below release.
+    pub trigger_press: u8,
+    pub trigger_release: u8,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        Self {
+            toggle_combo: DEFAULT_TOGGLE_COMBO,
+            deadzone: DEFAULT_DEADZONE as f32,
+            max_speed: DEFAULT_MAX_SPEED as f32,
+            curve: 2.0,
+            ramp_start: 0.4,
+            ramp_time: 0.6,
+            trigger_press: 128,
+            trigger_release: 64,
+        }
+    }
+}
+
+//One XInput reading.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GamepadSample {
+    pub buttons: u16,
+    pub left_trigger: u8,
+    pub right_trigger: u8,
+    pub thumb_x: i16,
+    pub thumb_y: i16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GamepadEvent {
+    ModeChanged(bool),
+    //Relative cursor motion in client pixels, y grows downwards.
+    Move { dx: i32, dy: i32 },
+    LeftButton(bool),
+    RightButton(bool),
+}
+
+///Cursor velocity in pixels per second for a stick position.
+///`held` is how long the stick has been out of the deadzone, in seconds.
+pub fn stick_velocity(config: &GamepadConfig, x: i16, y: i16, held: f32) -> (f32, f32) {
+    //i16::MIN would be slightly above 1.0
+    let nx = (x as f32 / i16::MAX as f32).clamp(-1.0, 1.0);
+    //XInput's y axis points up
+    let ny = -(y as f32 / i16::MAX as f32).clamp(-1.0, 1.0);
+
+    //Stick corners go past 1.0, the direction is taken from the full length.
+    let length = (nx * nx + ny * ny).sqrt();
+    let magnitude = length.min(1.0);
+    if magnitude <= config.deadzone {
+        return (0.0, 0.0);
+    }
+    let scaled = (magnitude - config.deadzone) / (1.0 - config.deadzone);
+    let ramp = if config.ramp_time > 0.0 {
+        config.ramp_start + (1.0 - config.ramp_start) * (held / config.ramp_time).min(1.0)
+    } else {
+        1.0
+    };
+    let speed = scaled.powf(config.curve) * config.max_speed * ramp;
+    (nx / length * speed, ny / length * speed)
+}
+
+///Turns gamepad samples into overlay cursor events.
+#[derive(Debug)]
+pub struct GamepadCursor {
+    pub config: GamepadConfig,
+    active: bool,
+    combo_held: bool,
+    stick_held: f32,
+    //Sub-pixel motion carried over to the next update.
+    remainder: (f32, f32),
+    left_down: bool,
+    right_down: bool,
+}
+
+impl GamepadCursor {
+    pub fn new(config: GamepadConfig) -> Self {
+        Self {
+            config,
+            active: false,
+            combo_held: false,
+            stick_held: 0.0,
+            remainder: (0.0, 0.0),
+            left_down: false,
+            right_down: false,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    ///Feeds a sample taken `dt` seconds after the previous one.
+    pub fn update(&mut self, sample: &GamepadSample, dt: f32) -> Vec<GamepadEvent> {
+        let mut events = Vec::new();
+
+        //Mode switching happens when the combo becomes fully pressed.
+        let combo = self.config.toggle_combo;
+        let combo_down = combo != 0 && sample.buttons & combo == combo;
+        if combo_down && !self.combo_held {
+            self.set_active(!self.active, &mut events);
+        }
+        self.combo_held = combo_down;
+
+        if !self.active {
+            return events;
+        }
+
+        //Stick
+        let (vx, vy) = stick_velocity(
+            &self.config,
+            sample.thumb_x,
+            sample.thumb_y,
+            self.stick_held,
+        );
+        if vx == 0.0 && vy == 0.0 {
+            self.stick_held = 0.0;
+            self.remainder = (0.0, 0.0);
+        } else {
+            self.stick_held += dt;
+            let x = self.remainder.0 + vx * dt;
+            let y = self.remainder.1 + vy * dt;
+            let (dx, dy) = (x.trunc(), y.trunc());
+            self.remainder = (x - dx, y - dy);
+            if dx != 0.0 || dy != 0.0 {
+                events.push(GamepadEvent::Move {
+                    dx: dx as i32,
+                    dy: dy as i32,
+                });
+            }
+        }
+
+        //Triggers. Right is the left click, like a mouse in the right hand.
+        if let Some(down) = trigger_edge(&self.config, sample.right_trigger, self.left_down) {
+            self.left_down = down;
+            events.push(GamepadEvent::LeftButton(down));
+        }
+        if let Some(down) = trigger_edge(&self.config, sample.left_trigger, self.right_down) {
+            self.right_down = down;
+            events.push(GamepadEvent::RightButton(down));
+        }
+        events
+    }
+
+    ///Leaves overlay cursor mode, eg. when gamepad support gets disabled.
+    pub fn deactivate(&mut self) -> Vec<GamepadEvent> {
+        let mut events = Vec::new();
+        if self.active {
+            self.set_active(false, &mut events);
+        }
+        events
+    }
+
+    fn set_active(&mut self, active: bool, events: &mut Vec<GamepadEvent>) {
+        self.active = active;
+        self.stick_held = 0.0;
+        self.remainder = (0.0, 0.0);
+        //Never leave a button pressed behind.
+        if !active {
+            if self.left_down {
+                self.left_down = false;
+                events.push(GamepadEvent::LeftButton(false));
+            }
+            if self.right_down {
+                self.right_down = false;
+                events.push(GamepadEvent::RightButton(false));
+            }
+        }
+        events.push(GamepadEvent::ModeChanged(active));
+    }
+}
+
+//Returns the new button state if the trigger crossed a threshold.
+fn trigger_edge(config: &GamepadConfig, value: u8, down: bool) -> Option<bool> {
+    if !down && value >= config.trigger_press {
+        Some(true)
+    } else if down && value < config.trigger_release {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+///Polls XInput and drives the overlay cursor. Does nothing until gamepad support is enabled.
+///Events are posted to the game window so they go through wnd_proc like any other input.
+pub fn start_gamepad_thread() {
+    spawn_worker("gamepad", || {
+        let mut cursor = GamepadCursor::new(GamepadConfig::default());
+        //Virtual cursor position, in client coordinates.
+        let mut position = (0i32, 0i32);
+        let mut last_poll = Instant::now();
+
+        while !stopping() {
+            if !INPUT_SETTINGS.gamepad_enabled.load(Ordering::Relaxed) {
+                for event in cursor.deactivate() {
+                    apply_event(event, &mut position);
+                }
+                sleep_unless_stopping(IDLE_INTERVAL);
+                last_poll = Instant::now();
+                continue;
+            }
+
+            cursor.config = INPUT_SETTINGS.gamepad_config();
+            let sample = poll_gamepad().unwrap_or_default();
+            let dt = last_poll.elapsed().as_secs_f32();
+            last_poll = Instant::now();
+
+            for event in cursor.update(&sample, dt) {
+                apply_event(event, &mut position);
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
+
+//Reads the first connected controller.
+fn poll_gamepad() -> Option<GamepadSample> {
+    for index in 0..XUSER_MAX_COUNT {
+        let mut state = XINPUT_STATE::default();
+        let result = unsafe { XInputGetState(index, &mut state) };
+        if result == ERROR_DEVICE_NOT_CONNECTED {
+            continue;
+        }
+        if result != 0 {
+            return None;
+        }
+        let pad = state.Gamepad;
+        return Some(GamepadSample {
+            buttons: pad.wButtons.0,
+            left_trigger: pad.bLeftTrigger,
+            right_trigger: pad.bRightTrigger,
+            thumb_x: pad.sThumbLX,
+            thumb_y: pad.sThumbLY,
+        });
+    }
+    None
+}
+
+fn apply_event(event: GamepadEvent, position: &mut (i32, i32)) {
+    let (kind, x, y) = match event {
+        GamepadEvent::ModeChanged(active) => {
+            if active {
+                *position = current_cursor_position();
+            }
+            OVERLAY_CURSOR_MODE.store(active, Ordering::Relaxed);
+            log::info!("Overlay cursor mode: {}", active);
+            (event_kind::OVERLAY_CURSOR_MODE, active as i32, 0)
+        }
+        GamepadEvent::Move { dx, dy } => {
+            let client = *CLIENT_GEOMETRY.read().unwrap();
+            let max_x = (client.width as i32 - 1).max(0);
+            let max_y = (client.height as i32 - 1).max(0);
+            position.0 = (position.0 + dx).clamp(0, max_x);
+            position.1 = (position.1 + dy).clamp(0, max_y);
+            (event_kind::MOUSE_MOVE, position.0, position.1)
+        }
+        GamepadEvent::LeftButton(down) => {
+            let kind = if down {
+                event_kind::MOUSE_LEFT_DOWN
+            } else {
+                event_kind::MOUSE_LEFT_UP
+            };
+            (kind, position.0, position.1)
+        }
+        GamepadEvent::RightButton(down) => {
+            let kind = if down {
+                event_kind::MOUSE_RIGHT_DOWN
+            } else {
+                event_kind::MOUSE_RIGHT_UP
+            };
+            (kind, position.0, position.1)
+        }
+    };
+    post_synthetic_input(kind, x, y);
+}
+
+///Sends an event through wnd_proc. Coordinates are client coordinates and are translated
+///like regular mouse messages. They must fit in an i16.
+pub fn post_synthetic_input(kind: u8, x: i32, y: i32) {
+    if let Some(hwnd) = GAME_HWND.get() {
+        let lparam = (x as u16 as u32 | ((y as u16 as u32) << 16)) as isize;
+        unsafe {
+            PostMessageW(
+                *hwnd,
+                WM_SYNTHETIC_INPUT,
+                WPARAM(kind as usize),
+                LPARAM(lparam),
+            )
+            .ok();
+        }
+    }
+}
+
+fn current_cursor_position() -> (i32, i32) {
+    let mut point = POINT::default();
+    unsafe {
+        GetCursorPos(&mut point).ok();
+        if let Some(hwnd) = GAME_HWND.get() {
+            ScreenToClient(*hwnd, &mut point);
+        }
+    }
+    (point.x, point.y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FULL: i16 = i16::MAX;
+    const COMBO: GamepadSample = GamepadSample {
+        buttons: DEFAULT_TOGGLE_COMBO,
+        left_trigger: 0,
+        right_trigger: 0,
+        thumb_x: 0,
+        thumb_y: 0,
+    };
+
+    fn sample(thumb_x: i16, thumb_y: i16) -> GamepadSample {
+        GamepadSample {
+            thumb_x,
+            thumb_y,
+            ..Default::default()
+        }
+    }
+
+    fn triggers(left_trigger: u8, right_trigger: u8) -> GamepadSample {
+        GamepadSample {
+            left_trigger,
+            right_trigger,
+            ..Default::default()
+        }
+    }
+
+    //Within 0.1%, or 0.01 pixels per second near zero.
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() <= (b.abs() * 0.001).max(0.01)
+    }
+
+    //Enters overlay cursor mode and releases the combo.
+    fn active_cursor(config: GamepadConfig) -> GamepadCursor {
+        let mut cursor = GamepadCursor::new(config);
+        cursor.update(&COMBO, 0.0);
+        cursor.update(&GamepadSample::default(), 0.0);
+        assert!(cursor.is_active());
+        cursor
+    }
+
+    #[test]
+    fn stick_inside_the_deadzone_doesnt_move() {
+        let config = GamepadConfig::default();
+        assert_eq!(stick_velocity(&config, 0, 0, 10.0), (0.0, 0.0));
+        let edge = (config.deadzone * FULL as f32) as i16;
+        assert_eq!(stick_velocity(&config, edge, 0, 10.0), (0.0, 0.0));
+        assert_eq!(stick_velocity(&config, 0, -edge, 10.0), (0.0, 0.0));
+        assert_ne!(stick_velocity(&config, edge + 500, 0, 10.0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn stick_directions_with_y_pointing_down() {
+        let config = GamepadConfig::default();
+        let max = config.max_speed;
+        let (vx, vy) = stick_velocity(&config, FULL, 0, 10.0);
+        assert!(close(vx, max) && close(vy, 0.0));
+        let (vx, vy) = stick_velocity(&config, -FULL, 0, 10.0);
+        assert!(close(vx, -max) && close(vy, 0.0));
+        //Up on the stick is up on the screen.
+        let (vx, vy) = stick_velocity(&config, 0, FULL, 10.0);
+        assert!(close(vx, 0.0) && close(vy, -max));
+        let (vx, vy) = stick_velocity(&config, 0, i16::MIN, 10.0);
+        assert!(close(vx, 0.0) && close(vy, max));
+    }
+
+    #[test]
+    fn diagonals_are_capped_at_full_speed() {
+        let config = GamepadConfig::default();
+        let (vx, vy) = stick_velocity(&config, FULL, FULL, 10.0);
+        assert!(close((vx * vx + vy * vy).sqrt(), config.max_speed));
+        assert!(close(vx, -vy));
+    }
+
+    #[test]
+    fn curve_gives_precision_near_the_center() {
+        let config = GamepadConfig {
+            deadzone: 0.0,
+            ramp_time: 0.0,
+            ..Default::default()
+        };
+        let (half, _) = stick_velocity(&config, FULL / 2, 0, 0.0);
+        assert!(close(half, config.max_speed * 0.25));
+
+        let linear = GamepadConfig {
+            curve: 1.0,
+            ..config
+        };
+        let (half, _) = stick_velocity(&linear, FULL / 2, 0, 0.0);
+        assert!(close(half, config.max_speed * 0.5));
+    }
+
+    #[test]
+    fn speed_ramps_up_while_held() {
+        let config = GamepadConfig::default();
+        let max = config.max_speed;
+        let (start, _) = stick_velocity(&config, FULL, 0, 0.0);
+        assert!(close(start, max * config.ramp_start));
+        let (middle, _) = stick_velocity(&config, FULL, 0, config.ramp_time / 2.0);
+        assert!(close(middle, max * (config.ramp_start + 1.0) / 2.0));
+        let (end, _) = stick_velocity(&config, FULL, 0, config.ramp_time);
+        assert!(close(end, max));
+        let (after, _) = stick_velocity(&config, FULL, 0, config.ramp_time * 10.0);
+        assert!(close(after, max));
+    }
+
+    #[test]
+    fn combo_toggles_the_mode_once_per_press() {
+        let mut cursor = GamepadCursor::new(GamepadConfig::default());
+        assert!(!cursor.is_active());
+        assert_eq!(
+            cursor.update(&COMBO, 0.01),
+            vec![GamepadEvent::ModeChanged(true)]
+        );
+        //Holding the combo doesn't toggle again.
+        assert_eq!(cursor.update(&COMBO, 0.01), vec![]);
+        assert!(cursor.is_active());
+        assert_eq!(cursor.update(&GamepadSample::default(), 0.01), vec![]);
+        assert_eq!(
+            cursor.update(&COMBO, 0.01),
+            vec![GamepadEvent::ModeChanged(false)]
+        );
+        assert!(!cursor.is_active());
+    }
+
+    #[test]
+    fn combo_needs_every_button() {
+        let mut cursor = GamepadCursor::new(GamepadConfig::default());
+        let partial = GamepadSample {
+            buttons: XINPUT_GAMEPAD_LEFT_SHOULDER.0 | XINPUT_GAMEPAD_RIGHT_SHOULDER.0,
+            ..Default::default()
+        };
+        assert_eq!(cursor.update(&partial, 0.01), vec![]);
+        //Extra buttons don't matter.
+        let extra = GamepadSample {
+            buttons: DEFAULT_TOGGLE_COMBO | 0x1000,
+            ..Default::default()
+        };
+        assert_eq!(
+            cursor.update(&extra, 0.01),
+            vec![GamepadEvent::ModeChanged(true)]
+        );
+    }
+
+    #[test]
+    fn empty_combo_never_toggles() {
+        let mut cursor = GamepadCursor::new(GamepadConfig {
+            toggle_combo: 0,
+            ..Default::default()
+        });
+        assert_eq!(cursor.update(&COMBO, 0.01), vec![]);
+        assert_eq!(cursor.update(&GamepadSample::default(), 0.01), vec![]);
+        assert!(!cursor.is_active());
+    }
+
+    #[test]
+    fn inactive_cursor_ignores_stick_and_triggers() {
+        let mut cursor = GamepadCursor::new(GamepadConfig::default());
+        let busy = GamepadSample {
+            thumb_x: FULL,
+            right_trigger: 255,
+            left_trigger: 255,
+            ..Default::default()
+        };
+        assert_eq!(cursor.update(&busy, 1.0), vec![]);
+    }
+
+    #[test]
+    fn stick_moves_the_cursor() {
+        let config = GamepadConfig {
+            ramp_time: 0.0,
+            ..Default::default()
+        };
+        let mut cursor = active_cursor(config);
+        assert_eq!(
+            cursor.update(&sample(FULL, 0), 0.1),
+            vec![GamepadEvent::Move { dx: 140, dy: 0 }]
+        );
+        assert_eq!(
+            cursor.update(&sample(0, FULL), 0.1),
+            vec![GamepadEvent::Move { dx: 0, dy: -140 }]
+        );
+        assert_eq!(cursor.update(&sample(0, 0), 0.1), vec![]);
+    }
+
+    #[test]
+    fn sub_pixel_motion_carries_over() {
+        let config = GamepadConfig {
+            ramp_time: 0.0,
+            max_speed: 100.0,
+            ..Default::default()
+        };
+        let mut cursor = active_cursor(config);
+        //0.4 pixels per update.
+        let dt = 0.004;
+        assert_eq!(cursor.update(&sample(FULL, 0), dt), vec![]);
+        assert_eq!(cursor.update(&sample(FULL, 0), dt), vec![]);
+        assert_eq!(
+            cursor.update(&sample(FULL, 0), dt),
+            vec![GamepadEvent::Move { dx: 1, dy: 0 }]
+        );
+        //Releasing the stick drops what was left.
+        cursor.update(&sample(FULL, 0), dt);
+        cursor.update(&sample(0, 0), dt);
+        assert_eq!(cursor.update(&sample(FULL, 0), dt), vec![]);
+        assert_eq!(cursor.update(&sample(FULL, 0), dt), vec![]);
+    }
+
+    #[test]
+    fn triggers_click_with_hysteresis() {
+        let config = GamepadConfig::default();
+        let mut cursor = active_cursor(config);
+        let press = config.trigger_press;
+        let release = config.trigger_release;
+
+        assert_eq!(cursor.update(&triggers(0, press - 1), 0.01), vec![]);
+        assert_eq!(
+            cursor.update(&triggers(0, press), 0.01),
+            vec![GamepadEvent::LeftButton(true)]
+        );
+        //Between the thresholds nothing changes.
+        assert_eq!(cursor.update(&triggers(0, release), 0.01), vec![]);
+        assert_eq!(
+            cursor.update(&triggers(0, release - 1), 0.01),
+            vec![GamepadEvent::LeftButton(false)]
+        );
+        assert_eq!(
+            cursor.update(&triggers(255, 0), 0.01),
+            vec![GamepadEvent::RightButton(true)]
+        );
+        assert_eq!(
+            cursor.update(&triggers(0, 0), 0.01),
+            vec![GamepadEvent::RightButton(false)]
+        );
+    }
+
+    #[test]
+    fn leaving_the_mode_releases_the_buttons() {
+        let mut cursor = active_cursor(GamepadConfig::default());
+        cursor.update(&triggers(255, 255), 0.01);
+        let held = GamepadSample {
+            buttons: DEFAULT_TOGGLE_COMBO,
+            left_trigger: 255,
+            right_trigger: 255,
+            ..Default::default()
+        };
+        assert_eq!(
+            cursor.update(&held, 0.01),
+            vec![
+                GamepadEvent::LeftButton(false),
+                GamepadEvent::RightButton(false),
+                GamepadEvent::ModeChanged(false),
+            ]
+        );
+    }
+
+    #[test]
+    fn combo_names() {
+        assert_eq!(parse_combo("LB+RB+Back"), Ok(DEFAULT_TOGGLE_COMBO));
+        assert_eq!(parse_combo(" back + lb+RB "), Ok(DEFAULT_TOGGLE_COMBO));
+        assert_eq!(combo_name(DEFAULT_TOGGLE_COMBO), "Back+LB+RB");
+        for (name, button) in BUTTONS {
+            assert_eq!(parse_combo(name), Ok(button.0));
+            assert_eq!(combo_name(button.0), name);
+        }
+        let every = BUTTONS
+            .iter()
+            .fold(0, |combo, (_, button)| combo | button.0);
+        assert_eq!(parse_combo(&combo_name(every)), Ok(every));
+    }
+
+    #[test]
+    fn invalid_combos() {
+        assert!(parse_combo("").is_err());
+        assert!(parse_combo("LB+").is_err());
+        assert!(
+            parse_combo("LB+RT")
+                .unwrap_err()
+                .contains("\"RT\" is not one of")
+        );
+        assert_eq!(parse_combo("A+a"), Err("a is used twice".to_string()));
+    }
+
+    #[test]
+    fn deactivate() {
+        let mut cursor = GamepadCursor::new(GamepadConfig::default());
+        assert_eq!(cursor.deactivate(), vec![]);
+
+        let mut cursor = active_cursor(GamepadConfig::default());
+        cursor.update(&triggers(0, 255), 0.01);
+        assert_eq!(
+            cursor.deactivate(),
+            vec![
+                GamepadEvent::LeftButton(false),
+                GamepadEvent::ModeChanged(false),
+            ]
+        );
+        assert!(!cursor.is_active());
+        assert_eq!(cursor.deactivate(), vec![]);
+    }
+}
@@ -0,0 +1,482 @@
+use std::{
+    sync::{
+        OnceLock,
+        atomic::{AtomicBool, AtomicU8, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+    },
+    thread::{self, Thread},
+    time::Duration,
+};
+
+use super::{InputPacket, event_kind};
+
+//Marks a slot the consumer already took. It is x = y = i32::MIN, which no real event uses.
+const CONSUMED: u64 = 0x8000_0000_8000_0000;
+
+struct Slot {
+    kind: AtomicU8,
+    //x in the high half, y in the low half.
+    data: AtomicU64,
+}
+
+fn pack(x: i32, y: i32) -> u64 {
+    ((x as u32 as u64) << 32) | y as u32 as u64
+}
+
+fn unpack(data: u64) -> (i32, i32) {
+    ((data >> 32) as u32 as i32, data as u32 as i32)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    //Events lost because the queue was full.
+    pub dropped: u32,
+    //Mouse moves merged into a pending mouse move.
+    pub coalesced: u32,
+}
+
+///Bounded single-producer single-consumer queue between wnd_proc and the UDP thread.
+///A mouse move pushed right after another mouse move that wasn't sent yet replaces it,
+///so only the latest position is sent and a stalled consumer can't make it grow.
+///Once full, new events are dropped and counted.
+pub struct InputQueue {
+    slots: Box<[Slot]>,
+    //Next slot to read. Only written by the consumer.
+    head: AtomicUsize,
+    //Next slot to write. Only written by the producer.
+    tail: AtomicUsize,
+    dropped: AtomicU32,
+    coalesced: AtomicU32,
+    closed: AtomicBool,
+    //Set while the consumer is parked and needs to be woken up.
+    waiting: AtomicBool,
+    consumer: OnceLock<Thread>,
+}
+
+impl InputQueue {
+    pub fn new(capacity: usize) -> Self {
+        let slots = (0..capacity.max(1))
+            .map(|_| Slot {
+                kind: AtomicU8::new(0),
+                data: AtomicU64::new(CONSUMED),
+            })
+            .collect();
+        Self {
+            slots,
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            dropped: AtomicU32::new(0),
+            coalesced: AtomicU32::new(0),
+            closed: AtomicBool::new(false),
+            waiting: AtomicBool::new(false),
+            consumer: OnceLock::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        let head = self.head.load(Ordering::Acquire);
+        tail.wrapping_sub(head)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn slot(&self, index: usize) -> &Slot {
+        &self.slots[index % self.slots.len()]
+    }
+
+    ///Returns false if the event was dropped, because the queue is full or closed.
+    ///
+    ///# Safety
+    ///Only one thread may ever push. In practice, that's the window thread through send_packet.
+    pub unsafe fn push(&self, packet: InputPacket) -> bool {
+        if self.closed.load(Ordering::Relaxed) {
+            return false;
+        }
+        let id = packet.id;
+        let data = pack(packet.x, packet.y);
+        let tail = self.tail.load(Ordering::Relaxed);
+
+        //Merge into the previous mouse move unless the consumer already took it.
+        //The consumer swaps CONSUMED in, so either it sees our position or the exchange fails.
+        if id == event_kind::MOUSE_MOVE && tail != self.head.load(Ordering::Acquire) {
+            let last = self.slot(tail.wrapping_sub(1));
+            if last.kind.load(Ordering::Relaxed) == event_kind::MOUSE_MOVE {
+                let current = last.data.load(Ordering::Acquire);
+                if current != CONSUMED
+                    && last
+                        .data
+                        .compare_exchange(current, data, Ordering::AcqRel, Ordering::Acquire)
+                        .is_ok()
+                {
+                    self.coalesced.fetch_add(1, Ordering::Relaxed);
+                    return true;
+                }
+            }
+        }
+
+        if tail.wrapping_sub(self.head.load(Ordering::Acquire)) >= self.slots.len() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        let slot = self.slot(tail);
+        slot.kind.store(id, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+        self.tail.store(tail.wrapping_add(1), Ordering::SeqCst);
+
+        if self.waiting.load(Ordering::SeqCst) {
+            self.wake_consumer();
+        }
+        true
+    }
+
+    fn try_pop(&self) -> Option<InputPacket> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let slot = self.slot(head);
+        let id = slot.kind.load(Ordering::Relaxed);
+        let data = slot.data.swap(CONSUMED, Ordering::AcqRel);
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        let (x, y) = unpack(data);
+        Some(InputPacket { id, x, y })
+    }
+
+    ///Blocks until an event is available. Returns None once the queue is closed and drained.
+    ///Must only be called from a single consumer thread.
+    pub fn pop(&self) -> Option<InputPacket> {
+        let current = self.consumer.get_or_init(thread::current);
+        debug_assert_eq!(current.id(), thread::current().id());
+        loop {
+            if let Some(packet) = self.try_pop() {
+                return Some(packet);
+            }
+            if self.closed.load(Ordering::Acquire) {
+                return None;
+            }
+            self.waiting.store(true, Ordering::SeqCst);
+            //Check again, the producer may have pushed before seeing the flag.
+            if self.tail.load(Ordering::SeqCst) == self.head.load(Ordering::Relaxed)
+                && !self.closed.load(Ordering::Acquire)
+            {
+                //The timeout is only a safety net, pushes wake us up.
+                thread::park_timeout(Duration::from_millis(100));
+            }
+            self.waiting.store(false, Ordering::SeqCst);
+        }
+    }
+
+    ///Stops accepting events. The consumer still gets what's left, then pop() returns None.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.wake_consumer();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    pub fn stats(&self) -> QueueStats {
+        QueueStats {
+            dropped: self.dropped.load(Ordering::Relaxed),
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+        }
+    }
+
+    fn wake_consumer(&self) {
+        if let Some(consumer) = self.consumer.get() {
+            consumer.unpark();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Instant};
+
+    use super::*;
+    use event_kind::{KEY_DOWN, MOUSE_LEFT_DOWN, MOUSE_MOVE};
+
+    fn packet(id: u8, x: i32, y: i32) -> InputPacket {
+        InputPacket { id, x, y }
+    }
+
+    fn fields(packet: InputPacket) -> (u8, i32, i32) {
+        (packet.id, packet.x, packet.y)
+    }
+
+    fn push(queue: &InputQueue, id: u8, x: i32, y: i32) -> bool {
+        unsafe { queue.push(packet(id, x, y)) }
+    }
+
+    fn drain(queue: &InputQueue) -> Vec<(u8, i32, i32)> {
+        std::iter::from_fn(|| queue.try_pop().map(fields)).collect()
+    }
+
+    #[test]
+    fn packing() {
+        for (x, y) in [(0, 0), (-1, 1), (i32::MAX, i32::MIN + 1), (1920, -1080)] {
+            assert_eq!(unpack(pack(x, y)), (x, y));
+            assert_ne!(pack(x, y), CONSUMED);
+        }
+    }
+
+    #[test]
+    fn keeps_the_order() {
+        let queue = InputQueue::new(8);
+        assert!(push(&queue, KEY_DOWN, 65, 1));
+        assert!(push(&queue, MOUSE_MOVE, 10, 20));
+        assert!(push(&queue, MOUSE_LEFT_DOWN, 10, 20));
+        assert_eq!(queue.len(), 3);
+        assert_eq!(
+            drain(&queue),
+            [
+                (KEY_DOWN, 65, 1),
+                (MOUSE_MOVE, 10, 20),
+                (MOUSE_LEFT_DOWN, 10, 20)
+            ]
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn coalesces_pending_mouse_moves() {
+        let queue = InputQueue::new(8);
+        for x in 0..100 {
+            assert!(push(&queue, MOUSE_MOVE, x, -x));
+        }
+        assert_eq!(queue.len(), 1);
+        assert_eq!(drain(&queue), [(MOUSE_MOVE, 99, -99)]);
+        assert_eq!(
+            queue.stats(),
+            QueueStats {
+                dropped: 0,
+                coalesced: 99
+            }
+        );
+    }
+
+    #[test]
+    fn other_events_separate_mouse_moves() {
+        let queue = InputQueue::new(8);
+        push(&queue, MOUSE_MOVE, 1, 1);
+        push(&queue, MOUSE_MOVE, 2, 2);
+        push(&queue, MOUSE_LEFT_DOWN, 2, 2);
+        push(&queue, MOUSE_MOVE, 3, 3);
+        push(&queue, MOUSE_MOVE, 4, 4);
+        assert_eq!(
+            drain(&queue),
+            [
+                (MOUSE_MOVE, 2, 2),
+                (MOUSE_LEFT_DOWN, 2, 2),
+                (MOUSE_MOVE, 4, 4)
+            ]
+        );
+        assert_eq!(queue.stats().coalesced, 2);
+    }
+
+    #[test]
+    fn consumed_mouse_moves_arent_replaced() {
+        let queue = InputQueue::new(8);
+        push(&queue, MOUSE_MOVE, 1, 1);
+        assert_eq!(drain(&queue), [(MOUSE_MOVE, 1, 1)]);
+        push(&queue, MOUSE_MOVE, 2, 2);
+        assert_eq!(drain(&queue), [(MOUSE_MOVE, 2, 2)]);
+        assert_eq!(queue.stats().coalesced, 0);
+    }
+
+    #[test]
+    fn drops_when_full() {
+        let queue = InputQueue::new(4);
+        for key in 0..10 {
+            push(&queue, KEY_DOWN, key, 0);
+            assert!(queue.len() <= queue.capacity());
+        }
+        assert_eq!(queue.stats().dropped, 6);
+        //A mouse move still replaces the pending one of a full queue.
+        push(&queue, MOUSE_MOVE, 0, 0);
+        assert_eq!(queue.stats().dropped, 7);
+        let events = drain(&queue);
+        assert_eq!(events, [0, 1, 2, 3].map(|key| (KEY_DOWN, key, 0)));
+
+        //Room again once the consumer caught up, also across the end of the slots.
+        for key in 10..13 {
+            assert!(push(&queue, KEY_DOWN, key, 0));
+        }
+        assert_eq!(drain(&queue).len(), 3);
+    }
+
+    #[test]
+    fn full_queue_still_coalesces() {
+        let queue = InputQueue::new(2);
+        push(&queue, KEY_DOWN, 1, 0);
+        push(&queue, MOUSE_MOVE, 1, 1);
+        assert!(push(&queue, MOUSE_MOVE, 2, 2));
+        assert!(!push(&queue, KEY_DOWN, 2, 0));
+        assert_eq!(drain(&queue), [(KEY_DOWN, 1, 0), (MOUSE_MOVE, 2, 2)]);
+        assert_eq!(
+            queue.stats(),
+            QueueStats {
+                dropped: 1,
+                coalesced: 1
+            }
+        );
+    }
+
+    #[test]
+    fn capacity_is_at_least_one() {
+        let queue = InputQueue::new(0);
+        assert_eq!(queue.capacity(), 1);
+        assert!(push(&queue, KEY_DOWN, 1, 0));
+        assert!(!push(&queue, KEY_DOWN, 2, 0));
+    }
+
+    #[test]
+    fn close_drains_then_stops() {
+        let queue = InputQueue::new(4);
+        push(&queue, KEY_DOWN, 1, 0);
+        push(&queue, KEY_DOWN, 2, 0);
+        queue.close();
+        assert!(queue.is_closed());
+        assert!(!push(&queue, KEY_DOWN, 3, 0));
+        assert_eq!(queue.pop().map(fields), Some((KEY_DOWN, 1, 0)));
+        assert_eq!(queue.pop().map(fields), Some((KEY_DOWN, 2, 0)));
+        assert_eq!(queue.pop().map(fields), None);
+    }
+
+    #[test]
+    fn close_wakes_a_waiting_consumer() {
+        let queue = Arc::new(InputQueue::new(4));
+        let consumer = {
+            let queue = queue.clone();
+            thread::spawn(move || queue.pop().map(fields))
+        };
+        thread::sleep(Duration::from_millis(20));
+        queue.close();
+        assert_eq!(consumer.join().unwrap(), None);
+    }
+
+    //A producer pushing keys and mouse moves as fast as it can against a slower consumer.
+    //Keys are pushed until accepted, so none may be lost, and the moves a consumer sees
+    //must be in order and end with the last position.
+    #[test]
+    fn concurrent_producer_and_consumer() {
+        const KEYS: i32 = 2_000;
+        const MOVES_PER_KEY: i32 = 50;
+        let queue = Arc::new(InputQueue::new(16));
+        let consumer = {
+            let queue = queue.clone();
+            thread::spawn(move || {
+                let mut received = Vec::new();
+                let mut max_len = 0;
+                while let Some(packet) = queue.pop() {
+                    max_len = max_len.max(queue.len());
+                    received.push(fields(packet));
+                    if received.len() % 64 == 0 {
+                        thread::yield_now();
+                    }
+                }
+                (received, max_len)
+            })
+        };
+
+        let mut position = 0;
+        let mut accepted_moves = 0;
+        let mut refused = 0;
+        for key in 0..KEYS {
+            for _ in 0..MOVES_PER_KEY {
+                position += 1;
+                if push(&queue, MOUSE_MOVE, position, -position) {
+                    accepted_moves += 1;
+                } else {
+                    refused += 1;
+                }
+            }
+            while !push(&queue, KEY_DOWN, key, 0) {
+                refused += 1;
+                thread::yield_now();
+            }
+        }
+        //Wait for the consumer before closing, so the last move isn't refused.
+        position += 1;
+        while !push(&queue, MOUSE_MOVE, position, -position) {
+            refused += 1;
+            thread::yield_now();
+        }
+        accepted_moves += 1;
+        queue.close();
+        let (received, max_len) = consumer.join().unwrap();
+
+        assert!(max_len <= queue.capacity());
+        let keys: Vec<i32> = received
+            .iter()
+            .filter(|(id, ..)| *id == KEY_DOWN)
+            .map(|(_, key, _)| *key)
+            .collect();
+        assert_eq!(keys, (0..KEYS).collect::<Vec<_>>());
+
+        let moves: Vec<i32> = received
+            .iter()
+            .filter(|(id, ..)| *id == MOUSE_MOVE)
+            .map(|&(_, x, y)| {
+                assert_eq!(x, -y);
+                x
+            })
+            .collect();
+        assert!(moves.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(moves.last(), Some(&position));
+
+        let stats = queue.stats();
+        assert_eq!(moves.len() as u32 + stats.coalesced, accepted_moves);
+        assert_eq!(stats.dropped, refused);
+    }
+
+    //cargo test --release input_queue::tests::throughput -- --ignored --nocapture
+    #[test]
+    #[ignore = "benchmark"]
+    fn throughput() {
+        const EVENTS: i32 = 10_000_000;
+        let queue = Arc::new(InputQueue::new(1024));
+        let consumer = {
+            let queue = queue.clone();
+            thread::spawn(move || {
+                let mut count = 0u64;
+                while queue.pop().is_some() {
+                    count += 1;
+                }
+                count
+            })
+        };
+        let started = Instant::now();
+        for i in 0..EVENTS {
+            //Mostly mouse moves, like a game window.
+            let id = if i % 8 == 0 { KEY_DOWN } else { MOUSE_MOVE };
+            push(&queue, id, i, i);
+        }
+        let pushed = started.elapsed();
+        queue.close();
+        let received = consumer.join().unwrap();
+        let elapsed = started.elapsed();
+        let stats = queue.stats();
+        println!(
+            "{} events pushed in {:?} ({:.1} ns/event), {} received in {:?}, {} coalesced, {} dropped",
+            EVENTS,
+            pushed,
+            pushed.as_nanos() as f64 / EVENTS as f64,
+            received,
+            elapsed,
+            stats.coalesced,
+            stats.dropped
+        );
+        assert_eq!(
+            received + stats.coalesced as u64 + stats.dropped as u64,
+            EVENTS as u64
+        );
+    }
+}
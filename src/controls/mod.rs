@@ -0,0 +1,735 @@
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+    slice::from_raw_parts,
+    sync::{
+        Arc, Mutex, OnceLock, RwLock,
+        atomic::{AtomicBool, AtomicU8, AtomicU16, AtomicU32, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use windows::Win32::{
+    Foundation::{HWND, LPARAM, LRESULT, RECT, WPARAM},
+    Graphics::Gdi::{MONITOR_DEFAULTTONEAREST, MonitorFromWindow},
+    UI::{
+        HiDpi::{
+            DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, GetDpiForMonitor, GetDpiForWindow,
+            MDT_EFFECTIVE_DPI, SetThreadDpiAwarenessContext,
+        },
+        Input::KeyboardAndMouse::{ReleaseCapture, SetCapture, SetFocus},
+        WindowsAndMessaging::{
+            CallWindowProcW, DefWindowProcW, GWLP_WNDPROC, GetClientRect, GetForegroundWindow,
+            GetWindowLongPtrW, SetForegroundWindow, SetWindowLongPtrW, WM_ACTIVATE, WM_ACTIVATEAPP,
+            WM_APP, WM_CHAR, WM_DPICHANGED, WM_INPUT, WM_KEYDOWN, WM_KEYUP, WM_KILLFOCUS,
+            WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDOWN, WM_MBUTTONUP, WM_MOUSEMOVE,
+            WM_RBUTTONDOWN, WM_RBUTTONUP, WM_SETCURSOR, WM_SETFOCUS, WM_SIZE, WM_SYSKEYDOWN,
+            WM_SYSKEYUP, WM_TIMER, WM_XBUTTONDOWN, WM_XBUTTONUP,
+        },
+    },
+};
+
+use crate::{
+    config::{InputConfig, config},
+    debug::statistics::{debug_stat, send_statistic},
+    globals::{GAME_HWND, ORIGINAL_WNDPROC},
+    keybinds::{
+        HOLD_TIMER_ID,
+        grammar::{MouseButton, Trigger},
+        hold_timer_elapsed, reset_triggers, stop_hold_timer, trigger_down, trigger_up,
+    },
+    shutdown::{HookCall, spawn_worker, stopping},
+    ui::OVERLAY_GEOMETRY,
+};
+use control_channel::{AckStatus, ControlRequest, WindowBackend, dispatch};
+use coords::{ClientGeometry, client_to_overlay};
+use focus::{FocusEvent, FocusPolicy};
+use gamepad::{
+    DEFAULT_DEADZONE, DEFAULT_MAX_SPEED, DEFAULT_TOGGLE_COMBO, GamepadConfig, OVERLAY_CURSOR_MODE,
+};
+use input_queue::{InputQueue, QueueStats};
+use subscribers::{Request, SubscriberTable, ack_packet, response};
+use win32_backend::{KEYBOARD_CAPTURED, Win32Backend, apply_cursor_override};
+
+pub mod control_channel;
+pub mod coords;
+pub mod focus;
+pub mod gamepad;
+pub mod input_queue;
+pub mod mumble_link;
+pub mod raw_input;
+pub mod recording;
+pub mod subscribers;
+pub mod win32_backend;
+
+//Ids of the events sent to subscribers. Also used as the bit index in subscription filters.
+pub mod event_kind {
+    //Buttons are only sent by the gamepad cursor, the game's own clicks are seen globally by overlays.
+    pub const MOUSE_LEFT_DOWN: u8 = 0;
+    pub const MOUSE_LEFT_UP: u8 = 1;
+    pub const MOUSE_MOVE: u8 = 2;
+    pub const MOUSE_RIGHT_DOWN: u8 = 3;
+    pub const MOUSE_RIGHT_UP: u8 = 4;
+    pub const RAW_MOUSE_MOVE: u8 = 5;
+    pub const RAW_MOUSE_BUTTON: u8 = 6;
+    //Only sent while the overlay captured the keyboard. x is the wparam, y the lparam.
+    pub const KEY_DOWN: u8 = 7;
+    pub const KEY_UP: u8 = 8;
+    pub const CHAR: u8 = 9;
+    //x is 1 when the gamepad starts driving the overlay cursor, 0 when it stops.
+    pub const OVERLAY_CURSOR_MODE: u8 = 10;
+}
+
+//The legacy listener (input.legacy_address) predates subscriptions. It gets mouse moves, and the
+//clicks of the gamepad cursor since nothing else can click for it.
+const LEGACY_FILTER: u32 = (1 << event_kind::MOUSE_MOVE)
+    | (1 << event_kind::MOUSE_LEFT_DOWN)
+    | (1 << event_kind::MOUSE_LEFT_UP)
+    | (1 << event_kind::MOUSE_RIGHT_DOWN)
+    | (1 << event_kind::MOUSE_RIGHT_UP);
+
+//Anything related to how input is forwarded. Toggled with keybinds.
+pub struct InputSettings {
+    //Registers raw mouse input and forwards relative motion and buttons.
+    pub raw_input_enabled: AtomicBool,
+    //Whether raw input is still forwarded while the game hides the cursor to rotate the camera.
+    pub raw_input_during_mouse_look: AtomicBool,
+    //A focus::FocusPolicy.
+    pub focus_policy: AtomicU8,
+    //Polls XInput so a controller can drive the overlay cursor.
+    pub gamepad_enabled: AtomicBool,
+    //XINPUT_GAMEPAD_* buttons that toggle overlay cursor mode.
+    gamepad_toggle_combo: AtomicU16,
+    //f32 bits, see GamepadConfig.
+    gamepad_deadzone: AtomicU32,
+    gamepad_max_speed: AtomicU32,
+}
+
+pub static INPUT_SETTINGS: InputSettings = InputSettings {
+    raw_input_enabled: AtomicBool::new(false),
+    raw_input_during_mouse_look: AtomicBool::new(true),
+    focus_policy: AtomicU8::new(FocusPolicy::Aggressive as u8),
+    gamepad_enabled: AtomicBool::new(false),
+    gamepad_toggle_combo: AtomicU16::new(DEFAULT_TOGGLE_COMBO),
+    gamepad_deadzone: AtomicU32::new((DEFAULT_DEADZONE as f32).to_bits()),
+    gamepad_max_speed: AtomicU32::new((DEFAULT_MAX_SPEED as f32).to_bits()),
+};
+
+impl InputSettings {
+    pub fn focus_policy(&self) -> FocusPolicy {
+        FocusPolicy::from_u8(self.focus_policy.load(Ordering::Relaxed))
+            .unwrap_or(FocusPolicy::Aggressive)
+    }
+    ///The settings of the gamepad cursor from input.gamepad_*, the rest are defaults.
+    pub fn gamepad_config(&self) -> GamepadConfig {
+        GamepadConfig {
+            toggle_combo: self.gamepad_toggle_combo.load(Ordering::Relaxed),
+            deadzone: f32::from_bits(self.gamepad_deadzone.load(Ordering::Relaxed)),
+            max_speed: f32::from_bits(self.gamepad_max_speed.load(Ordering::Relaxed)),
+            ..Default::default()
+        }
+    }
+    pub fn set_gamepad_config(&self, toggle_combo: u16, deadzone: f32, max_speed: f32) {
+        self.gamepad_toggle_combo
+            .store(toggle_combo, Ordering::Relaxed);
+        self.gamepad_deadzone
+            .store(deadzone.to_bits(), Ordering::Relaxed);
+        self.gamepad_max_speed
+            .store(max_speed.to_bits(), Ordering::Relaxed);
+    }
+}
+
+//Posted to the game window when the overlay becomes interactive or stops being interactive,
+//so the focus policy gets re-evaluated on the window thread.
+pub const WM_OVERLAY_INTERACTIVE_CHANGED: u32 = WM_APP + 0x101;
+//Posted to the game window to send an event that didn't come from Windows (eg. the gamepad cursor).
+//wparam is the event kind, lparam holds client coordinates like mouse messages.
+pub const WM_SYNTHETIC_INPUT: u32 = WM_APP + 0x102;
+//Posted to the game window to send a recorded packet as-is, without translating it.
+//wparam is the event kind, lparam holds x and y, see recording::pack_coordinates().
+pub const WM_REPLAY_INPUT: u32 = WM_APP + 0x103;
+//High word of WM_XBUTTONDOWN's wparam for the first X button (Mouse4).
+const XBUTTON1: usize = 1;
+
+//The overlay is interactive while it owns the keyboard or the gamepad drives its cursor.
+pub fn is_overlay_interactive() -> bool {
+    KEYBOARD_CAPTURED.load(Ordering::Relaxed) || OVERLAY_CURSOR_MODE.load(Ordering::Relaxed)
+}
+
+//Size and DPI scaling of the game window's client area. Only written from the window thread.
+static CLIENT_GEOMETRY: RwLock<ClientGeometry> = RwLock::new(ClientGeometry::EMPTY);
+
+pub fn initialize_controls(hwnd: HWND) {
+    GAME_HWND.set(hwnd).ok();
+    update_client_geometry(hwnd);
+    if INPUT_SETTINGS.raw_input_enabled.load(Ordering::Relaxed) {
+        raw_input::register(hwnd);
+    }
+    unsafe {
+        let old_wndproc = SetWindowLongPtrW(hwnd, GWLP_WNDPROC, wnd_proc as _);
+        ORIGINAL_WNDPROC = Some(std::mem::transmute(old_wndproc));
+    }
+}
+
+///Gives the window back to the game: the original window procedure, the raw mouse input
+///registration, the hold timer and the keyboard.
+///Returns false if the window procedure couldn't be restored, then unloading is unsafe.
+pub fn restore_controls() -> bool {
+    let Some(hwnd) = GAME_HWND.get() else {
+        return true;
+    };
+    stop_hold_timer();
+    raw_input::unregister();
+    Win32Backend.release_keyboard();
+    unsafe {
+        let Some(Some(original)) = ORIGINAL_WNDPROC else {
+            return true;
+        };
+        //Whoever subclassed the window after us calls into wnd_proc, putting the original
+        //back would cut them off. Their chain has to stay intact, so we stay loaded.
+        if GetWindowLongPtrW(*hwnd, GWLP_WNDPROC) != wnd_proc as usize as isize {
+            log::warn!(
+                "The game window was subclassed after us, its window procedure can't be restored."
+            );
+            return false;
+        }
+        SetWindowLongPtrW(*hwnd, GWLP_WNDPROC, original as usize as isize);
+    }
+    log::info!("Restored the game's window procedure.");
+    true
+}
+
+//Reads the client area and the DPI scaling of the window.
+//The monitor DPI is queried as per-monitor aware, otherwise Windows reports 96 to DPI-unaware games.
+fn update_client_geometry(hwnd: HWND) {
+    let mut rect = RECT::default();
+    let mut monitor_dpi = 0;
+    let mut monitor_dpi_y = 0;
+    let window_dpi = unsafe {
+        GetClientRect(hwnd, &mut rect).ok();
+
+        let previous = SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+        let monitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST);
+        GetDpiForMonitor(
+            monitor,
+            MDT_EFFECTIVE_DPI,
+            &mut monitor_dpi,
+            &mut monitor_dpi_y,
+        )
+        .ok();
+        SetThreadDpiAwarenessContext(previous);
+
+        GetDpiForWindow(hwnd)
+    };
+
+    let dpi_scale = if monitor_dpi != 0 && window_dpi != 0 {
+        monitor_dpi as f32 / window_dpi as f32
+    } else {
+        1.0
+    };
+    let geometry = ClientGeometry {
+        width: (rect.right - rect.left).max(0) as u32,
+        height: (rect.bottom - rect.top).max(0) as u32,
+        dpi_scale,
+    };
+    log::debug!("Client geometry: {:?}", geometry);
+    *CLIENT_GEOMETRY.write().unwrap() = geometry;
+}
+
+fn get_x_lparam(lparam: LPARAM) -> i32 {
+    let lparam_u32 = lparam.0 as u32;
+    let x = (lparam_u32 & 0xFFFF) as i16;
+    x as i32
+}
+
+fn get_y_lparam(lparam: LPARAM) -> i32 {
+    let lparam_u32 = lparam.0 as u32;
+    let y = ((lparam_u32 >> 16) & 0xFFFF) as i16;
+    y as i32
+}
+
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct InputPacket {
+    id: u8,
+    x: i32,
+    y: i32,
+}
+
+//Events waiting to be sent by the UDP thread. wnd_proc is its only producer.
+static INPUT_QUEUE: OnceLock<InputQueue> = OnceLock::new();
+const INPUT_QUEUE_CAPACITY: usize = 1024;
+//How often queue drops/coalescing are reported to the statistics.
+const QUEUE_STATS_INTERVAL: Duration = Duration::from_millis(500);
+//Longest the subscription thread blocks on its socket.
+const SOCKET_TIMEOUT: Duration = Duration::from_millis(100);
+
+unsafe extern "system" fn wnd_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    let _call = HookCall::enter();
+    'local_handling: {
+        match msg {
+            //Mouse
+            WM_MOUSEMOVE => {
+                //The overlay expects coordinates in its own texture space.
+                let (x, y) = client_to_overlay(
+                    get_x_lparam(lparam),
+                    get_y_lparam(lparam),
+                    &CLIENT_GEOMETRY.read().unwrap(),
+                    &OVERLAY_GEOMETRY.read().unwrap(),
+                );
+
+                //let is_overlay_pixel = ui::is_overlay_pixel(x as u32, y as u32);
+
+                //Mouse up/down are seemingly handled globally.
+                //So we only need to pass MOUSEMOVE.
+                let id = match msg {
+                    /*WM_LBUTTONDOWN => 0,
+                    WM_LBUTTONUP => 1,*/
+                    WM_MOUSEMOVE => event_kind::MOUSE_MOVE,
+                    /*WM_RBUTTONDOWN => 3,
+                    WM_RBUTTONUP => 4,*/
+                    _ => {
+                        break 'local_handling;
+                    }
+                };
+
+                send_packet(InputPacket { id, x, y });
+                /*if is_overlay_pixel && msg == WM_LBUTTONDOWN && msg == WM_RBUTTONDOWN {
+                    return LRESULT(0);
+                }*/
+            }
+            WM_KEYDOWN | WM_KEYUP | WM_CHAR => {
+                //Keybinds keep working while the overlay has the keyboard.
+                let trigger = Trigger::Key(wparam.0 as u16);
+                let consumed = match msg {
+                    WM_KEYDOWN => trigger_down(trigger),
+                    WM_KEYUP => trigger_up(trigger),
+                    _ => false,
+                };
+                if consumed {
+                    return LRESULT(0);
+                }
+                if KEYBOARD_CAPTURED.load(Ordering::Relaxed) {
+                    let id = match msg {
+                        WM_KEYDOWN => event_kind::KEY_DOWN,
+                        WM_KEYUP => event_kind::KEY_UP,
+                        _ => event_kind::CHAR,
+                    };
+                    send_packet(InputPacket {
+                        id,
+                        x: wparam.0 as i32,
+                        y: lparam.0 as i32,
+                    });
+                    return LRESULT(0);
+                }
+            }
+            //Alt+key and F10 come as system keys.
+            WM_SYSKEYDOWN | WM_SYSKEYUP => {
+                let trigger = Trigger::Key(wparam.0 as u16);
+                let consumed = if msg == WM_SYSKEYDOWN {
+                    trigger_down(trigger)
+                } else {
+                    trigger_up(trigger)
+                };
+                if consumed {
+                    return LRESULT(0);
+                }
+            }
+            WM_LBUTTONDOWN | WM_RBUTTONDOWN | WM_MBUTTONDOWN | WM_XBUTTONDOWN | WM_LBUTTONUP
+            | WM_RBUTTONUP | WM_MBUTTONUP | WM_XBUTTONUP => {
+                let button = match msg {
+                    WM_LBUTTONDOWN | WM_LBUTTONUP => MouseButton::Left,
+                    WM_RBUTTONDOWN | WM_RBUTTONUP => MouseButton::Right,
+                    WM_MBUTTONDOWN | WM_MBUTTONUP => MouseButton::Middle,
+                    //The high word of wparam tells which X button.
+                    _ if (wparam.0 >> 16) & 0xFFFF == XBUTTON1 => MouseButton::X1,
+                    _ => MouseButton::X2,
+                };
+                let trigger = Trigger::Mouse(button);
+                let consumed = match msg {
+                    WM_LBUTTONDOWN | WM_RBUTTONDOWN | WM_MBUTTONDOWN | WM_XBUTTONDOWN => {
+                        trigger_down(trigger)
+                    }
+                    _ => trigger_up(trigger),
+                };
+                if consumed {
+                    //X button messages expect TRUE when handled.
+                    return LRESULT((msg == WM_XBUTTONDOWN || msg == WM_XBUTTONUP) as isize);
+                }
+            }
+            WM_TIMER if wparam.0 == HOLD_TIMER_ID => {
+                hold_timer_elapsed();
+                return LRESULT(0);
+            }
+            WM_SETCURSOR => {
+                if apply_cursor_override(hwnd, lparam) {
+                    return LRESULT(1);
+                }
+            }
+            //Never consumed, the game may rely on raw input as well.
+            WM_INPUT => {
+                if INPUT_SETTINGS.raw_input_enabled.load(Ordering::Relaxed)
+                    && (INPUT_SETTINGS
+                        .raw_input_during_mouse_look
+                        .load(Ordering::Relaxed)
+                        || !raw_input::is_mouse_look())
+                {
+                    if let Some(mouse) = raw_input::read_raw_mouse(lparam) {
+                        for packet in raw_input::raw_mouse_packets(&mouse).into_iter().flatten() {
+                            send_packet(packet);
+                        }
+                    }
+                }
+            }
+            WM_SIZE | WM_DPICHANGED => update_client_geometry(hwnd),
+            WM_SETFOCUS => handle_focus_event(hwnd, FocusEvent::SetFocus),
+            WM_KILLFOCUS => {
+                //Key ups won't come while another window has the focus.
+                reset_triggers();
+                handle_focus_event(hwnd, FocusEvent::KillFocus);
+            }
+            //The high word of WM_ACTIVATE's wparam is the minimized state.
+            WM_ACTIVATE => handle_focus_event(hwnd, FocusEvent::Activate(wparam.0 & 0xFFFF != 0)),
+            WM_ACTIVATEAPP => handle_focus_event(hwnd, FocusEvent::Activate(wparam.0 != 0)),
+            WM_SYNTHETIC_INPUT => {
+                let id = wparam.0 as u8;
+                let (x, y) = if id == event_kind::OVERLAY_CURSOR_MODE {
+                    let window_active = unsafe { GetForegroundWindow() } == hwnd;
+                    handle_focus_event(hwnd, FocusEvent::InteractiveChanged { window_active });
+                    (get_x_lparam(lparam), 0)
+                } else {
+                    client_to_overlay(
+                        get_x_lparam(lparam),
+                        get_y_lparam(lparam),
+                        &CLIENT_GEOMETRY.read().unwrap(),
+                        &OVERLAY_GEOMETRY.read().unwrap(),
+                    )
+                };
+                send_packet(InputPacket { id, x, y });
+                return LRESULT(0);
+            }
+            WM_REPLAY_INPUT => {
+                let id = wparam.0 as u8;
+                let (x, y) = recording::unpack_coordinates(lparam);
+                //Not recorded again, a replay would otherwise become the latest recording.
+                queue_packet(InputPacket { id, x, y });
+                return LRESULT(0);
+            }
+            WM_OVERLAY_INTERACTIVE_CHANGED => {
+                let window_active = unsafe { GetForegroundWindow() } == hwnd;
+                handle_focus_event(hwnd, FocusEvent::InteractiveChanged { window_active });
+                return LRESULT(0);
+            }
+            _ => {}
+        }
+    }
+    unsafe {
+        if let Some(original) = ORIGINAL_WNDPROC {
+            CallWindowProcW(original, hwnd, msg, wparam, lparam)
+        } else {
+            DefWindowProcW(hwnd, msg, wparam, lparam)
+        }
+    }
+}
+
+//Send packet to listening thread. Must only be called from wnd_proc.
+fn send_packet(packet: InputPacket) {
+    recording::record_packet(&packet);
+    queue_packet(packet);
+}
+
+//send_packet() without recording it.
+fn queue_packet(packet: InputPacket) {
+    if let Some(queue) = INPUT_QUEUE.get() {
+        //wnd_proc only runs on the window thread, so there is a single producer.
+        unsafe { queue.push(packet) };
+    }
+}
+
+///Turns raw input forwarding on or off, registering the mouse if needed.
+pub fn set_raw_input_enabled(enabled: bool) {
+    INPUT_SETTINGS
+        .raw_input_enabled
+        .store(enabled, Ordering::Relaxed);
+    if let Some(hwnd) = GAME_HWND.get() {
+        if enabled {
+            raw_input::register(*hwnd);
+        } else {
+            raw_input::unregister();
+        }
+    }
+}
+
+//Applies the configured focus policy to a focus event.
+fn handle_focus_event(hwnd: HWND, event: FocusEvent) {
+    let actions = focus::decide(
+        INPUT_SETTINGS.focus_policy(),
+        event,
+        is_overlay_interactive(),
+    );
+    unsafe {
+        if actions.set_foreground {
+            SetForegroundWindow(hwnd).ok().ok();
+        }
+        if actions.set_focus {
+            SetFocus(hwnd);
+        }
+        if actions.set_capture {
+            SetCapture(hwnd);
+        }
+        if actions.release_capture {
+            ReleaseCapture().ok();
+        }
+    }
+}
+
+//Every consumer currently listening to input events.
+static SUBSCRIBERS: OnceLock<Arc<Mutex<SubscriberTable>>> = OnceLock::new();
+//Socket bound to input.subscribe_address, replaced when the address changes.
+static INPUT_SOCKET: RwLock<Option<Arc<UdpSocket>>> = RwLock::new(None);
+
+fn input_socket() -> Option<Arc<UdpSocket>> {
+    INPUT_SOCKET.read().unwrap().clone()
+}
+
+fn bind_input_socket(addr: SocketAddr) -> io::Result<UdpSocket> {
+    let socket = UdpSocket::bind(addr)?;
+    //Wake up regularly even if nobody talks to us, so dead subscribers still get dropped,
+    //a new socket is picked up and detatch() doesn't wait long.
+    socket.set_read_timeout(Some(SOCKET_TIMEOUT))?;
+    Ok(socket)
+}
+
+///Starts the input threads.
+///- The sender thread forwards every packet from wnd_proc to each subscriber that asked for it.
+///- The subscription thread handles registrations and heartbeats on input.subscribe_address,
+///  and drops consumers that stopped sending heartbeats.
+///The legacy input.legacy_address listener is always subscribed to mouse moves and clicks.
+pub fn start_mouse_input_thread() {
+    if INPUT_QUEUE
+        .set(InputQueue::new(INPUT_QUEUE_CAPACITY))
+        .is_err()
+    {
+        log::warn!("The input thread is already running.");
+        return;
+    }
+
+    let subscribers = SUBSCRIBERS
+        .get_or_init(|| Arc::new(Mutex::new(SubscriberTable::default())))
+        .clone();
+    let input = config().input.clone();
+    subscribers
+        .lock()
+        .unwrap()
+        .add_permanent(input.legacy_address, LEGACY_FILTER, Instant::now());
+
+    //Events are sent from the subscription socket so consumers get acks and events from the same address.
+    let socket = bind_input_socket(input.subscribe_address).unwrap_or_else(|e| {
+        log::warn!(
+            "Could not bind {} ({}). Only {} will receive input.",
+            input.subscribe_address,
+            e,
+            input.legacy_address
+        );
+        bind_input_socket("127.0.0.1:0".parse().unwrap()).expect("Failed to bind socket")
+    });
+    *INPUT_SOCKET.write().unwrap() = Some(Arc::new(socket));
+
+    start_subscription_thread(subscribers.clone());
+
+    spawn_worker("input sender", move || {
+        let queue = INPUT_QUEUE.get().unwrap();
+        let mut reported = QueueStats::default();
+        let mut last_report = Instant::now();
+
+        while let Some(packet) = queue.pop() {
+            let data = unsafe {
+                from_raw_parts(
+                    &packet as *const InputPacket as *const u8,
+                    size_of::<InputPacket>(),
+                )
+            };
+            if let Some(socket) = input_socket() {
+                let table = subscribers.lock().unwrap();
+                for addr in table.recipients(packet.id) {
+                    socket.send_to(data, addr).ok();
+                }
+            }
+
+            if last_report.elapsed() >= QUEUE_STATS_INTERVAL {
+                let stats = queue.stats();
+                if stats != reported {
+                    send_statistic(debug_stat::INPUT_DROPPED, stats.dropped);
+                    send_statistic(debug_stat::INPUT_COALESCED, stats.coalesced);
+                    reported = stats;
+                }
+                last_report = Instant::now();
+            }
+        }
+        let stats = queue.stats();
+        log::info!(
+            "Input thread stopped. {} events dropped, {} mouse moves coalesced.",
+            stats.dropped,
+            stats.coalesced
+        );
+    });
+}
+
+///Stops accepting input. The sender thread sends the pending events and returns,
+///stop_workers() waits for it.
+pub fn stop_mouse_input_thread() {
+    if let Some(queue) = INPUT_QUEUE.get() {
+        queue.close();
+    }
+}
+
+///Closes the subscription socket. The subscription thread must be stopped already.
+pub fn close_input_socket() {
+    if INPUT_SOCKET
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+        .is_some()
+    {
+        log::info!("Closed the input socket.");
+    }
+}
+
+///Applies changed input addresses while the input threads are running.
+///Consumers registered on the previous subscription address have to register again.
+pub fn apply_input_transport(old: &InputConfig, new: &InputConfig) {
+    let Some(subscribers) = SUBSCRIBERS.get() else {
+        return;
+    };
+    if old.legacy_address != new.legacy_address {
+        let mut table = subscribers.lock().unwrap();
+        table.remove_permanent(old.legacy_address);
+        table.add_permanent(new.legacy_address, LEGACY_FILTER, Instant::now());
+        log::info!("Legacy input listener moved to {}.", new.legacy_address);
+    }
+    if old.subscribe_address != new.subscribe_address {
+        match bind_input_socket(new.subscribe_address) {
+            Ok(socket) => {
+                *INPUT_SOCKET.write().unwrap() = Some(Arc::new(socket));
+                log::info!("Input subscriptions now on {}.", new.subscribe_address);
+            }
+            Err(e) => log::warn!(
+                "Could not bind {} ({}), keeping the current subscription socket.",
+                new.subscribe_address,
+                e
+            ),
+        }
+    }
+}
+
+fn start_subscription_thread(subscribers: Arc<Mutex<SubscriberTable>>) {
+    spawn_worker("input subscriptions", move || {
+        let mut buf = [0u8; 64];
+        //Subscriber that captured the keyboard. The keyboard is given back to the game if it goes away.
+        let mut keyboard_owner: Option<SocketAddr> = None;
+        while !stopping() {
+            //Picked up again every time, the address can change at runtime.
+            let Some(socket) = input_socket() else {
+                std::thread::sleep(SOCKET_TIMEOUT);
+                continue;
+            };
+            let received = socket.recv_from(&mut buf);
+            let now = Instant::now();
+            let mut table = subscribers.lock().unwrap();
+            let mut gone = Vec::new();
+
+            if let Ok((len, addr)) = received {
+                let data = &buf[..len];
+                if control_channel::is_control_packet(data) {
+                    if let Some(request) =
+                        handle_control_packet(&socket, &mut table, data, addr, now)
+                    {
+                        match request {
+                            ControlRequest::CaptureKeyboard => keyboard_owner = Some(addr),
+                            ControlRequest::ReleaseKeyboard => keyboard_owner = None,
+                            _ => {}
+                        }
+                    }
+                } else {
+                    match Request::parse(data) {
+                        Some(Request::Register { filter }) => {
+                            if table.register(addr, filter, now) {
+                                log::info!(
+                                    "Input subscriber registered: {} (filter {:#x})",
+                                    addr,
+                                    filter
+                                );
+                            }
+                            socket.send_to(&ack_packet(filter), addr).ok();
+                        }
+                        Some(Request::Heartbeat) => {
+                            if !table.heartbeat(addr, now) {
+                                socket.send_to(&[response::NOT_REGISTERED], addr).ok();
+                            }
+                        }
+                        Some(Request::Unregister) => {
+                            if table.unregister(addr) {
+                                log::info!("Input subscriber unregistered: {}", addr);
+                                gone.push(addr);
+                            }
+                        }
+                        None => {
+                            log::debug!("Ignoring malformed subscription packet from {}", addr)
+                        }
+                    }
+                }
+            }
+
+            for addr in table.prune(now) {
+                log::info!(
+                    "Input subscriber {} missed its heartbeats, dropping it.",
+                    addr
+                );
+                gone.push(addr);
+            }
+            if keyboard_owner.is_some_and(|owner| gone.contains(&owner)) {
+                keyboard_owner = None;
+                Win32Backend.release_keyboard();
+            }
+        }
+    });
+}
+
+//Answers a control request. Returns the request if it was applied.
+fn handle_control_packet(
+    socket: &UdpSocket,
+    table: &mut SubscriberTable,
+    data: &[u8],
+    addr: SocketAddr,
+    now: Instant,
+) -> Option<ControlRequest> {
+    let (seq, request) = match ControlRequest::parse(data) {
+        Ok(parsed) => parsed,
+        Err(seq) => {
+            log::debug!("Malformed control request from {}", addr);
+            if let Some(seq) = seq {
+                let ack = control_channel::ack_packet(seq, data[0], AckStatus::Malformed);
+                socket.send_to(&ack, addr).ok();
+            }
+            return None;
+        }
+    };
+
+    //A request counts as a heartbeat.
+    let status = if table.heartbeat(addr, now) {
+        dispatch(&Win32Backend, request)
+    } else {
+        AckStatus::NotRegistered
+    };
+    log::debug!("Control request {:?} from {}: {:?}", request, addr, status);
+    socket
+        .send_to(&control_channel::ack_packet(seq, data[0], status), addr)
+        .ok();
+
+    (status == AckStatus::Ok).then_some(request)
+}
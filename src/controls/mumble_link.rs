@@ -0,0 +1,95 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use windows::{
+    Win32::{
+        Foundation::{BOOL, CloseHandle, HANDLE},
+        System::Memory::{
+            FILE_MAP_READ, MEMORY_MAPPED_VIEW_ADDRESS, MapViewOfFile, OpenFileMappingW,
+            UnmapViewOfFile,
+        },
+    },
+    core::w,
+};
+
+use crate::shutdown::stopping;
+
+//GW2 publishes its state in the MumbleLink shared memory, used here to know if
+//the player is typing in a text box (chat, search...).
+//https://wiki.guildwars2.com/wiki/API:MumbleLink
+const LINKED_MEM_SIZE: usize = 5460;
+//uiVersion, uiTick, avatar position/front/top, name[256], camera position/front/top,
+//identity[256], context_len, then the GW2 context.
+const CONTEXT_OFFSET: usize = 1108;
+const UI_STATE_OFFSET: usize = CONTEXT_OFFSET + 48;
+const UI_STATE_TEXTBOX_FOCUSED: u32 = 1 << 5;
+//The game creates the mapping some time after starting, don't look for it on every key.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+struct Link {
+    view: Option<MEMORY_MAPPED_VIEW_ADDRESS>,
+    mapping: Option<HANDLE>,
+    last_attempt: Option<Instant>,
+}
+unsafe impl Send for Link {}
+
+static LINK: Mutex<Link> = Mutex::new(Link {
+    view: None,
+    mapping: None,
+    last_attempt: None,
+});
+
+///True while the game has a text box focused. False if the game doesn't provide MumbleLink.
+pub fn is_game_typing() -> bool {
+    let mut link = LINK.lock().unwrap();
+    if link.view.is_none() {
+        //Nothing is opened again once the DLL is detaching.
+        if stopping()
+            || link
+                .last_attempt
+                .is_some_and(|last| last.elapsed() < RETRY_INTERVAL)
+        {
+            return false;
+        }
+        link.last_attempt = Some(Instant::now());
+        let Some((view, mapping)) = open_link() else {
+            return false;
+        };
+        link.view = Some(view);
+        link.mapping = Some(mapping);
+    }
+    let Some(view) = link.view else {
+        return false;
+    };
+    let ui_state = unsafe {
+        std::ptr::read_unaligned((view.Value as *const u8).add(UI_STATE_OFFSET) as *const u32)
+    };
+    ui_state & UI_STATE_TEXTBOX_FOCUSED != 0
+}
+
+fn open_link() -> Option<(MEMORY_MAPPED_VIEW_ADDRESS, HANDLE)> {
+    unsafe {
+        let mapping = OpenFileMappingW(FILE_MAP_READ.0, BOOL(0), w!("MumbleLink")).ok()?;
+        let view = MapViewOfFile(mapping, FILE_MAP_READ, 0, 0, LINKED_MEM_SIZE);
+        if view.Value.is_null() {
+            CloseHandle(mapping).ok();
+            return None;
+        }
+        Some((view, mapping))
+    }
+}
+
+///Unmaps the view and closes the mapping, called by detatch().
+pub fn close_link() {
+    let mut link = LINK.lock().unwrap_or_else(|e| e.into_inner());
+    unsafe {
+        if let Some(view) = link.view.take() {
+            UnmapViewOfFile(view).ok();
+        }
+        if let Some(mapping) = link.mapping.take() {
+            CloseHandle(mapping).ok();
+        }
+    }
+}
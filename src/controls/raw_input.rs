@@ -0,0 +1,144 @@
+use std::{
+    mem::size_of,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use windows::Win32::{
+    Devices::HumanInterfaceDevice::{HID_USAGE_GENERIC_MOUSE, HID_USAGE_PAGE_GENERIC},
+    Foundation::{HWND, LPARAM},
+    UI::{
+        Input::{
+            GetRawInputData, GetRegisteredRawInputDevices, HRAWINPUT, MOUSE_MOVE_ABSOLUTE,
+            RAWINPUT, RAWINPUTDEVICE, RAWINPUTDEVICE_FLAGS, RAWINPUTHEADER, RAWMOUSE, RID_INPUT,
+            RIDEV_REMOVE, RIM_TYPEMOUSE, RegisterRawInputDevices,
+        },
+        WindowsAndMessaging::{CURSOR_SHOWING, CURSORINFO, GetCursorInfo},
+    },
+};
+
+use super::{InputPacket, event_kind};
+
+//Only one raw input registration per device type can exist in a process.
+//If the game registered the mouse itself, WM_INPUT already reaches us and we must not touch it.
+static REGISTERED_BY_US: AtomicBool = AtomicBool::new(false);
+
+//Registers the mouse for raw input, unless the game already did.
+pub fn register(hwnd: HWND) {
+    if REGISTERED_BY_US.load(Ordering::Relaxed) {
+        return;
+    }
+    if is_mouse_registered_by_game() {
+        log::info!("The game already receives raw mouse input, reusing it.");
+        return;
+    }
+    let device = RAWINPUTDEVICE {
+        usUsagePage: HID_USAGE_PAGE_GENERIC,
+        usUsage: HID_USAGE_GENERIC_MOUSE,
+        //No RIDEV_NOLEGACY, the game still needs its regular mouse messages.
+        dwFlags: RAWINPUTDEVICE_FLAGS(0),
+        hwndTarget: hwnd,
+    };
+    match unsafe { RegisterRawInputDevices(&[device], size_of::<RAWINPUTDEVICE>() as u32) } {
+        Ok(()) => {
+            REGISTERED_BY_US.store(true, Ordering::Relaxed);
+            log::info!("Registered raw mouse input.");
+        }
+        Err(e) => log::error!("Could not register raw mouse input: {}", e),
+    }
+}
+
+//Removes our registration. Does nothing if the registration belongs to the game.
+pub fn unregister() {
+    if !REGISTERED_BY_US.swap(false, Ordering::Relaxed) {
+        return;
+    }
+    let device = RAWINPUTDEVICE {
+        usUsagePage: HID_USAGE_PAGE_GENERIC,
+        usUsage: HID_USAGE_GENERIC_MOUSE,
+        dwFlags: RIDEV_REMOVE,
+        hwndTarget: HWND(0),
+    };
+    match unsafe { RegisterRawInputDevices(&[device], size_of::<RAWINPUTDEVICE>() as u32) } {
+        Ok(()) => log::info!("Unregistered raw mouse input."),
+        Err(e) => log::error!("Could not unregister raw mouse input: {}", e),
+    }
+}
+
+fn is_mouse_registered_by_game() -> bool {
+    let mut count = 0u32;
+    let size = size_of::<RAWINPUTDEVICE>() as u32;
+    unsafe {
+        GetRegisteredRawInputDevices(None, &mut count, size);
+        if count == 0 {
+            return false;
+        }
+        let mut devices = vec![RAWINPUTDEVICE::default(); count as usize];
+        if GetRegisteredRawInputDevices(Some(devices.as_mut_ptr()), &mut count, size) == u32::MAX {
+            return false;
+        }
+        devices.iter().any(|d| {
+            d.usUsagePage == HID_USAGE_PAGE_GENERIC && d.usUsage == HID_USAGE_GENERIC_MOUSE
+        })
+    }
+}
+
+//Reads the mouse data of a WM_INPUT message. The data stays available for the game.
+pub fn read_raw_mouse(lparam: LPARAM) -> Option<RAWMOUSE> {
+    let mut raw = RAWINPUT::default();
+    let mut size = size_of::<RAWINPUT>() as u32;
+    let read = unsafe {
+        GetRawInputData(
+            HRAWINPUT(lparam.0),
+            RID_INPUT,
+            Some(&mut raw as *mut RAWINPUT as *mut _),
+            &mut size,
+            size_of::<RAWINPUTHEADER>() as u32,
+        )
+    };
+    if read == u32::MAX || read == 0 || raw.header.dwType != RIM_TYPEMOUSE.0 {
+        return None;
+    }
+    Some(unsafe { raw.data.mouse })
+}
+
+///Turns raw mouse data into packets.
+///- RAW_MOUSE_MOVE: x/y are the relative motion. Absolute devices (tablets, some VMs) are ignored.
+///- RAW_MOUSE_BUTTON: x is usButtonFlags (RI_MOUSE_*), y is usButtonData (eg. the wheel delta).
+pub fn raw_mouse_packets(mouse: &RAWMOUSE) -> [Option<InputPacket>; 2] {
+    let motion = if mouse.usFlags.0 & MOUSE_MOVE_ABSOLUTE.0 == 0
+        && (mouse.lLastX != 0 || mouse.lLastY != 0)
+    {
+        Some(InputPacket {
+            id: event_kind::RAW_MOUSE_MOVE,
+            x: mouse.lLastX,
+            y: mouse.lLastY,
+        })
+    } else {
+        None
+    };
+
+    let buttons = unsafe { mouse.Anonymous.Anonymous };
+    let buttons = if buttons.usButtonFlags != 0 {
+        Some(InputPacket {
+            id: event_kind::RAW_MOUSE_BUTTON,
+            x: buttons.usButtonFlags as i32,
+            y: buttons.usButtonData as i16 as i32,
+        })
+    } else {
+        None
+    };
+
+    [motion, buttons]
+}
+
+//The game hides the cursor while the camera is being rotated.
+pub fn is_mouse_look() -> bool {
+    let mut info = CURSORINFO {
+        cbSize: size_of::<CURSORINFO>() as u32,
+        ..Default::default()
+    };
+    if unsafe { GetCursorInfo(&mut info) }.is_err() {
+        return false;
+    }
+    info.flags.0 & CURSOR_SHOWING.0 == 0
+}
@@ -0,0 +1,589 @@
+use std::{
+    fs::{File, create_dir_all, read_dir},
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::Local;
+use windows::Win32::{
+    Foundation::{LPARAM, WPARAM},
+    UI::WindowsAndMessaging::PostMessageW,
+};
+
+use super::{CLIENT_GEOMETRY, InputPacket, WM_REPLAY_INPUT, coords::ClientGeometry};
+use crate::{
+    config::{config, data_dir::resolve_path},
+    globals::GAME_HWND,
+    shutdown::{STOP_CHECK_INTERVAL, spawn_worker, stopping},
+    ui::{OVERLAY_GEOMETRY, OverlayGeometry},
+};
+
+//---------------------------------------- File format ---------------------------------------
+//Everything is little-endian.
+//Header: MAGIC, version: u16
+//Then any number of records: tag: u8, time since the start of the recording in µs: u64, body.
+//- RECORD_GEOMETRY body: client width/height: u32, dpi scale: f32, backbuffer width/height: u32,
+//  viewport x/y/width/height: f32, texture width/height: u32
+//- RECORD_EVENT body: id: u8, x: i32, y: i32 (exactly what was sent to subscribers)
+//A geometry record is written before the first event and every time the geometry changes.
+pub const MAGIC: &[u8; 8] = b"DX11INPT";
+pub const VERSION: u16 = 1;
+const RECORD_GEOMETRY: u8 = 1;
+const RECORD_EVENT: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Record {
+    Geometry {
+        time_us: u64,
+        client: ClientGeometry,
+        overlay: OverlayGeometry,
+    },
+    Event {
+        time_us: u64,
+        id: u8,
+        x: i32,
+        y: i32,
+    },
+}
+
+impl Record {
+    pub fn time_us(&self) -> u64 {
+        match self {
+            Record::Geometry { time_us, .. } | Record::Event { time_us, .. } => *time_us,
+        }
+    }
+}
+
+pub struct RecordingWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> RecordingWriter<W> {
+    ///Writes the header right away.
+    pub fn new(mut out: W) -> io::Result<Self> {
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        Ok(Self { out })
+    }
+
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        let out = &mut self.out;
+        match *record {
+            Record::Geometry {
+                time_us,
+                client,
+                overlay,
+            } => {
+                out.write_all(&[RECORD_GEOMETRY])?;
+                out.write_all(&time_us.to_le_bytes())?;
+                out.write_all(&client.width.to_le_bytes())?;
+                out.write_all(&client.height.to_le_bytes())?;
+                out.write_all(&client.dpi_scale.to_le_bytes())?;
+                out.write_all(&overlay.backbuffer_width.to_le_bytes())?;
+                out.write_all(&overlay.backbuffer_height.to_le_bytes())?;
+                out.write_all(&overlay.viewport_x.to_le_bytes())?;
+                out.write_all(&overlay.viewport_y.to_le_bytes())?;
+                out.write_all(&overlay.viewport_width.to_le_bytes())?;
+                out.write_all(&overlay.viewport_height.to_le_bytes())?;
+                out.write_all(&overlay.texture_width.to_le_bytes())?;
+                out.write_all(&overlay.texture_height.to_le_bytes())?;
+            }
+            Record::Event { time_us, id, x, y } => {
+                out.write_all(&[RECORD_EVENT])?;
+                out.write_all(&time_us.to_le_bytes())?;
+                out.write_all(&[id])?;
+                out.write_all(&x.to_le_bytes())?;
+                out.write_all(&y.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+pub struct RecordingReader<R: Read> {
+    input: R,
+}
+
+impl<R: Read> RecordingReader<R> {
+    ///Checks the header. Fails with ErrorKind::InvalidData if it isn't a recording we understand.
+    pub fn new(mut input: R) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "not an input recording",
+            ));
+        }
+        let version = u16::from_le_bytes(read_array(&mut input)?);
+        if version != VERSION {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("unsupported recording version {}", version),
+            ));
+        }
+        Ok(Self { input })
+    }
+
+    ///Returns None at the end of the file. A record cut in half is an error.
+    pub fn next_record(&mut self) -> io::Result<Option<Record>> {
+        let mut tag = [0u8; 1];
+        match self.input.read_exact(&mut tag) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let input = &mut self.input;
+        let time_us = u64::from_le_bytes(read_array(input)?);
+
+        let record = match tag[0] {
+            RECORD_GEOMETRY => Record::Geometry {
+                time_us,
+                client: ClientGeometry {
+                    width: u32::from_le_bytes(read_array(input)?),
+                    height: u32::from_le_bytes(read_array(input)?),
+                    dpi_scale: f32::from_le_bytes(read_array(input)?),
+                },
+                overlay: OverlayGeometry {
+                    backbuffer_width: u32::from_le_bytes(read_array(input)?),
+                    backbuffer_height: u32::from_le_bytes(read_array(input)?),
+                    viewport_x: f32::from_le_bytes(read_array(input)?),
+                    viewport_y: f32::from_le_bytes(read_array(input)?),
+                    viewport_width: f32::from_le_bytes(read_array(input)?),
+                    viewport_height: f32::from_le_bytes(read_array(input)?),
+                    texture_width: u32::from_le_bytes(read_array(input)?),
+                    texture_height: u32::from_le_bytes(read_array(input)?),
+                },
+            },
+            RECORD_EVENT => Record::Event {
+                time_us,
+                id: read_array::<1>(input)?[0],
+                x: i32::from_le_bytes(read_array(input)?),
+                y: i32::from_le_bytes(read_array(input)?),
+            },
+            tag => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("unknown record tag {}", tag),
+                ));
+            }
+        };
+        Ok(Some(record))
+    }
+}
+
+impl<R: Read> Iterator for RecordingReader<R> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+fn read_array<const N: usize>(input: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    input.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+//---------------------------------------- Recording ---------------------------------------
+
+struct ActiveRecording {
+    writer: RecordingWriter<BufWriter<File>>,
+    started: Instant,
+    last_client: Option<ClientGeometry>,
+    last_overlay: Option<OverlayGeometry>,
+}
+
+static RECORDING_ENABLED: AtomicBool = AtomicBool::new(false);
+static RECORDING: Mutex<Option<ActiveRecording>> = Mutex::new(None);
+
+///Starts recording to a new file, or stops the current recording.
+pub fn toggle_recording() {
+    let mut recording = RECORDING.lock().unwrap();
+    if let Some(mut active) = recording.take() {
+        RECORDING_ENABLED.store(false, Ordering::Relaxed);
+        active.writer.flush().ok();
+        log::info!("Input recording stopped.");
+        return;
+    }
+
+    let dir = resolve_path(&config().paths.recordings);
+    let path = dir.join(format!(
+        "input-{}.rec",
+        Local::now().format("%Y-%m-%d_%H-%M-%S")
+    ));
+    let writer = create_dir_all(&dir)
+        .and_then(|_| File::create(&path))
+        .and_then(|file| RecordingWriter::new(BufWriter::new(file)));
+    match writer {
+        Ok(writer) => {
+            *recording = Some(ActiveRecording {
+                writer,
+                started: Instant::now(),
+                last_client: None,
+                last_overlay: None,
+            });
+            RECORDING_ENABLED.store(true, Ordering::Relaxed);
+            log::info!("Recording input to {}", path.display());
+        }
+        Err(e) => log::error!("Could not start recording to {}: {}", path.display(), e),
+    }
+}
+
+///Called for every packet sent to subscribers.
+pub fn record_packet(packet: &InputPacket) {
+    if !RECORDING_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut recording = RECORDING.lock().unwrap();
+    let Some(active) = recording.as_mut() else {
+        return;
+    };
+    let time_us = active.started.elapsed().as_micros() as u64;
+
+    let client = *CLIENT_GEOMETRY.read().unwrap();
+    let overlay = *OVERLAY_GEOMETRY.read().unwrap();
+    let mut result = Ok(());
+    if active.last_client != Some(client) || active.last_overlay != Some(overlay) {
+        active.last_client = Some(client);
+        active.last_overlay = Some(overlay);
+        result = active.writer.write(&Record::Geometry {
+            time_us,
+            client,
+            overlay,
+        });
+    }
+    let (id, x, y) = (packet.id, packet.x, packet.y);
+    result = result.and_then(|_| active.writer.write(&Record::Event { time_us, id, x, y }));
+
+    if let Err(e) = result {
+        log::error!("Input recording failed, stopping it: {}", e);
+        RECORDING_ENABLED.store(false, Ordering::Relaxed);
+        *recording = None;
+    }
+}
+
+//---------------------------------------- Replay ---------------------------------------
+//Bumped to start or cancel a replay. A replay stops as soon as it isn't the current generation,
+//so one that is still winding down can't be mistaken for the next one.
+static REPLAY_GENERATION: AtomicU64 = AtomicU64::new(0);
+//Generation of the running replay, 0 if none.
+static REPLAY_RUNNING: AtomicU64 = AtomicU64::new(0);
+
+///Replays the most recent recording at `speed` times the original speed.
+///Calling it again while a replay is running stops it.
+pub fn replay_latest(speed: f32) {
+    if stop_replay() {
+        log::info!("Stopping input replay.");
+        return;
+    }
+    let dir = resolve_path(&config().paths.recordings);
+    match latest_recording(&dir) {
+        Some(path) => replay_file(path, speed),
+        None => log::warn!("No input recording found in {}", dir.display()),
+    }
+}
+
+fn latest_recording(dir: &Path) -> Option<PathBuf> {
+    //Names contain the date, so the last one alphabetically is the most recent.
+    read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rec"))
+        .max()
+}
+
+///Feeds a recording back through wnd_proc on its own thread, so subscribers receive the
+///exact same packets with the same timing (divided by `speed`).
+///A replay that is already running is stopped.
+pub fn replay_file(path: PathBuf, speed: f32) {
+    let speed = if speed > 0.0 { speed } else { 1.0 };
+    let generation = REPLAY_GENERATION.fetch_add(1, Ordering::AcqRel) + 1;
+    REPLAY_RUNNING.store(generation, Ordering::Release);
+
+    spawn_worker("replay", move || {
+        let reader = File::open(&path).and_then(|f| RecordingReader::new(BufReader::new(f)));
+        let reader = match reader {
+            Ok(reader) => reader,
+            Err(e) => {
+                log::error!("Could not replay {}: {}", path.display(), e);
+                replay_ended(generation);
+                return;
+            }
+        };
+        log::info!("Replaying {} at {}x speed", path.display(), speed);
+
+        let started = Instant::now();
+        let mut count = 0;
+        for record in reader {
+            if replay_cancelled(generation) {
+                break;
+            }
+            let record = match record {
+                Ok(record) => record,
+                Err(e) => {
+                    log::error!("Replay stopped, corrupted recording: {}", e);
+                    break;
+                }
+            };
+
+            let due = Duration::from_secs_f64(record.time_us() as f64 / 1_000_000.0 / speed as f64);
+            if let Some(wait) = due.checked_sub(started.elapsed())
+                && !sleep_unless_cancelled(generation, wait)
+            {
+                break;
+            }
+
+            match record {
+                Record::Geometry {
+                    client, overlay, ..
+                } => log::debug!("Replay geometry: {:?} {:?}", client, overlay),
+                Record::Event { id, x, y, .. } => {
+                    post_replayed_packet(id, x, y);
+                    count += 1;
+                }
+            }
+        }
+        replay_ended(generation);
+        log::info!("Replay finished, {} events sent.", count);
+    });
+}
+
+///Cancels the running replay. Returns false if there was none.
+fn stop_replay() -> bool {
+    let running = REPLAY_RUNNING.swap(0, Ordering::AcqRel);
+    if running == 0 {
+        return false;
+    }
+    //Unless a newer replay already took over.
+    REPLAY_GENERATION
+        .compare_exchange(running, running + 1, Ordering::AcqRel, Ordering::Acquire)
+        .ok();
+    true
+}
+
+fn replay_cancelled(generation: u64) -> bool {
+    REPLAY_GENERATION.load(Ordering::Acquire) != generation || stopping()
+}
+
+//Leaves REPLAY_RUNNING alone if a newer replay started meanwhile.
+fn replay_ended(generation: u64) {
+    REPLAY_RUNNING
+        .compare_exchange(generation, 0, Ordering::AcqRel, Ordering::Acquire)
+        .ok();
+}
+
+//Like sleep_unless_stopping(), also waking up when the replay is cancelled.
+fn sleep_unless_cancelled(generation: u64, duration: Duration) -> bool {
+    let started = Instant::now();
+    while !replay_cancelled(generation) {
+        let left = duration.saturating_sub(started.elapsed());
+        if left.is_zero() {
+            return true;
+        }
+        thread::sleep(left.min(STOP_CHECK_INTERVAL));
+    }
+    false
+}
+
+//Both coordinates go in lparam, x in the low dword and y in the high one.
+const _: () = assert!(
+    size_of::<LPARAM>() == 8,
+    "WM_REPLAY_INPUT needs a 64-bit lparam"
+);
+
+pub fn pack_coordinates(x: i32, y: i32) -> LPARAM {
+    LPARAM((x as u32 as u64 | ((y as u32 as u64) << 32)) as isize)
+}
+
+pub fn unpack_coordinates(lparam: LPARAM) -> (i32, i32) {
+    let packed = lparam.0 as u64;
+    (packed as u32 as i32, (packed >> 32) as u32 as i32)
+}
+
+fn post_replayed_packet(id: u8, x: i32, y: i32) {
+    if let Some(hwnd) = GAME_HWND.get() {
+        unsafe {
+            PostMessageW(
+                *hwnd,
+                WM_REPLAY_INPUT,
+                WPARAM(id as usize),
+                pack_coordinates(x, y),
+            )
+            .ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GEOMETRY: Record = Record::Geometry {
+        time_us: 0,
+        client: ClientGeometry {
+            width: 1920,
+            height: 1080,
+            dpi_scale: 1.5,
+        },
+        overlay: OverlayGeometry {
+            backbuffer_width: 2880,
+            backbuffer_height: 1620,
+            viewport_x: 10.5,
+            viewport_y: -4.0,
+            viewport_width: 2860.0,
+            viewport_height: 1600.25,
+            texture_width: 2560,
+            texture_height: 1440,
+        },
+    };
+    const GEOMETRY_SIZE: usize = 1 + 8 + 4 * 11;
+    const EVENT_SIZE: usize = 1 + 8 + 1 + 4 + 4;
+
+    fn event(time_us: u64, id: u8, x: i32, y: i32) -> Record {
+        Record::Event { time_us, id, x, y }
+    }
+
+    fn write(records: &[Record]) -> Vec<u8> {
+        let mut writer = RecordingWriter::new(Vec::new()).unwrap();
+        for record in records {
+            writer.write(record).unwrap();
+        }
+        writer.into_inner()
+    }
+
+    fn read(bytes: &[u8]) -> io::Result<Vec<Record>> {
+        RecordingReader::new(bytes)?.collect()
+    }
+
+    fn error_kind<T: std::fmt::Debug>(result: io::Result<T>) -> ErrorKind {
+        result.unwrap_err().kind()
+    }
+
+    #[test]
+    fn round_trip() {
+        let records = [
+            GEOMETRY,
+            event(0, 2, 100, 200),
+            event(1_500, 5, -12, 7),
+            event(u64::MAX, 255, i32::MIN, i32::MAX),
+            Record::Geometry {
+                time_us: 2_000_000,
+                client: ClientGeometry::EMPTY,
+                overlay: OverlayGeometry::EMPTY,
+            },
+            event(2_000_001, 0, 0, 0),
+        ];
+        let bytes = write(&records);
+        assert_eq!(
+            bytes.len(),
+            MAGIC.len() + 2 + 2 * GEOMETRY_SIZE + 4 * EVENT_SIZE
+        );
+        assert_eq!(read(&bytes).unwrap(), records);
+    }
+
+    #[test]
+    fn header_layout() {
+        let bytes = write(&[]);
+        assert_eq!(&bytes[..8], b"DX11INPT");
+        assert_eq!(bytes[8..], VERSION.to_le_bytes());
+        assert_eq!(read(&bytes).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn event_layout() {
+        let bytes = write(&[event(0x0102, 7, -2, 0x0304)]);
+        let time: [u8; 8] = [0x02, 0x01, 0, 0, 0, 0, 0, 0];
+        let x: [u8; 4] = [0xFE, 0xFF, 0xFF, 0xFF];
+        let y: [u8; 4] = [0x04, 0x03, 0, 0];
+        assert_eq!(
+            bytes[10..],
+            [&[RECORD_EVENT][..], &time, &[7], &x, &y].concat()
+        );
+    }
+
+    #[test]
+    fn wrong_magic() {
+        let mut bytes = write(&[event(0, 2, 1, 1)]);
+        bytes[0] = b'X';
+        assert_eq!(error_kind(read(&bytes)), ErrorKind::InvalidData);
+        assert_eq!(error_kind(read(b"not a recording")), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let mut bytes = write(&[]);
+        bytes[8..10].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        let error = RecordingReader::new(&bytes[..]).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(error.to_string().contains("version"));
+    }
+
+    #[test]
+    fn truncated_header() {
+        let bytes = write(&[]);
+        for len in 0..bytes.len() {
+            let error = RecordingReader::new(&bytes[..len]).err().unwrap();
+            assert_eq!(error.kind(), ErrorKind::UnexpectedEof, "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn truncated_records() {
+        let bytes = write(&[event(1, 2, 3, 4), GEOMETRY]);
+        let first_end = 10 + EVENT_SIZE;
+        //Cut at every byte of the records, the complete ones are still read.
+        for len in 11..bytes.len() {
+            let mut reader = RecordingReader::new(&bytes[..len]).unwrap();
+            if len >= first_end {
+                assert_eq!(reader.next_record().unwrap(), Some(event(1, 2, 3, 4)));
+            }
+            if len != first_end {
+                assert_eq!(
+                    error_kind(reader.next_record()),
+                    ErrorKind::UnexpectedEof,
+                    "{} bytes",
+                    len
+                );
+            } else {
+                assert_eq!(reader.next_record().unwrap(), None);
+            }
+        }
+    }
+
+    #[test]
+    fn corrupted_tag() {
+        let mut bytes = write(&[event(1, 2, 3, 4), event(5, 6, 7, 8)]);
+        bytes[10 + EVENT_SIZE] = 9;
+        let mut reader = RecordingReader::new(&bytes[..]).unwrap();
+        assert_eq!(reader.next().unwrap().unwrap(), event(1, 2, 3, 4));
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(error.to_string().contains("unknown record tag 9"));
+    }
+
+    #[test]
+    fn replayed_coordinates_survive_lparam() {
+        let values = [0, 1, -1, 1920, -32768, 65535, i32::MIN, i32::MAX];
+        for x in values {
+            for y in values {
+                assert_eq!(unpack_coordinates(pack_coordinates(x, y)), (x, y));
+            }
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+//Consumers are expected to send a heartbeat at least this often.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(1000);
+//How many heartbeats can be missed before a subscriber is dropped.
+pub const MAX_MISSED_HEARTBEATS: u32 = 3;
+
+//Packets a consumer can send to the subscription socket.
+//The first byte is always the packet id.
+pub mod request {
+    //[id, filter: u32 LE]
+    pub const REGISTER: u8 = 0xF0;
+    //[id]
+    pub const HEARTBEAT: u8 = 0xF2;
+    //[id]
+    pub const UNREGISTER: u8 = 0xF3;
+}
+
+//Packets the DLL sends back to a consumer.
+pub mod response {
+    //[id, filter: u32 LE, heartbeat interval in ms: u32 LE]
+    pub const ACK: u8 = 0xF1;
+    //[id] Sent in response to a heartbeat from an unknown address, so the consumer re-registers.
+    pub const NOT_REGISTERED: u8 = 0xF4;
+}
+
+//Filter that accepts every event kind.
+pub const ALL_EVENTS: u32 = u32::MAX;
+
+//Returns the filter bit of an event kind.
+pub fn filter_bit(kind: u8) -> u32 {
+    1u32.checked_shl(kind as u32).unwrap_or(0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    Register { filter: u32 },
+    Heartbeat,
+    Unregister,
+}
+
+impl Request {
+    pub fn parse(data: &[u8]) -> Option<Request> {
+        match *data.first()? {
+            request::REGISTER => {
+                let filter = data.get(1..5)?.try_into().ok().map(u32::from_le_bytes)?;
+                Some(Request::Register { filter })
+            }
+            request::HEARTBEAT => Some(Request::Heartbeat),
+            request::UNREGISTER => Some(Request::Unregister),
+            _ => None,
+        }
+    }
+}
+
+pub fn ack_packet(filter: u32) -> [u8; 9] {
+    let mut packet = [0u8; 9];
+    packet[0] = response::ACK;
+    packet[1..5].copy_from_slice(&filter.to_le_bytes());
+    packet[5..9].copy_from_slice(&(HEARTBEAT_INTERVAL.as_millis() as u32).to_le_bytes());
+    packet
+}
+
+#[derive(Debug, Clone)]
+pub struct Subscriber {
+    pub addr: SocketAddr,
+    pub filter: u32,
+    last_seen: Instant,
+    //Permanent subscribers never time out (eg. the legacy listener).
+    permanent: bool,
+}
+
+///Keeps track of every consumer that wants to receive input events.
+///Time is always passed in so the table doesn't depend on the clock.
+#[derive(Debug)]
+pub struct SubscriberTable {
+    subscribers: Vec<Subscriber>,
+    timeout: Duration,
+}
+
+impl SubscriberTable {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            subscribers: Vec::new(),
+            timeout,
+        }
+    }
+
+    pub fn add_permanent(&mut self, addr: SocketAddr, filter: u32, now: Instant) {
+        self.subscribers.retain(|s| s.addr != addr);
+        self.subscribers.push(Subscriber {
+            addr,
+            filter,
+            last_seen: now,
+            permanent: true,
+        });
+    }
+
+    pub fn remove_permanent(&mut self, addr: SocketAddr) {
+        self.subscribers
+            .retain(|s| !(s.permanent && s.addr == addr));
+    }
+
+    ///Registers a consumer, or updates its filter if it is already known.
+    ///Returns true if the consumer is new.
+    pub fn register(&mut self, addr: SocketAddr, filter: u32, now: Instant) -> bool {
+        if let Some(sub) = self.subscribers.iter_mut().find(|s| s.addr == addr) {
+            sub.filter = filter;
+            sub.last_seen = now;
+            return false;
+        }
+        self.subscribers.push(Subscriber {
+            addr,
+            filter,
+            last_seen: now,
+            permanent: false,
+        });
+        true
+    }
+
+    ///Returns false if the address isn't registered.
+    pub fn heartbeat(&mut self, addr: SocketAddr, now: Instant) -> bool {
+        match self.subscribers.iter_mut().find(|s| s.addr == addr) {
+            Some(sub) => {
+                sub.last_seen = now;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn unregister(&mut self, addr: SocketAddr) -> bool {
+        let len = self.subscribers.len();
+        self.subscribers.retain(|s| s.permanent || s.addr != addr);
+        len != self.subscribers.len()
+    }
+
+    ///Drops every subscriber that missed too many heartbeats and returns their addresses.
+    pub fn prune(&mut self, now: Instant) -> Vec<SocketAddr> {
+        let timeout = self.timeout;
+        let mut dropped = Vec::new();
+        self.subscribers.retain(|s| {
+            let alive = s.permanent || now.saturating_duration_since(s.last_seen) <= timeout;
+            if !alive {
+                dropped.push(s.addr);
+            }
+            alive
+        });
+        dropped
+    }
+
+    ///Every address that asked for this event kind.
+    pub fn recipients(&self, kind: u8) -> impl Iterator<Item = SocketAddr> + '_ {
+        let bit = filter_bit(kind);
+        self.subscribers
+            .iter()
+            .filter(move |s| s.filter & bit != 0)
+            .map(|s| s.addr)
+    }
+
+    pub fn len(&self) -> usize {
+        self.subscribers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subscribers.is_empty()
+    }
+}
+
+impl Default for SubscriberTable {
+    fn default() -> Self {
+        Self::new(HEARTBEAT_INTERVAL * MAX_MISSED_HEARTBEATS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn parses_requests() {
+        assert_eq!(
+            Request::parse(&[request::REGISTER, 0x04, 0, 0, 0]),
+            Some(Request::Register { filter: 4 })
+        );
+        assert_eq!(
+            Request::parse(&[request::HEARTBEAT]),
+            Some(Request::Heartbeat)
+        );
+        assert_eq!(
+            Request::parse(&[request::UNREGISTER]),
+            Some(Request::Unregister)
+        );
+        //Filter cut short, unknown id, nothing at all.
+        assert_eq!(Request::parse(&[request::REGISTER, 1, 2]), None);
+        assert_eq!(Request::parse(&[0x42]), None);
+        assert_eq!(Request::parse(&[]), None);
+    }
+
+    #[test]
+    fn ack_carries_filter_and_interval() {
+        let ack = ack_packet(0x24);
+        assert_eq!(ack[0], response::ACK);
+        assert_eq!(u32::from_le_bytes(ack[1..5].try_into().unwrap()), 0x24);
+        assert_eq!(
+            u32::from_le_bytes(ack[5..9].try_into().unwrap()),
+            HEARTBEAT_INTERVAL.as_millis() as u32
+        );
+    }
+
+    #[test]
+    fn filter_bits() {
+        assert_eq!(filter_bit(0), 1);
+        assert_eq!(filter_bit(5), 0x20);
+        //Kinds past 31 can't be asked for.
+        assert_eq!(filter_bit(32), 0);
+    }
+
+    #[test]
+    fn register_updates_known_subscribers() {
+        let now = Instant::now();
+        let mut table = SubscriberTable::default();
+        assert!(table.register(addr(1), filter_bit(2), now));
+        assert!(!table.register(addr(1), filter_bit(5), now));
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.recipients(2).count(), 0);
+        assert_eq!(table.recipients(5).collect::<Vec<_>>(), vec![addr(1)]);
+    }
+
+    #[test]
+    fn recipients_follow_filters() {
+        let now = Instant::now();
+        let mut table = SubscriberTable::default();
+        table.register(addr(1), filter_bit(2), now);
+        table.register(addr(2), filter_bit(2) | filter_bit(5), now);
+        table.register(addr(3), ALL_EVENTS, now);
+        assert_eq!(
+            table.recipients(2).collect::<Vec<_>>(),
+            vec![addr(1), addr(2), addr(3)]
+        );
+        assert_eq!(
+            table.recipients(5).collect::<Vec<_>>(),
+            vec![addr(2), addr(3)]
+        );
+        assert_eq!(table.recipients(7).collect::<Vec<_>>(), vec![addr(3)]);
+    }
+
+    #[test]
+    fn heartbeats_keep_subscribers_alive() {
+        let start = Instant::now();
+        let mut table = SubscriberTable::new(Duration::from_secs(3));
+        table.register(addr(1), ALL_EVENTS, start);
+        table.register(addr(2), ALL_EVENTS, start);
+
+        assert!(table.heartbeat(addr(1), start + Duration::from_secs(2)));
+        assert!(!table.heartbeat(addr(9), start + Duration::from_secs(2)));
+        //Exactly at the timeout is still alive.
+        assert!(table.prune(start + Duration::from_secs(3)).is_empty());
+
+        assert_eq!(table.prune(start + Duration::from_secs(4)), vec![addr(2)]);
+        assert_eq!(table.prune(start + Duration::from_secs(6)), vec![addr(1)]);
+        assert!(table.is_empty());
+        //Gone means gone, a heartbeat doesn't bring it back.
+        assert!(!table.heartbeat(addr(1), start + Duration::from_secs(6)));
+    }
+
+    #[test]
+    fn permanent_subscribers_never_expire() {
+        let start = Instant::now();
+        let mut table = SubscriberTable::new(Duration::from_secs(3));
+        table.add_permanent(addr(1), filter_bit(2), start);
+        assert!(table.prune(start + Duration::from_secs(3600)).is_empty());
+        //Unregistering only drops consumers that registered themselves.
+        assert!(!table.unregister(addr(1)));
+        assert_eq!(table.len(), 1);
+        table.remove_permanent(addr(1));
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn unregister() {
+        let now = Instant::now();
+        let mut table = SubscriberTable::default();
+        table.register(addr(1), ALL_EVENTS, now);
+        assert!(table.unregister(addr(1)));
+        assert!(!table.unregister(addr(1)));
+        assert_eq!(table.recipients(2).count(), 0);
+    }
+}
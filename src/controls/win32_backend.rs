@@ -0,0 +1,127 @@
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use windows::{
+    Win32::{
+        Foundation::{HWND, LPARAM, WPARAM},
+        Graphics::Gdi::InvalidateRect,
+        UI::WindowsAndMessaging::{
+            HTCLIENT, IDC_ARROW, IDC_CROSS, IDC_HAND, IDC_IBEAM, IDC_NO, IDC_SIZEALL, IDC_WAIT,
+            LoadCursorW, PostMessageW, SetCursor, WM_MOUSEMOVE, WM_SETCURSOR,
+        },
+    },
+    core::PCWSTR,
+};
+
+use super::{
+    WM_OVERLAY_INTERACTIVE_CHANGED,
+    control_channel::{CursorShape, WindowBackend},
+};
+use crate::{
+    eject,
+    globals::GAME_HWND,
+    ui::{OVERLAY_HIDDEN, REDRAW_REQUESTED},
+};
+
+//While set, keyboard messages are forwarded to the overlay instead of the game.
+pub static KEYBOARD_CAPTURED: AtomicBool = AtomicBool::new(false);
+//CursorShape requested by the overlay. CursorShape::Default means no override.
+static CURSOR_OVERRIDE: AtomicU8 = AtomicU8::new(CursorShape::Default as u8);
+
+///Applies control requests to the real game window.
+///Requests come from the subscription thread, so this only sets flags and posts messages:
+///the actual work happens in wnd_proc and present().
+pub struct Win32Backend;
+
+impl WindowBackend for Win32Backend {
+    fn capture_keyboard(&self) -> bool {
+        if GAME_HWND.get().is_none() {
+            return false;
+        }
+        if !KEYBOARD_CAPTURED.swap(true, Ordering::Relaxed) {
+            log::info!("Keyboard captured by the overlay.");
+            notify_interactive_changed();
+        }
+        true
+    }
+    fn release_keyboard(&self) -> bool {
+        if KEYBOARD_CAPTURED.swap(false, Ordering::Relaxed) {
+            log::info!("Keyboard released by the overlay.");
+            notify_interactive_changed();
+        }
+        true
+    }
+    fn set_cursor(&self, shape: CursorShape) -> bool {
+        let Some(hwnd) = GAME_HWND.get() else {
+            return false;
+        };
+        CURSOR_OVERRIDE.store(shape as u8, Ordering::Relaxed);
+        //Have the window thread apply it right away instead of on the next mouse move.
+        unsafe {
+            PostMessageW(
+                *hwnd,
+                WM_SETCURSOR,
+                WPARAM(hwnd.0 as usize),
+                LPARAM((HTCLIENT | (WM_MOUSEMOVE << 16)) as isize),
+            )
+            .is_ok()
+        }
+    }
+    fn set_overlay_hidden(&self, hidden: bool) -> bool {
+        OVERLAY_HIDDEN.store(hidden, Ordering::Relaxed);
+        log::info!("Overlay hidden: {}", hidden);
+        true
+    }
+    fn request_redraw(&self) -> bool {
+        REDRAW_REQUESTED.store(true, Ordering::Relaxed);
+        if let Some(hwnd) = GAME_HWND.get() {
+            unsafe { InvalidateRect(*hwnd, None, false) };
+        }
+        true
+    }
+    fn eject(&self) -> bool {
+        eject()
+    }
+}
+
+//Focus and capture can only be changed from the window thread.
+fn notify_interactive_changed() {
+    if let Some(hwnd) = GAME_HWND.get() {
+        unsafe {
+            PostMessageW(*hwnd, WM_OVERLAY_INTERACTIVE_CHANGED, WPARAM(0), LPARAM(0)).ok();
+        }
+    }
+}
+
+fn cursor_name(shape: CursorShape) -> Option<PCWSTR> {
+    Some(match shape {
+        CursorShape::Default => return None,
+        CursorShape::Arrow => IDC_ARROW,
+        CursorShape::Hand => IDC_HAND,
+        CursorShape::IBeam => IDC_IBEAM,
+        CursorShape::Wait => IDC_WAIT,
+        CursorShape::Cross => IDC_CROSS,
+        CursorShape::SizeAll => IDC_SIZEALL,
+        CursorShape::No => IDC_NO,
+    })
+}
+
+///Handles WM_SETCURSOR. Returns true if the overlay's cursor was set and the message
+///must not reach the game (which would set its own cursor).
+pub fn apply_cursor_override(hwnd: HWND, lparam: LPARAM) -> bool {
+    let hit_test = (lparam.0 as u32) & 0xFFFF;
+    if hit_test != HTCLIENT || GAME_HWND.get() != Some(&hwnd) {
+        return false;
+    }
+    let shape = CursorShape::from_u8(CURSOR_OVERRIDE.load(Ordering::Relaxed))
+        .unwrap_or(CursorShape::Default);
+    let Some(name) = cursor_name(shape) else {
+        return false;
+    };
+    match unsafe { LoadCursorW(None, name) } {
+        Ok(cursor) => {
+            unsafe { SetCursor(cursor) };
+            true
+        }
+        Err(_) => false,
+    }
+}
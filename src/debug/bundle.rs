@@ -0,0 +1,384 @@
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+};
+
+use chrono::{Datelike, Local, Timelike};
+use zip::{CompressionMethod, DateTime, ZipWriter, write::SimpleFileOptions};
+
+use super::{
+    DEBUG_FEATURES,
+    statistics::{debug_stat, latest_statistics},
+};
+use crate::{
+    config::{config, config_path, data_dir::data_dir},
+    globals::lifecycle,
+    keybinds::describe_keybinds,
+    logging::{
+        crash::report_session,
+        list_log_files, log_session,
+        retention::{parse_log_name, previous_session},
+    },
+    ui::{MMF_DATA, OVERLAY_HIDDEN, OVERLAY_STATE, mmf::is_overlay_connected},
+    utils::{describe_platform, list_windows},
+};
+
+//The bundle is diagnostics-<date>.zip in the data directory.
+pub const BUNDLE_PREFIX: &str = "diagnostics-";
+pub const BUNDLE_EXTENSION: &str = ".zip";
+
+///A file of the bundle, copied from disk or written from memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BundleEntry {
+    File { name: String, path: PathBuf },
+    Text { name: String, content: String },
+}
+
+impl BundleEntry {
+    pub fn name(&self) -> &str {
+        match self {
+            BundleEntry::File { name, .. } | BundleEntry::Text { name, .. } => name,
+        }
+    }
+}
+
+///The log files of `current_session` and the session before it, and the crash reports,
+///found in `logs_dir`. Everything else there is left out.
+pub fn log_entries(logs_dir: &Path, current_session: &str) -> Vec<BundleEntry> {
+    let files = list_log_files(logs_dir);
+    let previous = previous_session(&files, current_session);
+    let mut entries: Vec<BundleEntry> = files
+        .iter()
+        .filter_map(|file| {
+            let name = file.path.file_name()?.to_str()?;
+            let folder = if report_session(&file.path).is_some() {
+                "crashes"
+            } else {
+                let session = parse_log_name(name)?.session;
+                if session != current_session && Some(session) != previous {
+                    return None;
+                }
+                "logs"
+            };
+            Some(BundleEntry::File {
+                name: format!("{}/{}", folder, name),
+                path: file.path.clone(),
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| a.name().cmp(b.name()));
+    entries
+}
+
+///Writes `entries` to a new zip at `path`. Files that can't be read are left out and returned
+///with the reason, the bundle is still useful without them.
+pub fn write_bundle(path: &Path, entries: &[BundleEntry]) -> io::Result<Vec<String>> {
+    let now = Local::now();
+    let modified = DateTime::from_date_and_time(
+        now.year().clamp(1980, 2107) as u16,
+        now.month() as u8,
+        now.day() as u8,
+        now.hour() as u8,
+        now.minute() as u8,
+        now.second() as u8,
+    )
+    .unwrap_or_default();
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .last_modified_time(modified);
+
+    let mut zip = ZipWriter::new(BufWriter::new(File::create(path)?));
+    let mut skipped = Vec::new();
+    for entry in entries {
+        let content = match entry {
+            BundleEntry::File { path, .. } => match fs::read(path) {
+                Ok(content) => content,
+                Err(e) => {
+                    skipped.push(format!("{}: {}", entry.name(), e));
+                    continue;
+                }
+            },
+            BundleEntry::Text { content, .. } => content.clone().into_bytes(),
+        };
+        zip.start_file(entry.name(), options)?;
+        zip.write_all(&content)?;
+    }
+    zip.finish()?.flush()?;
+    Ok(skipped)
+}
+
+///Everything a bug report needs, in one zip in the data directory.
+pub fn create_bundle() -> io::Result<PathBuf> {
+    let now = Local::now();
+    let path = data_dir().path.join(format!(
+        "{}{}{}",
+        BUNDLE_PREFIX,
+        now.format("%Y-%m-%d_%H-%M-%S"),
+        BUNDLE_EXTENSION
+    ));
+
+    let mut entries = match log_session() {
+        Some(session) => log_entries(&session.dir, &session.id),
+        None => Vec::new(),
+    };
+    entries.push(BundleEntry::File {
+        name: "overlay.toml".to_string(),
+        path: config_path(),
+    });
+    let text = |name: &str, content: String| BundleEntry::Text {
+        name: name.to_string(),
+        content,
+    };
+    entries.push(text("effective_config.toml", config().to_toml()));
+    entries.push(text(
+        "keybinds.txt",
+        lines(describe_keybinds(&config().keybinds)),
+    ));
+    entries.push(text("statistics.txt", statistics_summary()));
+    entries.push(text(
+        "windows.txt",
+        lines(
+            list_windows()
+                .into_iter()
+                .map(|(hwnd, class, title)| format!("{:#x}  {}  {}", hwnd.0, class, title)),
+        ),
+    ));
+    entries.push(text("state.txt", state_summary()));
+
+    let skipped = write_bundle(&path, &entries)?;
+    for skipped in skipped {
+        log::warn!("Left out of the diagnostics bundle: {}", skipped);
+    }
+    Ok(path)
+}
+
+pub fn create_bundle_action() {
+    match create_bundle() {
+        Ok(path) => log::info!("Diagnostics bundle written to {}", path.display()),
+        Err(e) => log::error!("Could not write the diagnostics bundle: {}", e),
+    }
+}
+
+fn lines(lines: impl IntoIterator<Item = String>) -> String {
+    lines.into_iter().fold(String::new(), |mut text, line| {
+        let _ = writeln!(text, "{}", line);
+        text
+    })
+}
+
+fn statistics_summary() -> String {
+    let Some(stats) = latest_statistics() else {
+        return "No statistics yet.\n".to_string();
+    };
+    lines(
+        debug_stat::ALL
+            .iter()
+            .zip(debug_stat::NAMES)
+            .map(|(key, name)| format!("{}: {}", name, stats.get(key).copied().unwrap_or(0))),
+    )
+}
+
+fn state_summary() -> String {
+    let mut state = String::new();
+    let _ = writeln!(state, "Version: {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(state, "Platform: {}", describe_platform());
+    let _ = writeln!(state, "State: {}", lifecycle().name());
+    let _ = writeln!(state, "Overlay connected: {}", is_overlay_connected());
+    let _ = writeln!(
+        state,
+        "Overlay hidden: {}",
+        OVERLAY_HIDDEN.load(Ordering::Relaxed)
+    );
+    let _ = writeln!(
+        state,
+        "Rendering: {}, processing: {}, debug overlay: {}",
+        DEBUG_FEATURES.rendering_enabled.load(Ordering::Relaxed),
+        DEBUG_FEATURES.processing_enabled.load(Ordering::Relaxed),
+        DEBUG_FEATURES.debug_overlay_enabled.load(Ordering::Relaxed)
+    );
+    let size = OVERLAY_STATE.get().and_then(|overlay| {
+        let overlay = overlay.lock().unwrap();
+        overlay
+            .as_ref()
+            .map(|overlay| (overlay.width, overlay.height))
+    });
+    match size {
+        Some((width, height)) => {
+            let _ = writeln!(state, "Overlay size: {}x{}", width, height);
+        }
+        None => {
+            let _ = writeln!(state, "Overlay size: not set up");
+        }
+    }
+    match MMF_DATA.get() {
+        Some(mmf) => {
+            let _ = writeln!(state, "MMF: {:?}", mmf.read().unwrap());
+        }
+        None => {
+            let _ = writeln!(state, "MMF: not started");
+        }
+    }
+    if let Some(profile) = config().active_profile() {
+        let _ = writeln!(state, "Profile: {}", profile.name);
+    }
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Read,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use zip::ZipArchive;
+
+    use super::*;
+
+    const CURRENT: &str = "2026-10-19_12-00-00";
+
+    //A new directory in the system temp dir, removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "overlay-bundle-test-{}-{}",
+                std::process::id(),
+                COUNT.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn write(&self, name: &str, content: &str) -> PathBuf {
+            let path = self.0.join(name);
+            fs::write(&path, content).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    fn names(entries: &[BundleEntry]) -> Vec<&str> {
+        entries.iter().map(BundleEntry::name).collect()
+    }
+
+    //The name and content of every file in the zip, in order.
+    fn read_zip(path: &Path) -> Vec<(String, String)> {
+        let mut zip = ZipArchive::new(File::open(path).unwrap()).unwrap();
+        (0..zip.len())
+            .map(|i| {
+                let mut file = zip.by_index(i).unwrap();
+                assert_eq!(file.compression(), CompressionMethod::Deflated);
+                let mut content = String::new();
+                file.read_to_string(&mut content).unwrap();
+                (file.name().to_string(), content)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn picks_current_and_previous_session() {
+        let dir = TempDir::new();
+        dir.write("overlay-2026-10-17_12-00-00.log.gz", "older");
+        dir.write("overlay-2026-10-18_12-00-00.log.gz", "previous");
+        dir.write("overlay-2026-10-18_12-00-00.1.log.gz", "previous, part 1");
+        dir.write("overlay-2026-10-19_12-00-00.log", "current");
+        dir.write("crash-2026-10-17_12-00-00.txt", "crash");
+        dir.write("notes.txt", "not ours");
+        fs::create_dir(dir.0.join("overlay-2026-10-19_13-00-00.log")).unwrap();
+
+        let entries = log_entries(&dir.0, CURRENT);
+        assert_eq!(
+            names(&entries),
+            [
+                "crashes/crash-2026-10-17_12-00-00.txt",
+                "logs/overlay-2026-10-18_12-00-00.1.log.gz",
+                "logs/overlay-2026-10-18_12-00-00.log.gz",
+                "logs/overlay-2026-10-19_12-00-00.log",
+            ]
+        );
+        assert_eq!(
+            entries[3],
+            BundleEntry::File {
+                name: "logs/overlay-2026-10-19_12-00-00.log".to_string(),
+                path: dir.0.join("overlay-2026-10-19_12-00-00.log"),
+            }
+        );
+    }
+
+    #[test]
+    fn first_session() {
+        let dir = TempDir::new();
+        dir.write("overlay-2026-10-19_12-00-00.log", "current");
+        assert_eq!(
+            names(&log_entries(&dir.0, CURRENT)),
+            ["logs/overlay-2026-10-19_12-00-00.log"]
+        );
+        assert!(log_entries(&dir.0.join("missing"), CURRENT).is_empty());
+    }
+
+    #[test]
+    fn zip_entries() {
+        let dir = TempDir::new();
+        let log = dir.write("overlay-2026-10-19_12-00-00.log", "line 1\nline 2\n");
+        let entries = [
+            BundleEntry::File {
+                name: "logs/overlay-2026-10-19_12-00-00.log".to_string(),
+                path: log,
+            },
+            BundleEntry::File {
+                name: "overlay.toml".to_string(),
+                path: dir.0.join("missing.toml"),
+            },
+            BundleEntry::Text {
+                name: "state.txt".to_string(),
+                content: "State: Running\n".to_string(),
+            },
+            BundleEntry::Text {
+                name: "empty.txt".to_string(),
+                content: String::new(),
+            },
+        ];
+        let path = dir.0.join("diagnostics-2026-10-19_12-00-00.zip");
+
+        let skipped = write_bundle(&path, &entries).unwrap();
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0].starts_with("overlay.toml: "), "{}", skipped[0]);
+        assert_eq!(
+            read_zip(&path),
+            [
+                (
+                    "logs/overlay-2026-10-19_12-00-00.log".to_string(),
+                    "line 1\nline 2\n".to_string()
+                ),
+                ("state.txt".to_string(), "State: Running\n".to_string()),
+                ("empty.txt".to_string(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_bundle() {
+        let dir = TempDir::new();
+        let path = dir.0.join("empty.zip");
+        assert!(write_bundle(&path, &[]).unwrap().is_empty());
+        assert!(read_zip(&path).is_empty());
+    }
+
+    #[test]
+    fn unwritable_bundle() {
+        let dir = TempDir::new();
+        let path = dir.0.join("missing").join("bundle.zip");
+        assert!(write_bundle(&path, &[]).is_err());
+    }
+}
@@ -24,6 +24,14 @@ pub static OVERLAY_MODE: AtomicU8 = AtomicU8::new(0);
 pub mod overlay_mode {
     pub const LOG_MODE: u8 = 0;
     pub const STAT_MODE: u8 = 1;
+
+    pub const ALL: [u8; 2] = [LOG_MODE, STAT_MODE];
+    //Same order as ALL, used in overlay.toml and keybind actions.
+    pub const NAMES: [&str; 2] = ["log", "statistics"];
+
+    pub fn name(mode: u8) -> &'static str {
+        NAMES.get(mode as usize).copied().unwrap_or(NAMES[0])
+    }
 }
 
 //Font. Because I don't want users to have to install corefonts to their wine prefix.
@@ -95,6 +103,15 @@ pub fn add_to_debug_log_overlay(str: String) {
     }
 }
 
+//Opens the overlay on the log, so the user sees problems that were just logged.
+pub fn show_log_overlay() {
+    OVERLAY_MODE.store(overlay_mode::LOG_MODE, Ordering::Relaxed);
+    DEBUG_FEATURES
+        .debug_overlay_enabled
+        .store(true, Ordering::Relaxed);
+    refresh_overlay_buffer(None);
+}
+
 pub fn refresh_overlay_buffer(stats: Option<&HashMap<u32, u32>>) {
     let overlay = get_overlay();
     let overlay_ptr = overlay.as_ptr() as *mut u8;
@@ -144,12 +161,31 @@ pub fn refresh_overlay_buffer(stats: Option<&HashMap<u32, u32>>) {
                         y,
                     );
 
-                    x = draw_text_at(
+                    draw_text_at(
                         overlay_ptr,
                         format!("Original: {}ns.  ", frame_time_diff),
                         x,
                         y,
                     );
+
+                    //Input queue, on its own line.
+                    let input_dropped = stats.get(&debug_stat::INPUT_DROPPED).unwrap();
+                    let input_coalesced = stats.get(&debug_stat::INPUT_COALESCED).unwrap();
+                    x = 2.0;
+                    y += FONT_SIZE + 2.0;
+
+                    x = draw_text_at(
+                        overlay_ptr,
+                        format!("Input dropped: {}.  ", input_dropped),
+                        x,
+                        y,
+                    );
+                    draw_text_at(
+                        overlay_ptr,
+                        format!("Coalesced: {}.  ", input_coalesced),
+                        x,
+                        y,
+                    );
                 }
             }
             _ => {}
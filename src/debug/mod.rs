@@ -1,4 +1,7 @@
-use crate::ui::OVERLAY_STATE;
+use crate::{
+    config::{config, data_dir::resolve_path},
+    ui::OVERLAY_STATE,
+};
 use std::os::windows::process::CommandExt;
 use std::process::{Command, Stdio};
 use std::sync::atomic::AtomicBool;
@@ -15,6 +18,7 @@ use windows::Win32::{
     },
 };
 
+pub mod bundle;
 pub mod debug_overlay;
 pub mod statistics;
 
@@ -51,9 +55,10 @@ pub fn dump_debug_data() {
 
 pub fn restart_blish() {
     log::info!("Restarting blish");
-    kill_process_by_name("Blish HUD.exe");
+    let config = config();
+    kill_process_by_name(&config.overlay.process_name);
     sleep(Duration::from_millis(1000));
-    Command::new("addons/LOADER_public/Blish.HUD.1.2.0/Blish HUD.exe")
+    Command::new(resolve_path(&config.paths.blish_hud))
         .creation_flags(0x08000000)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
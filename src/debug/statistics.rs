@@ -1,9 +1,9 @@
 use std::{
     collections::HashMap,
     sync::{
-        OnceLock,
+        Mutex, OnceLock,
         atomic::Ordering,
-        mpsc::{Sender, channel},
+        mpsc::{RecvTimeoutError, Sender, channel},
     },
     time::{Duration, Instant},
 };
@@ -12,15 +12,36 @@ use super::{
     DEBUG_FEATURES,
     debug_overlay::{OVERLAY_MODE, overlay_mode, refresh_overlay_buffer},
 };
+use crate::shutdown::{STOP_CHECK_INTERVAL, spawn_worker, stopping};
 
 //Sender
 static STATISTIC_SENDER: OnceLock<Sender<(u32, u32)>> = OnceLock::new();
+//Copy of the stats, taken every refresh interval, for the diagnostics bundle.
+static LATEST: Mutex<Option<HashMap<u32, u32>>> = Mutex::new(None);
 
 //Stores the stats that will be rendered on the overlay
 pub mod debug_stat {
     pub const FRAME_TIME_CUSTOM: u32 = 0;
     pub const FRAME_TIME_TOTAL: u32 = 1;
     pub const FRAME_TIME_DIFF: u32 = 2;
+    pub const INPUT_DROPPED: u32 = 3;
+    pub const INPUT_COALESCED: u32 = 4;
+
+    pub const ALL: [u32; 5] = [
+        FRAME_TIME_CUSTOM,
+        FRAME_TIME_TOTAL,
+        FRAME_TIME_DIFF,
+        INPUT_DROPPED,
+        INPUT_COALESCED,
+    ];
+    //Same order as ALL, used in the diagnostics bundle.
+    pub const NAMES: [&str; 5] = [
+        "frame_time_custom_ns",
+        "frame_time_total_ns",
+        "frame_time_diff_ns",
+        "input_dropped",
+        "input_coalesced",
+    ];
 }
 
 //Small thread that listens to and counts certain statistics for debugging purposes.
@@ -29,13 +50,18 @@ pub fn start_statistics_server() {
     let (tx, rx) = channel::<(u32, u32)>();
     STATISTIC_SENDER.set(tx).ok();
 
-    std::thread::spawn(move || {
+    spawn_worker("statistics", move || {
         let mut stats = get_stats_map();
 
         let mut last_refresh = Instant::now();
         let refresh_interval = Duration::from_millis(500);
 
-        while let Ok(msg) = rx.recv() {
+        while !stopping() {
+            let msg = match rx.recv_timeout(STOP_CHECK_INTERVAL) {
+                Ok(msg) => msg,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
             match msg.0 {
                 _ => {
                     stats.insert(msg.0, msg.1);
@@ -53,17 +79,24 @@ pub fn start_statistics_server() {
                     );*/
                 }
             }
-            if last_refresh.elapsed() >= refresh_interval
-                && DEBUG_FEATURES.debug_overlay_enabled.load(Ordering::Relaxed)
-                && OVERLAY_MODE.load(Ordering::Relaxed) == overlay_mode::STAT_MODE
-            {
-                refresh_overlay_buffer(Some(&stats));
+            if last_refresh.elapsed() >= refresh_interval {
+                *LATEST.lock().unwrap() = Some(stats.clone());
+                if DEBUG_FEATURES.debug_overlay_enabled.load(Ordering::Relaxed)
+                    && OVERLAY_MODE.load(Ordering::Relaxed) == overlay_mode::STAT_MODE
+                {
+                    refresh_overlay_buffer(Some(&stats));
+                }
                 last_refresh = Instant::now();
             }
         }
     });
 }
 
+///The stats as of the last refresh interval, None before the first one.
+pub fn latest_statistics() -> Option<HashMap<u32, u32>> {
+    LATEST.lock().unwrap().clone()
+}
+
 //Just to make sure the stats are indeed valid.
 fn get_stats_map() -> HashMap<u32, u32> {
     let mut stats = HashMap::with_capacity(100);
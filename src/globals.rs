@@ -1,13 +1,64 @@
-use std::sync::OnceLock;
+use std::sync::{
+    OnceLock,
+    atomic::{AtomicU8, Ordering},
+};
 
-use windows::Win32::{Foundation::HANDLE, UI::WindowsAndMessaging::WNDPROC};
+use windows::Win32::{
+    Foundation::{HANDLE, HWND},
+    UI::WindowsAndMessaging::WNDPROC,
+};
 
 pub static mut ORIGINAL_WNDPROC: Option<WNDPROC> = None;
 
+//The game's main window, set once controls are initialized.
+pub static GAME_HWND: OnceLock<HWND> = OnceLock::new();
+
 //Mutex used to check if blish is still alive, if it crashed, or if it simply not sending frames
 //(eg if it hasn't changed)
 pub static LIVE_MUTEX: OnceLock<Option<HANDLE>> = OnceLock::new();
 
-//This socket is used to send input data to any overlay that
-//cares to listen to this port.
-pub const UDPADDR: &str = "127.0.0.1:49152";
+//How far the DLL got, shown in crash reports.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lifecycle {
+    //Reading overlay.toml, setting up logging.
+    Loading = 0,
+    //Looking for Present and installing the hooks.
+    Hooking = 1,
+    Running = 2,
+    //Stopping the threads, removing the hooks and releasing everything.
+    Detaching = 3,
+    //Nothing of ours is running anymore, the DLL can be unloaded.
+    Detached = 4,
+}
+
+impl Lifecycle {
+    pub const ALL: [Lifecycle; 5] = [
+        Lifecycle::Loading,
+        Lifecycle::Hooking,
+        Lifecycle::Running,
+        Lifecycle::Detaching,
+        Lifecycle::Detached,
+    ];
+
+    //Same order as ALL.
+    pub const NAMES: [&'static str; 5] = ["loading", "hooking", "running", "detaching", "detached"];
+
+    pub fn from_u8(value: u8) -> Option<Lifecycle> {
+        Self::ALL.get(value as usize).copied()
+    }
+
+    pub fn name(self) -> &'static str {
+        Self::NAMES[self as usize]
+    }
+}
+
+static LIFECYCLE: AtomicU8 = AtomicU8::new(Lifecycle::Loading as u8);
+
+pub fn lifecycle() -> Lifecycle {
+    Lifecycle::from_u8(LIFECYCLE.load(Ordering::Relaxed)).unwrap_or(Lifecycle::Loading)
+}
+
+pub fn set_lifecycle(state: Lifecycle) {
+    LIFECYCLE.store(state as u8, Ordering::Relaxed);
+}
@@ -0,0 +1,578 @@
+use std::fmt;
+
+use crate::logging::LogFilter;
+
+//What a keybind runs, as written in the action of a keybind in overlay.toml:
+//one or more steps separated by ';', each step being an action name followed by its arguments.
+//eg. "Ctrl+Alt+S debug_overlay_mode statistics; set_debug_overlay on"
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    //true/false, on/off, yes/no, 1/0
+    Bool,
+    Int,
+    Float,
+    //One of the listed words.
+    Choice(&'static [&'static str]),
+    //Log levels, written like logging.level: "info,mmf=trace".
+    LogFilter,
+}
+
+impl fmt::Display for ArgKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgKind::Bool => write!(f, "on/off"),
+            ArgKind::Int => write!(f, "a whole number"),
+            ArgKind::Float => write!(f, "a number"),
+            ArgKind::Choice(choices) => write!(f, "one of {}", choices.join(", ")),
+            ArgKind::LogFilter => write!(f, "a log filter like info,mmf=trace"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgValue {
+    Bool(bool),
+    Int(i64),
+    Float(f32),
+    Choice(&'static str),
+    LogFilter(LogFilter),
+}
+
+impl ArgKind {
+    pub fn parse(self, text: &str) -> Option<ArgValue> {
+        match self {
+            ArgKind::Bool => match text.to_ascii_lowercase().as_str() {
+                "true" | "on" | "yes" | "1" => Some(ArgValue::Bool(true)),
+                "false" | "off" | "no" | "0" => Some(ArgValue::Bool(false)),
+                _ => None,
+            },
+            ArgKind::Int => text.parse().ok().map(ArgValue::Int),
+            ArgKind::Float => text
+                .parse::<f32>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .map(ArgValue::Float),
+            ArgKind::Choice(choices) => choices
+                .iter()
+                .find(|c| c.eq_ignore_ascii_case(text))
+                .map(|c| ArgValue::Choice(c)),
+            ArgKind::LogFilter => text.parse().ok().map(ArgValue::LogFilter),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    //Used when the argument is left out. Arguments without a default are required.
+    pub default: Option<&'static str>,
+}
+
+//Arguments of an action, already checked against its ArgSpecs.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Args(Vec<ArgValue>);
+
+impl Args {
+    pub fn new(values: Vec<ArgValue>) -> Self {
+        Self(values)
+    }
+    pub fn bool(&self, index: usize) -> bool {
+        matches!(self.0.get(index), Some(ArgValue::Bool(true)))
+    }
+    pub fn int(&self, index: usize) -> i64 {
+        match self.0.get(index) {
+            Some(ArgValue::Int(v)) => *v,
+            _ => 0,
+        }
+    }
+    pub fn float(&self, index: usize) -> f32 {
+        match self.0.get(index) {
+            Some(ArgValue::Float(v)) => *v,
+            _ => 0.0,
+        }
+    }
+    pub fn choice(&self, index: usize) -> &'static str {
+        match self.0.get(index) {
+            Some(ArgValue::Choice(v)) => v,
+            _ => "",
+        }
+    }
+    pub fn log_filter(&self, index: usize) -> Option<&LogFilter> {
+        match self.0.get(index) {
+            Some(ArgValue::LogFilter(v)) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+pub struct ActionDef {
+    pub name: &'static str,
+    pub args: &'static [ArgSpec],
+    pub run: fn(&Args),
+}
+
+impl fmt::Debug for ActionDef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ActionDef")
+            .field("name", &self.name)
+            .field("args", &self.args)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActionError {
+    Empty,
+    UnknownAction(String),
+    MissingArgument {
+        action: &'static str,
+        arg: &'static str,
+    },
+    InvalidArgument {
+        action: &'static str,
+        arg: &'static str,
+        value: String,
+        expected: ArgKind,
+    },
+    TooManyArguments {
+        action: &'static str,
+        expected: usize,
+    },
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionError::Empty => write!(f, "no action given"),
+            ActionError::UnknownAction(name) => write!(f, "unknown action \"{}\"", name),
+            ActionError::MissingArgument { action, arg } => {
+                write!(f, "{} needs a value for {}", action, arg)
+            }
+            ActionError::InvalidArgument {
+                action,
+                arg,
+                value,
+                expected,
+            } => write!(
+                f,
+                "{}: \"{}\" is not a valid {}, expected {}",
+                action, value, arg, expected
+            ),
+            ActionError::TooManyArguments { action, expected } => {
+                write!(f, "{} takes at most {} argument(s)", action, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ActionError {}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Registry {
+    actions: &'static [ActionDef],
+}
+
+impl Registry {
+    pub const fn new(actions: &'static [ActionDef]) -> Self {
+        Self { actions }
+    }
+
+    pub fn find(&self, name: &str) -> Option<&'static ActionDef> {
+        self.actions.iter().find(|a| a.name == name)
+    }
+
+    ///Parses one step: an action name and its arguments.
+    pub fn parse_invocation(&self, text: &str) -> Result<Invocation, ActionError> {
+        let mut words = text.split_whitespace();
+        let name = words.next().ok_or(ActionError::Empty)?;
+        let action = self
+            .find(name)
+            .ok_or_else(|| ActionError::UnknownAction(name.to_string()))?;
+
+        let words: Vec<&str> = words.collect();
+        if words.len() > action.args.len() {
+            return Err(ActionError::TooManyArguments {
+                action: action.name,
+                expected: action.args.len(),
+            });
+        }
+        let mut values = Vec::with_capacity(action.args.len());
+        for (index, spec) in action.args.iter().enumerate() {
+            let text = match (words.get(index), spec.default) {
+                (Some(text), _) => *text,
+                (None, Some(default)) => default,
+                (None, None) => {
+                    return Err(ActionError::MissingArgument {
+                        action: action.name,
+                        arg: spec.name,
+                    });
+                }
+            };
+            let value = spec
+                .kind
+                .parse(text)
+                .ok_or_else(|| ActionError::InvalidArgument {
+                    action: action.name,
+                    arg: spec.name,
+                    value: text.to_string(),
+                    expected: spec.kind,
+                })?;
+            values.push(value);
+        }
+        Ok(Invocation {
+            action,
+            args: Args(values),
+        })
+    }
+
+    ///Parses everything a keybind runs: steps separated by ';'.
+    pub fn parse_macro(&self, text: &str) -> Result<Macro, ActionError> {
+        let steps = text
+            .split(';')
+            .map(str::trim)
+            .filter(|step| !step.is_empty())
+            .map(|step| self.parse_invocation(step))
+            .collect::<Result<Vec<_>, _>>()?;
+        if steps.is_empty() {
+            return Err(ActionError::Empty);
+        }
+        Ok(Macro { steps })
+    }
+}
+
+//An action with its arguments.
+#[derive(Debug, Clone)]
+pub struct Invocation {
+    pub action: &'static ActionDef,
+    pub args: Args,
+}
+
+impl Invocation {
+    pub fn run(&self) {
+        (self.action.run)(&self.args);
+    }
+}
+
+//Actions run in order by a single keybind.
+#[derive(Debug, Clone)]
+pub struct Macro {
+    pub steps: Vec<Invocation>,
+}
+
+impl Macro {
+    pub fn run(&self) {
+        for step in &self.steps {
+            step.run();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    thread_local! {
+        //What the test actions ran, on this test's thread.
+        static RAN: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn ran(entry: String) {
+        RAN.with(|ran| ran.borrow_mut().push(entry));
+    }
+
+    fn take_ran() -> Vec<String> {
+        RAN.with(|ran| ran.take())
+    }
+
+    const MODES: &[&str] = &["log", "statistics"];
+
+    static TEST_ACTIONS: &[ActionDef] = &[
+        ActionDef {
+            name: "toggle",
+            args: &[],
+            run: |_| ran("toggle".to_string()),
+        },
+        ActionDef {
+            name: "set",
+            args: &[ArgSpec {
+                name: "state",
+                kind: ArgKind::Bool,
+                default: None,
+            }],
+            run: |args| ran(format!("set {}", args.bool(0))),
+        },
+        ActionDef {
+            name: "move",
+            args: &[
+                ArgSpec {
+                    name: "x",
+                    kind: ArgKind::Int,
+                    default: None,
+                },
+                ArgSpec {
+                    name: "y",
+                    kind: ArgKind::Int,
+                    default: Some("0"),
+                },
+            ],
+            run: |args| ran(format!("move {} {}", args.int(0), args.int(1))),
+        },
+        ActionDef {
+            name: "speed",
+            args: &[ArgSpec {
+                name: "speed",
+                kind: ArgKind::Float,
+                default: Some("1"),
+            }],
+            run: |args| ran(format!("speed {}", args.float(0))),
+        },
+        ActionDef {
+            name: "mode",
+            args: &[ArgSpec {
+                name: "mode",
+                kind: ArgKind::Choice(MODES),
+                default: None,
+            }],
+            run: |args| ran(format!("mode {}", args.choice(0))),
+        },
+        ActionDef {
+            name: "level",
+            args: &[ArgSpec {
+                name: "filter",
+                kind: ArgKind::LogFilter,
+                default: None,
+            }],
+            run: |args| ran(format!("level {}", args.log_filter(0).unwrap())),
+        },
+    ];
+    static REGISTRY: Registry = Registry::new(TEST_ACTIONS);
+
+    fn run(text: &str) -> Vec<String> {
+        REGISTRY.parse_macro(text).unwrap().run();
+        take_ran()
+    }
+
+    fn error(text: &str) -> ActionError {
+        REGISTRY.parse_macro(text).unwrap_err()
+    }
+
+    #[test]
+    fn bool_values() {
+        for text in ["true", "on", "yes", "1", "ON", "Yes"] {
+            assert_eq!(ArgKind::Bool.parse(text), Some(ArgValue::Bool(true)));
+        }
+        for text in ["false", "off", "no", "0", "Off"] {
+            assert_eq!(ArgKind::Bool.parse(text), Some(ArgValue::Bool(false)));
+        }
+        for text in ["", "2", "enabled", "onn"] {
+            assert_eq!(ArgKind::Bool.parse(text), None);
+        }
+    }
+
+    #[test]
+    fn number_values() {
+        assert_eq!(ArgKind::Int.parse("-42"), Some(ArgValue::Int(-42)));
+        assert_eq!(ArgKind::Int.parse("1.5"), None);
+        assert_eq!(ArgKind::Int.parse("ten"), None);
+        assert_eq!(ArgKind::Float.parse("1.5"), Some(ArgValue::Float(1.5)));
+        assert_eq!(ArgKind::Float.parse("2"), Some(ArgValue::Float(2.0)));
+        for text in ["inf", "NaN", "-infinity", "fast"] {
+            assert_eq!(ArgKind::Float.parse(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn choice_values() {
+        let kind = ArgKind::Choice(MODES);
+        assert_eq!(
+            kind.parse("Statistics"),
+            Some(ArgValue::Choice("statistics"))
+        );
+        assert_eq!(kind.parse("stats"), None);
+        assert_eq!(kind.to_string(), "one of log, statistics");
+    }
+
+    #[test]
+    fn log_filter_values() {
+        let Some(ArgValue::LogFilter(filter)) = ArgKind::LogFilter.parse("info,mmf=trace") else {
+            panic!("not parsed");
+        };
+        assert_eq!(filter.to_string(), "info,mmf=trace");
+        assert_eq!(ArgKind::LogFilter.parse("loud"), None);
+    }
+
+    #[test]
+    fn runs_actions_with_their_arguments() {
+        assert_eq!(run("toggle"), ["toggle"]);
+        assert_eq!(run("set off"), ["set false"]);
+        assert_eq!(run("move -3 7"), ["move -3 7"]);
+        assert_eq!(run("mode STATISTICS"), ["mode statistics"]);
+        assert_eq!(run("level warn,ui=trace"), ["level warn,ui=trace"]);
+        assert_eq!(run("  speed   0.5  "), ["speed 0.5"]);
+    }
+
+    #[test]
+    fn defaults_fill_missing_arguments() {
+        assert_eq!(run("move 3"), ["move 3 0"]);
+        assert_eq!(run("speed"), ["speed 1"]);
+    }
+
+    #[test]
+    fn macros_run_in_order() {
+        assert_eq!(
+            run("mode log; set on;toggle ; move 1 2"),
+            ["mode log", "set true", "toggle", "move 1 2"]
+        );
+        //Empty steps are skipped.
+        assert_eq!(run(";toggle;;toggle;"), ["toggle", "toggle"]);
+    }
+
+    #[test]
+    fn macros_dont_run_if_a_step_is_invalid() {
+        assert_eq!(
+            error("toggle; set maybe"),
+            ActionError::InvalidArgument {
+                action: "set",
+                arg: "state",
+                value: "maybe".to_string(),
+                expected: ArgKind::Bool,
+            }
+        );
+        assert!(take_ran().is_empty());
+    }
+
+    #[test]
+    fn empty() {
+        for text in ["", "   ", ";", " ; ;"] {
+            assert_eq!(error(text), ActionError::Empty, "{:?}", text);
+        }
+        assert_eq!(
+            REGISTRY.parse_invocation("  ").unwrap_err(),
+            ActionError::Empty
+        );
+    }
+
+    #[test]
+    fn unknown_actions() {
+        assert_eq!(
+            error("jump"),
+            ActionError::UnknownAction("jump".to_string())
+        );
+        //Names are case sensitive, like in the README.
+        assert_eq!(
+            error("Toggle"),
+            ActionError::UnknownAction("Toggle".to_string())
+        );
+        assert!(REGISTRY.find("toggle").is_some());
+        assert!(REGISTRY.find("jump").is_none());
+    }
+
+    #[test]
+    fn bad_arity() {
+        assert_eq!(
+            error("set"),
+            ActionError::MissingArgument {
+                action: "set",
+                arg: "state",
+            }
+        );
+        assert_eq!(
+            error("move"),
+            ActionError::MissingArgument {
+                action: "move",
+                arg: "x",
+            }
+        );
+        assert_eq!(
+            error("toggle now"),
+            ActionError::TooManyArguments {
+                action: "toggle",
+                expected: 0,
+            }
+        );
+        assert_eq!(
+            error("move 1 2 3"),
+            ActionError::TooManyArguments {
+                action: "move",
+                expected: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn bad_types() {
+        let cases = [
+            ("move up", "x", "up", ArgKind::Int),
+            ("move 1 2.5", "y", "2.5", ArgKind::Int),
+            ("speed fast", "speed", "fast", ArgKind::Float),
+            ("mode stats", "mode", "stats", ArgKind::Choice(MODES)),
+            ("level loud", "filter", "loud", ArgKind::LogFilter),
+        ];
+        for (text, arg, value, expected) in cases {
+            let ActionError::InvalidArgument {
+                arg: got_arg,
+                value: got_value,
+                expected: got_expected,
+                ..
+            } = error(text)
+            else {
+                panic!("{} parsed", text);
+            };
+            assert_eq!(
+                (got_arg, got_value.as_str(), got_expected),
+                (arg, value, expected)
+            );
+        }
+    }
+
+    #[test]
+    fn error_messages() {
+        assert_eq!(
+            error("set maybe").to_string(),
+            "set: \"maybe\" is not a valid state, expected on/off"
+        );
+        assert_eq!(error("set").to_string(), "set needs a value for state");
+        assert_eq!(
+            error("move 1 2 3").to_string(),
+            "move takes at most 2 argument(s)"
+        );
+        assert_eq!(error("jump").to_string(), "unknown action \"jump\"");
+    }
+
+    //The actions overlay.toml can use.
+    #[test]
+    fn built_in_actions() {
+        use crate::keybinds::{ACTION_REGISTRY, ACTIONS, DEFAULT_KEYBINDS};
+        for (index, action) in ACTIONS.iter().enumerate() {
+            assert!(
+                ACTIONS[..index]
+                    .iter()
+                    .all(|other| other.name != action.name),
+                "{} is registered twice",
+                action.name
+            );
+            for spec in action.args {
+                if let Some(default) = spec.default {
+                    assert!(spec.kind.parse(default).is_some(), "{}", action.name);
+                }
+            }
+        }
+        for (keys, action) in DEFAULT_KEYBINDS {
+            assert!(ACTION_REGISTRY.parse_macro(action).is_ok(), "{}", keys);
+        }
+    }
+
+    #[test]
+    fn args_out_of_range_use_neutral_values() {
+        let args = Args::new(vec![ArgValue::Int(3)]);
+        assert_eq!(args.int(0), 3);
+        assert!(!args.bool(0));
+        assert_eq!(args.float(1), 0.0);
+        assert_eq!(args.choice(1), "");
+        assert!(args.log_filter(0).is_none());
+    }
+}
@@ -0,0 +1,554 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::Hash,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+//When a keybind fires, written after the keybind: "F5@release", "Alt@hold=800", "F6@double".
+//Without a suffix, keybinds fire when pressed.
+pub const DEFAULT_HOLD: Duration = Duration::from_millis(500);
+pub const DEFAULT_DOUBLE_TAP: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Activation {
+    Press,
+    Release,
+    //Fires once the key has been held that long.
+    Hold(Duration),
+    //Fires on the second press if it comes within that time of the first one.
+    DoubleTap(Duration),
+}
+
+impl Activation {
+    pub fn name(self) -> &'static str {
+        match self {
+            Activation::Press => "press",
+            Activation::Release => "release",
+            Activation::Hold(_) => "hold",
+            Activation::DoubleTap(_) => "double",
+        }
+    }
+}
+
+impl fmt::Display for Activation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Activation::Press | Activation::Release => write!(f, "{}", self.name()),
+            Activation::Hold(time) | Activation::DoubleTap(time) => {
+                write!(f, "{}={}", self.name(), time.as_millis())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActivationError {
+    Unknown(String),
+    InvalidDuration(String),
+    //"press=100"
+    UnexpectedDuration(&'static str),
+}
+
+impl fmt::Display for ActivationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActivationError::Unknown(name) => write!(
+                f,
+                "unknown activation \"{}\", expected press, release, hold or double",
+                name
+            ),
+            ActivationError::InvalidDuration(text) => {
+                write!(f, "\"{}\" is not a duration in milliseconds", text)
+            }
+            ActivationError::UnexpectedDuration(name) => {
+                write!(f, "{} doesn't take a duration", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ActivationError {}
+
+///Splits "Ctrl+F5@hold=800" into the keybind text and its activation.
+pub fn split_activation(text: &str) -> Result<(&str, Activation), ActivationError> {
+    let Some((keybind, suffix)) = text.split_once('@') else {
+        return Ok((text, Activation::Press));
+    };
+    let (name, duration) = match suffix.split_once('=') {
+        Some((name, duration)) => {
+            let ms = duration
+                .parse::<u64>()
+                .ok()
+                .filter(|ms| *ms > 0)
+                .ok_or_else(|| ActivationError::InvalidDuration(duration.to_string()))?;
+            (name, Some(Duration::from_millis(ms)))
+        }
+        None => (suffix, None),
+    };
+    let activation = match name.to_ascii_lowercase().as_str() {
+        "press" => Activation::Press,
+        "release" => Activation::Release,
+        "hold" => Activation::Hold(duration.unwrap_or(DEFAULT_HOLD)),
+        "double" => Activation::DoubleTap(duration.unwrap_or(DEFAULT_DOUBLE_TAP)),
+        _ => return Err(ActivationError::Unknown(name.to_string())),
+    };
+    if duration.is_some() && matches!(activation, Activation::Press | Activation::Release) {
+        return Err(ActivationError::UnexpectedDuration(activation.name()));
+    }
+    Ok((keybind, activation))
+}
+
+//Everything bound to one keybind, at most one action per kind of activation.
+#[derive(Debug, Clone)]
+pub struct Bindings<A> {
+    pub press: Option<A>,
+    pub release: Option<A>,
+    pub hold: Option<(Duration, A)>,
+    pub double_tap: Option<(Duration, A)>,
+}
+
+impl<A> Default for Bindings<A> {
+    fn default() -> Self {
+        Self {
+            press: None,
+            release: None,
+            hold: None,
+            double_tap: None,
+        }
+    }
+}
+
+impl<A> Bindings<A> {
+    ///Replaces whatever was bound to that kind of activation.
+    pub fn set(&mut self, activation: Activation, action: A) {
+        match activation {
+            Activation::Press => self.press = Some(action),
+            Activation::Release => self.release = Some(action),
+            Activation::Hold(time) => self.hold = Some((time, action)),
+            Activation::DoubleTap(time) => self.double_tap = Some((time, action)),
+        }
+    }
+}
+
+//What to do after a key event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome<A> {
+    pub actions: Vec<A>,
+    //The event belongs to a keybind and must not reach the game.
+    pub consumed: bool,
+}
+
+impl<A> Outcome<A> {
+    fn ignored() -> Self {
+        Self {
+            actions: Vec::new(),
+            consumed: false,
+        }
+    }
+}
+
+struct Pressed<A> {
+    //Looked up when the key went down, so releasing the modifiers first doesn't matter.
+    bindings: Arc<Bindings<A>>,
+    hold_deadline: Option<Instant>,
+}
+
+///Turns key downs/ups into actions to run. `T` identifies a physical key or button.
+///Auto-repeat is suppressed: a key that is already down doesn't fire again.
+pub struct BindingMachine<T, A> {
+    pressed: HashMap<T, Pressed<A>>,
+    //Time of the last press of keys with a double-tap binding.
+    last_tap: HashMap<T, Instant>,
+}
+
+impl<T: Eq + Hash + Copy, A: Clone> Default for BindingMachine<T, A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Eq + Hash + Copy, A: Clone> BindingMachine<T, A> {
+    pub fn new() -> Self {
+        Self {
+            pressed: HashMap::new(),
+            last_tap: HashMap::new(),
+        }
+    }
+
+    ///`bindings` are the ones matching the key and the modifiers held right now.
+    pub fn key_down(
+        &mut self,
+        trigger: T,
+        bindings: Option<Arc<Bindings<A>>>,
+        now: Instant,
+    ) -> Outcome<A> {
+        if self.pressed.contains_key(&trigger) {
+            return Outcome {
+                actions: Vec::new(),
+                consumed: true,
+            };
+        }
+        let Some(bindings) = bindings else {
+            self.last_tap.remove(&trigger);
+            return Outcome::ignored();
+        };
+
+        let mut actions = Vec::new();
+        if let Some(action) = &bindings.press {
+            actions.push(action.clone());
+        }
+        if let Some((window, action)) = &bindings.double_tap {
+            match self.last_tap.remove(&trigger) {
+                Some(last) if now.duration_since(last) <= *window => actions.push(action.clone()),
+                _ => {
+                    self.last_tap.insert(trigger, now);
+                }
+            }
+        }
+        let hold_deadline = bindings.hold.as_ref().map(|(time, _)| now + *time);
+        self.pressed.insert(
+            trigger,
+            Pressed {
+                bindings,
+                hold_deadline,
+            },
+        );
+        Outcome {
+            actions,
+            consumed: true,
+        }
+    }
+
+    pub fn key_up(&mut self, trigger: T) -> Outcome<A> {
+        let Some(pressed) = self.pressed.remove(&trigger) else {
+            return Outcome::ignored();
+        };
+        Outcome {
+            actions: pressed.bindings.release.iter().cloned().collect(),
+            consumed: pressed.bindings.release.is_some(),
+        }
+    }
+
+    ///Fires the hold bindings whose time has come.
+    pub fn tick(&mut self, now: Instant) -> Vec<A> {
+        let mut actions = Vec::new();
+        for pressed in self.pressed.values_mut() {
+            if pressed
+                .hold_deadline
+                .is_some_and(|deadline| deadline <= now)
+            {
+                pressed.hold_deadline = None;
+                if let Some((_, action)) = &pressed.bindings.hold {
+                    actions.push(action.clone());
+                }
+            }
+        }
+        actions
+    }
+
+    ///When tick() must be called next, if a hold is pending.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.pressed.values().filter_map(|p| p.hold_deadline).min()
+    }
+
+    ///Forgets every key, eg. when the window loses focus and key ups won't come.
+    pub fn reset(&mut self) {
+        self.pressed.clear();
+        self.last_tap.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: u16 = 0x75;
+    const OTHER: u16 = 0x76;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn bindings(entries: &[(Activation, &'static str)]) -> Option<Arc<Bindings<&'static str>>> {
+        let mut bindings = Bindings::default();
+        for (activation, action) in entries {
+            bindings.set(*activation, *action);
+        }
+        Some(Arc::new(bindings))
+    }
+
+    fn fired(actions: &[&'static str]) -> Outcome<&'static str> {
+        Outcome {
+            actions: actions.to_vec(),
+            consumed: true,
+        }
+    }
+
+    fn ignored() -> Outcome<&'static str> {
+        Outcome {
+            actions: Vec::new(),
+            consumed: false,
+        }
+    }
+
+    #[test]
+    fn split_activations() {
+        let cases = [
+            ("F5", "F5", Activation::Press),
+            ("F5@press", "F5", Activation::Press),
+            ("F5@release", "F5", Activation::Release),
+            ("F5@RELEASE", "F5", Activation::Release),
+            ("Alt@hold", "Alt", Activation::Hold(DEFAULT_HOLD)),
+            ("Alt@hold=800", "Alt", Activation::Hold(ms(800))),
+            ("F6@double", "F6", Activation::DoubleTap(DEFAULT_DOUBLE_TAP)),
+            (
+                "Ctrl+F6@double=250",
+                "Ctrl+F6",
+                Activation::DoubleTap(ms(250)),
+            ),
+        ];
+        for (text, keybind, activation) in cases {
+            assert_eq!(
+                split_activation(text),
+                Ok((keybind, activation)),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn activations_round_trip() {
+        for activation in [
+            Activation::Press,
+            Activation::Release,
+            Activation::Hold(ms(1234)),
+            Activation::DoubleTap(ms(1)),
+        ] {
+            let text = format!("F1@{}", activation);
+            assert_eq!(split_activation(&text), Ok(("F1", activation)));
+        }
+    }
+
+    #[test]
+    fn invalid_activations() {
+        let cases = [
+            ("F5@tap", ActivationError::Unknown("tap".to_string())),
+            ("F5@", ActivationError::Unknown(String::new())),
+            ("F5@hold=", ActivationError::InvalidDuration(String::new())),
+            (
+                "F5@hold=0",
+                ActivationError::InvalidDuration("0".to_string()),
+            ),
+            (
+                "F5@hold=-5",
+                ActivationError::InvalidDuration("-5".to_string()),
+            ),
+            (
+                "F5@double=1.5",
+                ActivationError::InvalidDuration("1.5".to_string()),
+            ),
+            ("F5@press=100", ActivationError::UnexpectedDuration("press")),
+            (
+                "F5@release=100",
+                ActivationError::UnexpectedDuration("release"),
+            ),
+        ];
+        for (text, error) in cases {
+            assert_eq!(split_activation(text), Err(error), "{}", text);
+        }
+    }
+
+    #[test]
+    fn press_fires_once_until_released() {
+        let mut machine = BindingMachine::new();
+        let t0 = Instant::now();
+        let bound = bindings(&[(Activation::Press, "press")]);
+        assert_eq!(machine.key_down(KEY, bound.clone(), t0), fired(&["press"]));
+        //Auto-repeat is swallowed.
+        assert_eq!(
+            machine.key_down(KEY, bound.clone(), t0 + ms(30)),
+            fired(&[])
+        );
+        //Without a release binding the key up goes to the game.
+        assert_eq!(machine.key_up(KEY), ignored());
+        assert_eq!(machine.key_down(KEY, bound, t0 + ms(60)), fired(&["press"]));
+    }
+
+    #[test]
+    fn unbound_keys_are_ignored() {
+        let mut machine = BindingMachine::<u16, &str>::new();
+        assert_eq!(machine.key_down(KEY, None, Instant::now()), ignored());
+        assert_eq!(machine.key_up(KEY), ignored());
+    }
+
+    #[test]
+    fn release_fires_on_key_up() {
+        let mut machine = BindingMachine::new();
+        let t0 = Instant::now();
+        let bound = bindings(&[(Activation::Release, "release")]);
+        assert_eq!(machine.key_down(KEY, bound, t0), fired(&[]));
+        assert_eq!(machine.key_up(KEY), fired(&["release"]));
+        //Only once.
+        assert_eq!(machine.key_up(KEY), ignored());
+    }
+
+    #[test]
+    fn release_uses_the_bindings_from_the_key_down() {
+        let mut machine = BindingMachine::new();
+        let t0 = Instant::now();
+        machine.key_down(KEY, bindings(&[(Activation::Release, "ctrl+f6")]), t0);
+        //Modifiers released before the key: the lookup would find nothing now.
+        assert_eq!(machine.key_up(KEY), fired(&["ctrl+f6"]));
+    }
+
+    #[test]
+    fn hold_fires_after_its_time() {
+        let mut machine = BindingMachine::new();
+        let t0 = Instant::now();
+        let bound = bindings(&[(Activation::Hold(ms(500)), "hold")]);
+        assert_eq!(machine.key_down(KEY, bound, t0), fired(&[]));
+        assert_eq!(machine.next_deadline(), Some(t0 + ms(500)));
+        assert!(machine.tick(t0 + ms(499)).is_empty());
+        assert_eq!(machine.tick(t0 + ms(500)), ["hold"]);
+        //Only once per press.
+        assert!(machine.tick(t0 + ms(2000)).is_empty());
+        assert_eq!(machine.next_deadline(), None);
+        assert_eq!(machine.key_up(KEY), ignored());
+    }
+
+    #[test]
+    fn hold_released_early_doesnt_fire() {
+        let mut machine = BindingMachine::new();
+        let t0 = Instant::now();
+        let bound = bindings(&[
+            (Activation::Hold(ms(500)), "hold"),
+            (Activation::Release, "release"),
+        ]);
+        machine.key_down(KEY, bound.clone(), t0);
+        assert_eq!(machine.key_up(KEY), fired(&["release"]));
+        assert!(machine.tick(t0 + ms(600)).is_empty());
+        assert_eq!(machine.next_deadline(), None);
+
+        //Held long enough: both fire.
+        machine.key_down(KEY, bound, t0 + ms(1000));
+        assert_eq!(machine.tick(t0 + ms(1500)), ["hold"]);
+        assert_eq!(machine.key_up(KEY), fired(&["release"]));
+    }
+
+    #[test]
+    fn holds_of_several_keys() {
+        let mut machine = BindingMachine::new();
+        let t0 = Instant::now();
+        machine.key_down(KEY, bindings(&[(Activation::Hold(ms(800)), "long")]), t0);
+        let short = bindings(&[(Activation::Hold(ms(200)), "short")]);
+        machine.key_down(OTHER, short, t0 + ms(100));
+        assert_eq!(machine.next_deadline(), Some(t0 + ms(300)));
+        assert_eq!(machine.tick(t0 + ms(300)), ["short"]);
+        assert_eq!(machine.next_deadline(), Some(t0 + ms(800)));
+        assert_eq!(machine.tick(t0 + ms(900)), ["long"]);
+    }
+
+    #[test]
+    fn double_tap_within_the_window() {
+        let mut machine = BindingMachine::new();
+        let t0 = Instant::now();
+        let bound = bindings(&[(Activation::DoubleTap(ms(300)), "double")]);
+        assert_eq!(machine.key_down(KEY, bound.clone(), t0), fired(&[]));
+        machine.key_up(KEY);
+        assert_eq!(
+            machine.key_down(KEY, bound.clone(), t0 + ms(300)),
+            fired(&["double"])
+        );
+        machine.key_up(KEY);
+        //A third tap starts over.
+        assert_eq!(
+            machine.key_down(KEY, bound.clone(), t0 + ms(400)),
+            fired(&[])
+        );
+        machine.key_up(KEY);
+        assert_eq!(
+            machine.key_down(KEY, bound, t0 + ms(500)),
+            fired(&["double"])
+        );
+    }
+
+    #[test]
+    fn double_tap_too_slow() {
+        let mut machine = BindingMachine::new();
+        let t0 = Instant::now();
+        let bound = bindings(&[(Activation::DoubleTap(ms(300)), "double")]);
+        machine.key_down(KEY, bound.clone(), t0);
+        machine.key_up(KEY);
+        assert_eq!(
+            machine.key_down(KEY, bound.clone(), t0 + ms(301)),
+            fired(&[])
+        );
+        machine.key_up(KEY);
+        //The slow tap counts as a first tap.
+        assert_eq!(
+            machine.key_down(KEY, bound, t0 + ms(500)),
+            fired(&["double"])
+        );
+    }
+
+    #[test]
+    fn double_tap_broken_by_an_unbound_press() {
+        let mut machine = BindingMachine::new();
+        let t0 = Instant::now();
+        let bound = bindings(&[(Activation::DoubleTap(ms(300)), "double")]);
+        machine.key_down(KEY, bound.clone(), t0);
+        machine.key_up(KEY);
+        //Same key with other modifiers, which aren't bound.
+        machine.key_down(KEY, None, t0 + ms(100));
+        machine.key_up(KEY);
+        assert_eq!(machine.key_down(KEY, bound, t0 + ms(200)), fired(&[]));
+    }
+
+    #[test]
+    fn press_and_double_tap_together() {
+        let mut machine = BindingMachine::new();
+        let t0 = Instant::now();
+        let bound = bindings(&[
+            (Activation::Press, "press"),
+            (Activation::DoubleTap(ms(300)), "double"),
+        ]);
+        assert_eq!(machine.key_down(KEY, bound.clone(), t0), fired(&["press"]));
+        machine.key_up(KEY);
+        assert_eq!(
+            machine.key_down(KEY, bound, t0 + ms(100)),
+            fired(&["press", "double"])
+        );
+    }
+
+    #[test]
+    fn reset_forgets_everything() {
+        let mut machine = BindingMachine::new();
+        let t0 = Instant::now();
+        let bound = bindings(&[
+            (Activation::Hold(ms(500)), "hold"),
+            (Activation::DoubleTap(ms(300)), "double"),
+        ]);
+        machine.key_down(KEY, bound.clone(), t0);
+        machine.reset();
+        assert_eq!(machine.next_deadline(), None);
+        assert!(machine.tick(t0 + ms(600)).is_empty());
+        assert_eq!(machine.key_up(KEY), ignored());
+        //Not a second tap either.
+        assert_eq!(machine.key_down(KEY, bound, t0 + ms(100)), fired(&[]));
+    }
+
+    #[test]
+    fn set_replaces_the_binding() {
+        let mut bindings = Bindings::default();
+        bindings.set(Activation::Hold(ms(100)), "first");
+        bindings.set(Activation::Hold(ms(200)), "second");
+        bindings.set(Activation::Press, "press");
+        assert_eq!(bindings.hold, Some((ms(200), "second")));
+        assert_eq!(bindings.press, Some("press"));
+        assert_eq!(bindings.release, None);
+        assert_eq!(bindings.double_tap, None);
+    }
+}
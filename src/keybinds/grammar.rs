@@ -0,0 +1,670 @@
+use std::{fmt, str::FromStr};
+
+//Text form of a keybind: modifiers and a key separated by '+', eg. "Ctrl+Alt+P", "LShift+F5",
+//"Win+Numpad1" or "Ctrl+Mouse4". Names are case insensitive.
+//Formatting a parsed keybind gives back its canonical text, which parses to the same keybind.
+
+//Which physical modifier a keybind asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    Either,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Modifier {
+    Ctrl,
+    Alt,
+    Shift,
+    Win,
+}
+
+impl Modifier {
+    pub const ALL: [Modifier; 4] = [
+        Modifier::Ctrl,
+        Modifier::Alt,
+        Modifier::Shift,
+        Modifier::Win,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Modifier::Ctrl => "Ctrl",
+            Modifier::Alt => "Alt",
+            Modifier::Shift => "Shift",
+            Modifier::Win => "Win",
+        }
+    }
+}
+
+//Modifiers of a keybind. None means the modifier must not be held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Modifiers {
+    pub ctrl: Option<Side>,
+    pub alt: Option<Side>,
+    pub shift: Option<Side>,
+    pub win: Option<Side>,
+}
+
+impl Modifiers {
+    pub fn get(&self, modifier: Modifier) -> Option<Side> {
+        match modifier {
+            Modifier::Ctrl => self.ctrl,
+            Modifier::Alt => self.alt,
+            Modifier::Shift => self.shift,
+            Modifier::Win => self.win,
+        }
+    }
+
+    pub fn set(&mut self, modifier: Modifier, side: Option<Side>) {
+        match modifier {
+            Modifier::Ctrl => self.ctrl = side,
+            Modifier::Alt => self.alt = side,
+            Modifier::Shift => self.shift = side,
+            Modifier::Win => self.win = side,
+        }
+    }
+}
+
+//Which modifier keys are physically held right now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HeldModifier {
+    pub left: bool,
+    pub right: bool,
+}
+
+impl HeldModifier {
+    //Every Side (or None) a keybind may ask for and still match this state.
+    //The most specific ones come first.
+    fn matching_sides(self) -> &'static [Option<Side>] {
+        match (self.left, self.right) {
+            (false, false) => &[None],
+            (true, false) => &[Some(Side::Left), Some(Side::Either)],
+            (false, true) => &[Some(Side::Right), Some(Side::Either)],
+            (true, true) => &[Some(Side::Left), Some(Side::Right), Some(Side::Either)],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HeldModifiers {
+    pub ctrl: HeldModifier,
+    pub alt: HeldModifier,
+    pub shift: HeldModifier,
+    pub win: HeldModifier,
+}
+
+impl HeldModifiers {
+    fn get(&self, modifier: Modifier) -> HeldModifier {
+        match modifier {
+            Modifier::Ctrl => self.ctrl,
+            Modifier::Alt => self.alt,
+            Modifier::Shift => self.shift,
+            Modifier::Win => self.win,
+        }
+    }
+
+    ///A modifier used as the key itself ("Alt@hold") is held while it triggers: don't count it.
+    pub fn ignoring(mut self, trigger: Trigger) -> Self {
+        match trigger {
+            Trigger::Key(0x10 | 0xA0 | 0xA1) => self.shift = HeldModifier::default(),
+            Trigger::Key(0x11 | 0xA2 | 0xA3) => self.ctrl = HeldModifier::default(),
+            Trigger::Key(0x12 | 0xA4 | 0xA5) => self.alt = HeldModifier::default(),
+            Trigger::Key(0x5B | 0x5C) => self.win = HeldModifier::default(),
+            _ => {}
+        }
+        self
+    }
+
+    ///Every Modifiers a keybind may have and still match what is held, most specific first.
+    ///Used to look keybinds up in a map.
+    pub fn candidates(&self) -> Vec<Modifiers> {
+        let mut candidates = vec![Modifiers::default()];
+        for modifier in Modifier::ALL {
+            let sides = self.get(modifier).matching_sides();
+            candidates = candidates
+                .iter()
+                .flat_map(|base| {
+                    sides.iter().map(move |side| {
+                        let mut next = *base;
+                        next.set(modifier, *side);
+                        next
+                    })
+                })
+                .collect();
+        }
+        candidates
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    X1,
+    X2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Trigger {
+    //Windows virtual-key code.
+    Key(u16),
+    Mouse(MouseButton),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyBind {
+    pub trigger: Trigger,
+    pub modifiers: Modifiers,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyBindError {
+    Empty,
+    //"Ctrl+" or "Ctrl++P"
+    EmptyPart,
+    //"Ctrl+LAlt", sided modifiers can't be the key.
+    MissingKey,
+    UnknownKey(String),
+    UnknownModifier(String),
+    DuplicateModifier(Modifier),
+}
+
+impl fmt::Display for KeyBindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyBindError::Empty => write!(f, "empty keybind"),
+            KeyBindError::EmptyPart => write!(f, "empty key name between '+'"),
+            KeyBindError::MissingKey => {
+                write!(
+                    f,
+                    "only Ctrl, Alt, Shift, LWin and RWin can be used as the key"
+                )
+            }
+            KeyBindError::UnknownKey(name) => write!(f, "unknown key \"{}\"", name),
+            KeyBindError::UnknownModifier(name) => write!(f, "\"{}\" is not a modifier", name),
+            KeyBindError::DuplicateModifier(modifier) => {
+                write!(f, "{} is used more than once", modifier.name())
+            }
+        }
+    }
+}
+
+impl std::error::Error for KeyBindError {}
+
+fn parse_modifier(name: &str) -> Option<(Modifier, Side)> {
+    let lower = name.to_ascii_lowercase();
+    let (side, base) = if let Some(base) = lower.strip_prefix('l') {
+        (Side::Left, base)
+    } else if let Some(base) = lower.strip_prefix('r') {
+        (Side::Right, base)
+    } else {
+        (Side::Either, lower.as_str())
+    };
+    let modifier = match base {
+        "ctrl" | "control" => Modifier::Ctrl,
+        "alt" => Modifier::Alt,
+        "shift" => Modifier::Shift,
+        "win" | "windows" | "super" => Modifier::Win,
+        _ => return None,
+    };
+    Some((modifier, side))
+}
+
+fn format_modifier(modifier: Modifier, side: Side) -> String {
+    let prefix = match side {
+        Side::Either => "",
+        Side::Left => "L",
+        Side::Right => "R",
+    };
+    format!("{}{}", prefix, modifier.name())
+}
+
+impl FromStr for KeyBind {
+    type Err = KeyBindError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(KeyBindError::Empty);
+        }
+        let parts: Vec<&str> = text.split('+').map(str::trim).collect();
+        if parts.iter().any(|part| part.is_empty()) {
+            return Err(KeyBindError::EmptyPart);
+        }
+        let (key, modifier_parts) = parts.split_last().ok_or(KeyBindError::Empty)?;
+
+        let mut modifiers = Modifiers::default();
+        for part in modifier_parts {
+            let (modifier, side) = parse_modifier(part)
+                .ok_or_else(|| KeyBindError::UnknownModifier(part.to_string()))?;
+            if modifiers.get(modifier).is_some() {
+                return Err(KeyBindError::DuplicateModifier(modifier));
+            }
+            modifiers.set(modifier, Some(side));
+        }
+
+        let trigger = match parse_trigger(key) {
+            Some(trigger) => trigger,
+            None if parse_modifier(key).is_some() => return Err(KeyBindError::MissingKey),
+            None => return Err(KeyBindError::UnknownKey(key.to_string())),
+        };
+        Ok(KeyBind { trigger, modifiers })
+    }
+}
+
+impl fmt::Display for KeyBind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for modifier in Modifier::ALL {
+            if let Some(side) = self.modifiers.get(modifier) {
+                write!(f, "{}+", format_modifier(modifier, side))?;
+            }
+        }
+        write!(f, "{}", trigger_name(self.trigger))
+    }
+}
+
+//---------------------------------------- Key names ---------------------------------------
+//Canonical names, used for formatting. Parsing also accepts ALIASES and "0x.." virtual-key codes.
+const NAMED_KEYS: &[(&str, u16)] = &[
+    ("Backspace", 0x08),
+    ("Tab", 0x09),
+    ("Enter", 0x0D),
+    //Modifiers can be the key too, eg. "Alt@hold".
+    ("Shift", 0x10),
+    ("Ctrl", 0x11),
+    ("Alt", 0x12),
+    ("Pause", 0x13),
+    ("CapsLock", 0x14),
+    ("Escape", 0x1B),
+    ("Space", 0x20),
+    ("PageUp", 0x21),
+    ("PageDown", 0x22),
+    ("End", 0x23),
+    ("Home", 0x24),
+    ("Left", 0x25),
+    ("Up", 0x26),
+    ("Right", 0x27),
+    ("Down", 0x28),
+    ("PrintScreen", 0x2C),
+    ("Insert", 0x2D),
+    ("Delete", 0x2E),
+    ("LWin", 0x5B),
+    ("RWin", 0x5C),
+    ("Apps", 0x5D),
+    ("NumpadMultiply", 0x6A),
+    ("NumpadAdd", 0x6B),
+    ("NumpadSeparator", 0x6C),
+    ("NumpadSubtract", 0x6D),
+    ("NumpadDecimal", 0x6E),
+    ("NumpadDivide", 0x6F),
+    ("NumLock", 0x90),
+    ("ScrollLock", 0x91),
+    ("Semicolon", 0xBA),
+    ("Equals", 0xBB),
+    ("Comma", 0xBC),
+    ("Minus", 0xBD),
+    ("Period", 0xBE),
+    ("Slash", 0xBF),
+    ("Backtick", 0xC0),
+    ("LeftBracket", 0xDB),
+    ("Backslash", 0xDC),
+    ("RightBracket", 0xDD),
+    ("Quote", 0xDE),
+];
+
+const ALIASES: &[(&str, u16)] = &[
+    ("Back", 0x08),
+    ("Return", 0x0D),
+    ("Control", 0x11),
+    ("Esc", 0x1B),
+    ("PgUp", 0x21),
+    ("PgDn", 0x22),
+    ("PrtSc", 0x2C),
+    ("Ins", 0x2D),
+    ("Del", 0x2E),
+    ("Win", 0x5B),
+    ("Menu", 0x5D),
+    ("Plus", 0xBB),
+    ("Grave", 0xC0),
+    ("Tilde", 0xC0),
+];
+
+const MOUSE_BUTTONS: [(&str, MouseButton); 5] = [
+    ("Mouse1", MouseButton::Left),
+    ("Mouse2", MouseButton::Right),
+    ("Mouse3", MouseButton::Middle),
+    ("Mouse4", MouseButton::X1),
+    ("Mouse5", MouseButton::X2),
+];
+
+const MOUSE_ALIASES: [(&str, MouseButton); 5] = [
+    ("MouseLeft", MouseButton::Left),
+    ("MouseRight", MouseButton::Right),
+    ("MouseMiddle", MouseButton::Middle),
+    ("MouseX1", MouseButton::X1),
+    ("MouseX2", MouseButton::X2),
+];
+
+fn parse_trigger(name: &str) -> Option<Trigger> {
+    let lower = name.to_ascii_lowercase();
+
+    //Single letters and digits are their own virtual-key code, in uppercase.
+    if let [c] = lower.as_bytes()
+        && (c.is_ascii_lowercase() || c.is_ascii_digit())
+    {
+        return Some(Trigger::Key(c.to_ascii_uppercase() as u16));
+    }
+    if let Some(n) = lower.strip_prefix('f').and_then(|n| n.parse::<u16>().ok()) {
+        return (1..=24).contains(&n).then(|| Trigger::Key(0x70 + n - 1));
+    }
+    if let Some(n) = lower
+        .strip_prefix("numpad")
+        .and_then(|n| n.parse::<u16>().ok())
+    {
+        return (n <= 9).then(|| Trigger::Key(0x60 + n));
+    }
+    if let Some(hex) = lower.strip_prefix("0x") {
+        return u8::from_str_radix(hex, 16)
+            .ok()
+            .filter(|vk| *vk != 0)
+            .map(|vk| Trigger::Key(vk as u16));
+    }
+
+    let find = |table: &[(&str, u16)]| {
+        table
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, vk)| Trigger::Key(*vk))
+    };
+    let find_mouse = |table: &[(&str, MouseButton)]| {
+        table
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, button)| Trigger::Mouse(*button))
+    };
+    find(NAMED_KEYS)
+        .or_else(|| find(ALIASES))
+        .or_else(|| find_mouse(&MOUSE_BUTTONS))
+        .or_else(|| find_mouse(&MOUSE_ALIASES))
+}
+
+pub fn trigger_name(trigger: Trigger) -> String {
+    match trigger {
+        Trigger::Mouse(button) => MOUSE_BUTTONS
+            .iter()
+            .find(|(_, b)| *b == button)
+            .map(|(name, _)| name.to_string())
+            .unwrap(),
+        Trigger::Key(vk) => match vk {
+            0x30..=0x39 | 0x41..=0x5A => (vk as u8 as char).to_string(),
+            0x60..=0x69 => format!("Numpad{}", vk - 0x60),
+            0x70..=0x87 => format!("F{}", vk - 0x70 + 1),
+            _ => NAMED_KEYS
+                .iter()
+                .find(|(_, v)| *v == vk)
+                .map(|(name, _)| name.to_string())
+                .unwrap_or_else(|| format!("0x{:02X}", vk)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIDES: [Option<Side>; 4] = [
+        None,
+        Some(Side::Either),
+        Some(Side::Left),
+        Some(Side::Right),
+    ];
+
+    fn parse(text: &str) -> Result<KeyBind, KeyBindError> {
+        text.parse()
+    }
+
+    fn every_trigger() -> impl Iterator<Item = Trigger> {
+        (1..=0xFF).map(Trigger::Key).chain(
+            MOUSE_BUTTONS
+                .iter()
+                .map(|(_, button)| Trigger::Mouse(*button)),
+        )
+    }
+
+    fn every_modifiers() -> Vec<Modifiers> {
+        let mut all = Vec::new();
+        for ctrl in SIDES {
+            for alt in SIDES {
+                for shift in SIDES {
+                    for win in SIDES {
+                        all.push(Modifiers {
+                            ctrl,
+                            alt,
+                            shift,
+                            win,
+                        });
+                    }
+                }
+            }
+        }
+        all
+    }
+
+    #[test]
+    fn every_trigger_round_trips() {
+        for trigger in every_trigger() {
+            let name = trigger_name(trigger);
+            assert_eq!(parse_trigger(&name), Some(trigger), "{}", name);
+            assert_eq!(
+                parse_trigger(&name.to_lowercase()),
+                Some(trigger),
+                "{}",
+                name
+            );
+            assert_eq!(
+                parse_trigger(&name.to_uppercase()),
+                Some(trigger),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn every_keybind_round_trips() {
+        let modifiers = every_modifiers();
+        assert_eq!(modifiers.len(), 256);
+        for trigger in every_trigger() {
+            for modifiers in &modifiers {
+                let keybind = KeyBind {
+                    trigger,
+                    modifiers: *modifiers,
+                };
+                let text = keybind.to_string();
+                assert_eq!(parse(&text), Ok(keybind), "{}", text);
+            }
+        }
+    }
+
+    #[test]
+    fn every_key_code_parses_as_hex() {
+        for vk in 1..=0xFFu16 {
+            let expected = Ok(KeyBind {
+                trigger: Trigger::Key(vk),
+                modifiers: Modifiers::default(),
+            });
+            assert_eq!(parse(&format!("0x{:02X}", vk)), expected);
+            assert_eq!(parse(&format!("0x{:x}", vk)), expected);
+        }
+        for text in ["0x00", "0x100", "0x", "0xZZ"] {
+            assert_eq!(parse(text), Err(KeyBindError::UnknownKey(text.to_string())));
+        }
+    }
+
+    #[test]
+    fn aliases_parse_to_canonical_names() {
+        for (alias, vk) in ALIASES {
+            let text = parse(alias).unwrap().to_string();
+            assert_eq!(
+                parse(&text).unwrap().trigger,
+                Trigger::Key(*vk),
+                "{}",
+                alias
+            );
+        }
+        for (alias, button) in MOUSE_ALIASES {
+            assert_eq!(parse(alias).unwrap().trigger, Trigger::Mouse(button));
+        }
+        assert_eq!(parse("Esc").unwrap().to_string(), "Escape");
+        assert_eq!(parse("Tilde").unwrap().to_string(), "Backtick");
+        assert_eq!(parse("MouseX1").unwrap().to_string(), "Mouse4");
+        assert_eq!(parse("Control+Return").unwrap().to_string(), "Ctrl+Enter");
+        assert_eq!(parse("Super+Win").unwrap().to_string(), "Win+LWin");
+    }
+
+    #[test]
+    fn canonical_form() {
+        let cases = [
+            ("ctrl+alt+p", "Ctrl+Alt+P"),
+            ("Shift+Ctrl+P", "Ctrl+Shift+P"),
+            (" LShift + f5 ", "LShift+F5"),
+            ("rctrl+RALT+numpad1", "RCtrl+RAlt+Numpad1"),
+            ("win+Numpad9", "Win+Numpad9"),
+            ("lwin+F24", "LWin+F24"),
+            ("ctrl+mouse4", "Ctrl+Mouse4"),
+            ("Alt", "Alt"),
+            ("Ctrl+Shift", "Ctrl+Shift"),
+            ("LAlt+LWin", "LAlt+LWin"),
+            ("0x41", "A"),
+            ("0xFF", "0xFF"),
+            ("Shift+Plus", "Shift+Equals"),
+        ];
+        for (text, canonical) in cases {
+            assert_eq!(parse(text).unwrap().to_string(), canonical, "{}", text);
+        }
+    }
+
+    #[test]
+    fn sided_modifiers() {
+        let keybind = parse("LCtrl+RShift+Alt+P").unwrap();
+        assert_eq!(
+            keybind.modifiers,
+            Modifiers {
+                ctrl: Some(Side::Left),
+                alt: Some(Side::Either),
+                shift: Some(Side::Right),
+                win: None,
+            }
+        );
+        assert_eq!(keybind.trigger, Trigger::Key(b'P' as u16));
+    }
+
+    #[test]
+    fn errors() {
+        let cases = [
+            ("", KeyBindError::Empty),
+            ("   ", KeyBindError::Empty),
+            ("+", KeyBindError::EmptyPart),
+            ("Ctrl+", KeyBindError::EmptyPart),
+            ("+P", KeyBindError::EmptyPart),
+            ("Ctrl++P", KeyBindError::EmptyPart),
+            ("Ctrl+ +P", KeyBindError::EmptyPart),
+            ("Ctrl+LAlt", KeyBindError::MissingKey),
+            ("RShift", KeyBindError::MissingKey),
+            ("Ctrl+Foo", KeyBindError::UnknownKey("Foo".to_string())),
+            ("F0", KeyBindError::UnknownKey("F0".to_string())),
+            ("F25", KeyBindError::UnknownKey("F25".to_string())),
+            ("Numpad10", KeyBindError::UnknownKey("Numpad10".to_string())),
+            ("Mouse6", KeyBindError::UnknownKey("Mouse6".to_string())),
+            ("P+Ctrl", KeyBindError::UnknownModifier("P".to_string())),
+            (
+                "Hyper+P",
+                KeyBindError::UnknownModifier("Hyper".to_string()),
+            ),
+            (
+                "Ctrl+Ctrl+P",
+                KeyBindError::DuplicateModifier(Modifier::Ctrl),
+            ),
+            (
+                "LAlt+RAlt+P",
+                KeyBindError::DuplicateModifier(Modifier::Alt),
+            ),
+            ("Win+LWin+P", KeyBindError::DuplicateModifier(Modifier::Win)),
+        ];
+        for (text, error) in cases {
+            assert_eq!(parse(text), Err(error), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn error_messages() {
+        assert_eq!(
+            KeyBindError::UnknownKey("Foo".to_string()).to_string(),
+            "unknown key \"Foo\""
+        );
+        assert_eq!(
+            KeyBindError::DuplicateModifier(Modifier::Shift).to_string(),
+            "Shift is used more than once"
+        );
+    }
+
+    #[test]
+    fn modifier_keys_ignore_themselves() {
+        let held = HeldModifiers {
+            ctrl: HeldModifier {
+                left: true,
+                right: false,
+            },
+            alt: HeldModifier {
+                left: false,
+                right: true,
+            },
+            ..Default::default()
+        };
+        let ignoring = held.ignoring(Trigger::Key(0xA2));
+        assert_eq!(ignoring.ctrl, HeldModifier::default());
+        assert_eq!(ignoring.alt, held.alt);
+        assert_eq!(held.ignoring(Trigger::Key(b'P' as u16)), held);
+    }
+
+    #[test]
+    fn candidates_most_specific_first() {
+        assert_eq!(
+            HeldModifiers::default().candidates(),
+            vec![Modifiers::default()]
+        );
+        let held = HeldModifiers {
+            ctrl: HeldModifier {
+                left: true,
+                right: false,
+            },
+            ..Default::default()
+        };
+        let sides: Vec<Option<Side>> = held.candidates().iter().map(|m| m.ctrl).collect();
+        assert_eq!(sides, [Some(Side::Left), Some(Side::Either)]);
+
+        let held = HeldModifiers {
+            shift: HeldModifier {
+                left: true,
+                right: true,
+            },
+            win: HeldModifier {
+                left: false,
+                right: true,
+            },
+            ..Default::default()
+        };
+        let candidates = held.candidates();
+        assert_eq!(candidates.len(), 6);
+        assert_eq!(candidates[0].shift, Some(Side::Left));
+        assert_eq!(candidates[0].win, Some(Side::Right));
+        assert!(
+            candidates
+                .iter()
+                .all(|m| m.ctrl.is_none() && m.alt.is_none())
+        );
+    }
+}
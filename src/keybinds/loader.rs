@@ -0,0 +1,371 @@
+use std::{collections::HashMap, fmt};
+
+use super::{
+    activation::{Activation, ActivationError, Bindings, split_activation},
+    grammar::{KeyBind, KeyBindError},
+    scope::{Scope, ScopeError, Scoped, parse_scope},
+};
+use crate::config::KeybindEntry;
+
+//Problem found in a keybind of overlay.toml. The keybind is skipped, everything else still loads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    //Index in the keybinds array, like the field names of config diagnostics.
+    pub entry: usize,
+    pub kind: DiagnosticKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    InvalidKeybind(String, KeyBindError),
+    InvalidActivation(String, ActivationError),
+    InvalidScope(String, ScopeError),
+    MissingAction(String),
+    //Unknown action or bad arguments.
+    InvalidAction(String),
+    //Same keybind, activation, scope and action as an earlier entry.
+    Duplicate { first_entry: usize },
+    //Same keybind, kind of activation and scope as an earlier entry, but another action.
+    //The first one is kept.
+    Conflict { first_entry: usize, action: String },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "keybinds[{}]: ", self.entry)?;
+        match &self.kind {
+            DiagnosticKind::InvalidKeybind(text, e) => {
+                write!(f, "invalid keybind \"{}\": {}", text, e)
+            }
+            DiagnosticKind::InvalidActivation(text, e) => {
+                write!(f, "invalid keybind \"{}\": {}", text, e)
+            }
+            DiagnosticKind::InvalidScope(text, e) => {
+                write!(f, "invalid scope \"{}\": {}", text, e)
+            }
+            DiagnosticKind::MissingAction(keybind) => write!(f, "no action given for {}", keybind),
+            DiagnosticKind::InvalidAction(message) => write!(f, "{}", message),
+            DiagnosticKind::Duplicate { first_entry } => {
+                write!(f, "same keybind as keybinds[{}], ignored", first_entry)
+            }
+            DiagnosticKind::Conflict {
+                first_entry,
+                action,
+            } => write!(
+                f,
+                "keybind already used for \"{}\" by keybinds[{}], ignored",
+                action, first_entry
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LoadedKeybinds<A> {
+    pub bindings: HashMap<KeyBind, Scoped<Bindings<A>>>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+///Checks the keybinds of overlay.toml: "<keybind>[@activation]", the actions and an optional scope.
+///`resolve` turns the action text into what it runs.
+pub fn parse_keybinds<A, E: fmt::Display>(
+    entries: &[KeybindEntry],
+    resolve: impl Fn(&str) -> Result<A, E>,
+) -> LoadedKeybinds<A> {
+    let mut loaded = LoadedKeybinds {
+        bindings: HashMap::new(),
+        diagnostics: Vec::new(),
+    };
+    //Where each keybind, kind of activation and scope was first bound, and to what.
+    let mut seen: HashMap<(KeyBind, &str, Scope), (usize, String)> = HashMap::new();
+
+    for (index, entry) in entries.iter().enumerate() {
+        let mut report = |kind| loaded.diagnostics.push(Diagnostic { entry: index, kind });
+
+        let combo = entry.keys.trim();
+        let (combo, activation) = match split_activation(combo) {
+            Ok(split) => split,
+            Err(e) => {
+                report(DiagnosticKind::InvalidActivation(combo.to_string(), e));
+                continue;
+            }
+        };
+        let keybind = match combo.parse::<KeyBind>() {
+            Ok(keybind) => keybind,
+            Err(e) => {
+                report(DiagnosticKind::InvalidKeybind(combo.to_string(), e));
+                continue;
+            }
+        };
+        let scope = match parse_scope(&entry.scope) {
+            Ok(scope) => scope,
+            Err(e) => {
+                report(DiagnosticKind::InvalidScope(entry.scope.clone(), e));
+                continue;
+            }
+        };
+        //Normalized, so duplicates are found whatever the spacing.
+        let action_text = entry
+            .action
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let described = describe(activation, &action_text);
+        if action_text.is_empty() {
+            report(DiagnosticKind::MissingAction(keybind.to_string()));
+            continue;
+        }
+        let action = match resolve(&action_text) {
+            Ok(action) => action,
+            Err(e) => {
+                report(DiagnosticKind::InvalidAction(e.to_string()));
+                continue;
+            }
+        };
+
+        let slot = (keybind, activation.name(), scope);
+        if let Some((first_entry, first_action)) = seen.get(&slot) {
+            report(if *first_action == described {
+                DiagnosticKind::Duplicate {
+                    first_entry: *first_entry,
+                }
+            } else {
+                DiagnosticKind::Conflict {
+                    first_entry: *first_entry,
+                    action: first_action.clone(),
+                }
+            });
+            continue;
+        }
+        seen.insert(slot, (index, described));
+        loaded
+            .bindings
+            .entry(keybind)
+            .or_default()
+            .get_or_insert_default(scope)
+            .set(activation, action);
+    }
+    loaded
+}
+
+//What an entry binds, to tell duplicates from conflicts.
+fn describe(activation: Activation, action_text: &str) -> String {
+    match activation {
+        Activation::Press => action_text.to_string(),
+        _ => format!("{} on {}", action_text, activation),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KNOWN_ACTIONS: [&str; 3] = ["reload_config", "restart_blish", "set_debug_overlay"];
+
+    //Resolves to the action text, for the actions above.
+    fn resolve(text: &str) -> Result<String, String> {
+        let name = text.split_whitespace().next().unwrap_or("");
+        if KNOWN_ACTIONS.contains(&name) {
+            Ok(text.to_string())
+        } else {
+            Err(format!("unknown action \"{}\"", name))
+        }
+    }
+
+    fn entry(keys: &str, action: &str, scope: &str) -> KeybindEntry {
+        KeybindEntry {
+            keys: keys.to_string(),
+            action: action.to_string(),
+            scope: scope.to_string(),
+        }
+    }
+
+    fn load(entries: &[KeybindEntry]) -> LoadedKeybinds<String> {
+        parse_keybinds(entries, resolve)
+    }
+
+    //What `keys` runs, as "<scope>: <activation> <action>", most specific scope first.
+    fn bound(loaded: &LoadedKeybinds<String>, keys: &str) -> Vec<String> {
+        let Some(scoped) = loaded.bindings.get(&keys.parse().unwrap()) else {
+            return Vec::new();
+        };
+        let mut bound = Vec::new();
+        for (scope, bindings) in scoped.iter() {
+            let mut add = |activation: &str, action: &String| {
+                bound.push(format!("{}: {} {}", scope, activation, action))
+            };
+            if let Some(action) = &bindings.press {
+                add("press", action);
+            }
+            if let Some(action) = &bindings.release {
+                add("release", action);
+            }
+            if let Some((time, action)) = &bindings.hold {
+                add(&format!("hold={}", time.as_millis()), action);
+            }
+            if let Some((time, action)) = &bindings.double_tap {
+                add(&format!("double={}", time.as_millis()), action);
+            }
+        }
+        bound
+    }
+
+    #[test]
+    fn every_diagnostic() {
+        let cases: [(KeybindEntry, DiagnosticKind); 7] = [
+            (
+                entry("Ctrl+Nope", "reload_config", ""),
+                DiagnosticKind::InvalidKeybind(
+                    "Ctrl+Nope".to_string(),
+                    KeyBindError::UnknownKey("Nope".to_string()),
+                ),
+            ),
+            (
+                entry(" F5@tap ", "reload_config", ""),
+                DiagnosticKind::InvalidActivation(
+                    "F5@tap".to_string(),
+                    ActivationError::Unknown("tap".to_string()),
+                ),
+            ),
+            (
+                entry("F5", "reload_config", "in_menu"),
+                DiagnosticKind::InvalidScope(
+                    "in_menu".to_string(),
+                    ScopeError::Unknown("in_menu".to_string()),
+                ),
+            ),
+            (
+                entry("Ctrl+F5", "  ", ""),
+                DiagnosticKind::MissingAction("Ctrl+F5".to_string()),
+            ),
+            (
+                entry("F5", "launch_rockets", ""),
+                DiagnosticKind::InvalidAction("unknown action \"launch_rockets\"".to_string()),
+            ),
+            (
+                entry("F9", "  reload_config ", ""),
+                DiagnosticKind::Duplicate { first_entry: 0 },
+            ),
+            (
+                entry("F9", "restart_blish", ""),
+                DiagnosticKind::Conflict {
+                    first_entry: 0,
+                    action: "reload_config".to_string(),
+                },
+            ),
+        ];
+        for (bad, kind) in cases {
+            //A valid entry before and after the bad one.
+            let loaded = load(&[
+                entry("F9", "reload_config", ""),
+                bad.clone(),
+                entry("F10", "set_debug_overlay on", ""),
+            ]);
+            assert_eq!(
+                loaded.diagnostics,
+                [Diagnostic { entry: 1, kind }],
+                "{:?}",
+                bad
+            );
+            assert_eq!(bound(&loaded, "F9"), ["always: press reload_config"]);
+            assert_eq!(
+                bound(&loaded, "F10"),
+                ["always: press set_debug_overlay on"]
+            );
+            assert_eq!(loaded.bindings.len(), 2);
+        }
+    }
+
+    #[test]
+    fn everything_at_once() {
+        let loaded = load(&[
+            entry("Ctrl+Alt+P", "reload_config", ""),
+            entry("Ctrl+", "reload_config", ""),
+            entry("F6@hold=0", "reload_config", ""),
+            entry("F6@hold=800", "restart_blish", ""),
+            entry("F6", "reload_config", "overlay_connected"),
+            entry("F6@hold", "reload_config", ""),
+            entry("Alt+Ctrl+P", "restart_blish", ""),
+        ]);
+        let entries: Vec<usize> = loaded.diagnostics.iter().map(|d| d.entry).collect();
+        assert_eq!(entries, [1, 2, 5, 6]);
+        assert_eq!(
+            bound(&loaded, "Ctrl+Alt+P"),
+            ["always: press reload_config"]
+        );
+        assert_eq!(
+            bound(&loaded, "F6"),
+            [
+                "overlay_connected: press reload_config",
+                "always: hold=800 restart_blish",
+            ]
+        );
+    }
+
+    #[test]
+    fn activations_and_scopes_share_a_key() {
+        let loaded = load(&[
+            entry("F5", "reload_config", ""),
+            entry("F5@release", "restart_blish", ""),
+            entry("F5@double", "set_debug_overlay on", ""),
+            entry("F5", "restart_blish", "overlay_connected, game_not_typing"),
+            entry("F5", "set_debug_overlay off", "debug_overlay_visible"),
+        ]);
+        assert_eq!(loaded.diagnostics, []);
+        assert_eq!(
+            bound(&loaded, "F5"),
+            [
+                "overlay_connected, game_not_typing: press restart_blish",
+                "debug_overlay_visible: press set_debug_overlay off",
+                "always: press reload_config",
+                "always: release restart_blish",
+                "always: double=300 set_debug_overlay on",
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicates_need_the_same_activation() {
+        let loaded = load(&[
+            entry("F5@hold=800", "reload_config", ""),
+            entry("F5@hold=500", "reload_config", ""),
+            entry("F5@hold=500", "restart_blish", ""),
+        ]);
+        assert_eq!(
+            loaded.diagnostics,
+            [
+                Diagnostic {
+                    entry: 1,
+                    kind: DiagnosticKind::Conflict {
+                        first_entry: 0,
+                        action: "reload_config on hold=800".to_string(),
+                    },
+                },
+                Diagnostic {
+                    entry: 2,
+                    kind: DiagnosticKind::Conflict {
+                        first_entry: 0,
+                        action: "reload_config on hold=800".to_string(),
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn messages() {
+        let loaded = load(&[
+            entry("F5", "reload_config", ""),
+            entry("F5", "restart_blish", ""),
+            entry("F6", "", ""),
+        ]);
+        let messages: Vec<String> = loaded.diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "keybinds[1]: keybind already used for \"reload_config\" by keybinds[0], ignored",
+                "keybinds[2]: no action given for F6",
+            ]
+        );
+    }
+}
@@ -0,0 +1,485 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex, RwLock, atomic::Ordering},
+    time::Instant,
+};
+
+use windows::Win32::UI::{
+    Input::KeyboardAndMouse::{
+        GetKeyState, VIRTUAL_KEY, VK_LCONTROL, VK_LMENU, VK_LSHIFT, VK_LWIN, VK_RCONTROL, VK_RMENU,
+        VK_RSHIFT, VK_RWIN,
+    },
+    WindowsAndMessaging::{KillTimer, SetTimer},
+};
+
+use crate::{
+    config::{KeybindEntry, reload_config},
+    controls::{
+        INPUT_SETTINGS, focus::FocusPolicy, mumble_link::is_game_typing, recording,
+        set_raw_input_enabled,
+    },
+    debug::{
+        DEBUG_FEATURES, bundle,
+        debug_overlay::{OVERLAY_MODE, overlay_mode, refresh_overlay_buffer, show_log_overlay},
+        dump_debug_data, restart_blish,
+    },
+    eject,
+    globals::GAME_HWND,
+    logging::set_log_filter,
+    shutdown::spawn_worker,
+    ui::{OVERLAY_HIDDEN, mmf::is_overlay_connected},
+};
+use actions::{ActionDef, ActionError, ArgKind, ArgSpec, Macro, Registry};
+use activation::{BindingMachine, Bindings};
+use grammar::{HeldModifier, HeldModifiers, KeyBind, Trigger};
+use loader::{LoadedKeybinds, parse_keybinds};
+use scope::{Scope, Scoped};
+
+pub mod actions;
+pub mod activation;
+pub mod grammar;
+pub mod loader;
+pub mod scope;
+
+//Handle keybinds and custom keybinds
+type KeybindMap = HashMap<KeyBind, Scoped<Arc<Bindings<Arc<Macro>>>>>;
+//Replaced as a whole every time overlay.toml is reloaded.
+pub static KEYBINDS: RwLock<Option<KeybindMap>> = RwLock::new(None);
+//State of the keys and buttons that have keybinds. Only used from the window thread.
+static BINDING_MACHINE: LazyLock<Mutex<BindingMachine<Trigger, Arc<Macro>>>> =
+    LazyLock::new(|| Mutex::new(BindingMachine::new()));
+//Timer set on the game window while a hold keybind is pending.
+pub const HOLD_TIMER_ID: usize = 0x4B42_0001;
+
+//Written to overlay.toml when it gets created.
+pub const DEFAULT_KEYBINDS: [(&str, &str); 8] = [
+    ("Ctrl+Alt+P", "dump_debug_data"),
+    ("Ctrl+Alt+O", "restart_blish"),
+    ("Ctrl+Alt+B", "toggle_rendering"),
+    ("Ctrl+Alt+N", "toggle_processing"),
+    ("Ctrl+Alt+D", "toggle_debug_overlay"),
+    ("Ctrl+Alt+Shift+1", "debug_overlay_log_mode"),
+    ("Ctrl+Alt+Shift+2", "debug_overlay_statistics_mode"),
+    ("Ctrl+Alt+Shift+B", "create_diagnostics_bundle"),
+];
+
+///Swaps in the keybinds of overlay.toml. Bad entries are reported and skipped.
+pub fn load_keybinds(entries: &[KeybindEntry]) {
+    let map = report_loaded(parse_keybinds(entries, parse_actions));
+    let count = map.len();
+    *KEYBINDS.write().unwrap() = Some(map);
+    log::info!("{} keybind(s) active.", count);
+}
+
+///The keybinds as written in overlay.toml, one line per entry, in the file's order.
+pub fn describe_keybinds(entries: &[KeybindEntry]) -> Vec<String> {
+    entries
+        .iter()
+        .map(|entry| match entry.scope.as_str() {
+            "" => format!("{} = {}", entry.keys, entry.action),
+            scope => format!("{} = {} [{}]", entry.keys, entry.action, scope),
+        })
+        .collect()
+}
+
+//Logs what got loaded and shows the problems on the debug overlay.
+fn report_loaded(loaded: LoadedKeybinds<Arc<Macro>>) -> KeybindMap {
+    for (keybind, scoped) in &loaded.bindings {
+        for (scope, bindings) in scoped.iter() {
+            log::debug!("Keybind loaded: {} [{}] {:?}", keybind, scope, bindings);
+        }
+    }
+    if !loaded.diagnostics.is_empty() {
+        for diagnostic in &loaded.diagnostics {
+            log::warn!("overlay.toml {}", diagnostic);
+        }
+        log::warn!(
+            "overlay.toml has {} keybind problem(s), {} keybind(s) loaded.",
+            loaded.diagnostics.len(),
+            loaded.bindings.len()
+        );
+        show_log_overlay();
+    }
+    loaded
+        .bindings
+        .into_iter()
+        .map(|(keybind, scoped)| (keybind, scoped.map(Arc::new)))
+        .collect()
+}
+
+//Every action that can be bound in overlay.toml.
+const BOOL_ARG: &[ArgSpec] = &[ArgSpec {
+    name: "state",
+    kind: ArgKind::Bool,
+    default: None,
+}];
+static ACTIONS: &[ActionDef] = &[
+    ActionDef {
+        name: "dump_debug_data",
+        args: &[],
+        run: |_| dump_debug_data(),
+    },
+    ActionDef {
+        name: "create_diagnostics_bundle",
+        args: &[],
+        //Zipping the logs takes a moment, not on the window thread.
+        run: |_| spawn_worker("diagnostics bundle", bundle::create_bundle_action),
+    },
+    ActionDef {
+        name: "eject",
+        args: &[],
+        run: |_| {
+            eject();
+        },
+    },
+    ActionDef {
+        name: "restart_blish",
+        args: &[],
+        run: |_| restart_blish(),
+    },
+    ActionDef {
+        name: "toggle_rendering",
+        args: &[],
+        run: |_| toggle_rendering_action(),
+    },
+    ActionDef {
+        name: "toggle_processing",
+        args: &[],
+        run: |_| toggle_processing_action(),
+    },
+    ActionDef {
+        name: "toggle_debug_overlay",
+        args: &[],
+        run: |_| toggle_debug_overlay(),
+    },
+    ActionDef {
+        name: "set_debug_overlay",
+        args: BOOL_ARG,
+        run: |args| set_debug_overlay(args.bool(0)),
+    },
+    ActionDef {
+        name: "debug_overlay_log_mode",
+        args: &[],
+        run: |_| change_overlay_mode_to_log(),
+    },
+    ActionDef {
+        name: "debug_overlay_statistics_mode",
+        args: &[],
+        run: |_| change_overlay_mode_to_statistics(),
+    },
+    ActionDef {
+        name: "debug_overlay_mode",
+        args: &[ArgSpec {
+            name: "mode",
+            kind: ArgKind::Choice(&overlay_mode::NAMES),
+            default: None,
+        }],
+        run: |args| match args.choice(0) {
+            "statistics" => change_overlay_mode_to_statistics(),
+            _ => change_overlay_mode_to_log(),
+        },
+    },
+    ActionDef {
+        name: "reload_config",
+        args: &[],
+        run: |_| reload_config(),
+    },
+    //Kept for keybinds written before reload_config existed.
+    ActionDef {
+        name: "reload_keybinds",
+        args: &[],
+        run: |_| reload_config(),
+    },
+    ActionDef {
+        name: "set_log_level",
+        args: &[ArgSpec {
+            name: "filter",
+            kind: ArgKind::LogFilter,
+            default: None,
+        }],
+        run: |args| {
+            if let Some(filter) = args.log_filter(0) {
+                set_log_filter(filter.clone());
+                log::info!("Log filter: {}", filter);
+            }
+        },
+    },
+    ActionDef {
+        name: "toggle_raw_input",
+        args: &[],
+        run: |_| toggle_raw_input(),
+    },
+    ActionDef {
+        name: "set_raw_input",
+        args: BOOL_ARG,
+        run: |args| {
+            set_raw_input_enabled(args.bool(0));
+            log::info!("Raw input forwarding: {}", args.bool(0));
+        },
+    },
+    ActionDef {
+        name: "toggle_raw_input_mouse_look",
+        args: &[],
+        run: |_| toggle_raw_input_mouse_look(),
+    },
+    ActionDef {
+        name: "cycle_focus_policy",
+        args: &[],
+        run: |_| cycle_focus_policy(),
+    },
+    ActionDef {
+        name: "set_focus_policy",
+        args: &[ArgSpec {
+            name: "policy",
+            kind: ArgKind::Choice(&FocusPolicy::NAMES),
+            default: None,
+        }],
+        run: |args| {
+            if let Some(policy) = FocusPolicy::from_name(args.choice(0)) {
+                set_focus_policy(policy);
+            }
+        },
+    },
+    ActionDef {
+        name: "toggle_gamepad",
+        args: &[],
+        run: |_| toggle_gamepad(),
+    },
+    ActionDef {
+        name: "set_gamepad",
+        args: BOOL_ARG,
+        run: |args| set_gamepad(args.bool(0)),
+    },
+    ActionDef {
+        name: "set_overlay_hidden",
+        args: BOOL_ARG,
+        run: |args| {
+            OVERLAY_HIDDEN.store(args.bool(0), Ordering::Relaxed);
+            log::info!("Overlay hidden: {}", args.bool(0));
+        },
+    },
+    ActionDef {
+        name: "toggle_input_recording",
+        args: &[],
+        run: |_| recording::toggle_recording(),
+    },
+    ActionDef {
+        name: "replay_last_recording",
+        args: &[ArgSpec {
+            name: "speed",
+            kind: ArgKind::Float,
+            default: Some("1"),
+        }],
+        run: |args| recording::replay_latest(args.float(0)),
+    },
+];
+pub static ACTION_REGISTRY: Registry = Registry::new(ACTIONS);
+
+fn parse_actions(text: &str) -> Result<Arc<Macro>, ActionError> {
+    ACTION_REGISTRY.parse_macro(text).map(Arc::new)
+}
+
+//Finds what is bound to a key or mouse button, given the modifiers currently held
+//and the scopes active right now. Side-specific keybinds (LCtrl+X) win over generic ones (Ctrl+X).
+//Keybinds whose scope isn't active are skipped, so the key can reach the game.
+fn find_bindings(trigger: Trigger) -> Option<Arc<Bindings<Arc<Macro>>>> {
+    let keybinds = KEYBINDS.read().unwrap();
+    let map = keybinds.as_ref()?;
+    let state = active_scopes();
+    held_modifiers()
+        .ignoring(trigger)
+        .candidates()
+        .into_iter()
+        .find_map(|modifiers| {
+            map.get(&KeyBind { trigger, modifiers })?
+                .active(state)
+                .cloned()
+        })
+}
+
+//Conditions keybind scopes can depend on that are true right now.
+fn active_scopes() -> Scope {
+    let mut state = Scope::ALWAYS;
+    if is_overlay_connected() {
+        state = state.with(Scope::OVERLAY_CONNECTED);
+    }
+    if DEBUG_FEATURES.debug_overlay_enabled.load(Ordering::Relaxed) {
+        state = state.with(Scope::DEBUG_OVERLAY_VISIBLE);
+    }
+    if !is_game_typing() {
+        state = state.with(Scope::GAME_NOT_TYPING);
+    }
+    state
+}
+
+///Called by wnd_proc when a key or mouse button goes down.
+///Returns true if it belongs to a keybind and must not reach the game.
+pub fn trigger_down(trigger: Trigger) -> bool {
+    let bindings = find_bindings(trigger);
+    let outcome = {
+        let mut machine = BINDING_MACHINE.lock().unwrap();
+        let outcome = machine.key_down(trigger, bindings, Instant::now());
+        schedule_hold_timer(&machine);
+        outcome
+    };
+    run_all(&outcome.actions);
+    outcome.consumed
+}
+
+///Called by wnd_proc when a key or mouse button goes up.
+pub fn trigger_up(trigger: Trigger) -> bool {
+    let outcome = {
+        let mut machine = BINDING_MACHINE.lock().unwrap();
+        let outcome = machine.key_up(trigger);
+        schedule_hold_timer(&machine);
+        outcome
+    };
+    run_all(&outcome.actions);
+    outcome.consumed
+}
+
+///Called by wnd_proc on WM_TIMER with HOLD_TIMER_ID.
+pub fn hold_timer_elapsed() {
+    let actions = {
+        let mut machine = BINDING_MACHINE.lock().unwrap();
+        let actions = machine.tick(Instant::now());
+        schedule_hold_timer(&machine);
+        actions
+    };
+    run_all(&actions);
+}
+
+///Stops hold bindings from firing, before the window procedure is given back.
+pub fn stop_hold_timer() {
+    if let Some(hwnd) = GAME_HWND.get() {
+        unsafe {
+            KillTimer(*hwnd, HOLD_TIMER_ID).ok();
+        }
+    }
+}
+
+///Forgets which keys are down, key ups get lost when the window loses focus.
+pub fn reset_triggers() {
+    let mut machine = BINDING_MACHINE.lock().unwrap();
+    machine.reset();
+    schedule_hold_timer(&machine);
+}
+
+fn run_all(actions: &[Arc<Macro>]) {
+    for action in actions {
+        action.run();
+    }
+}
+
+fn schedule_hold_timer(machine: &BindingMachine<Trigger, Arc<Macro>>) {
+    let Some(hwnd) = GAME_HWND.get() else {
+        return;
+    };
+    unsafe {
+        match machine.next_deadline() {
+            Some(deadline) => {
+                let wait = deadline.saturating_duration_since(Instant::now());
+                SetTimer(*hwnd, HOLD_TIMER_ID, (wait.as_millis() as u32).max(1), None);
+            }
+            None => {
+                KillTimer(*hwnd, HOLD_TIMER_ID).ok();
+            }
+        }
+    }
+}
+
+fn held_modifiers() -> HeldModifiers {
+    let held = |left: VIRTUAL_KEY, right: VIRTUAL_KEY| HeldModifier {
+        left: is_key_down(left),
+        right: is_key_down(right),
+    };
+    HeldModifiers {
+        ctrl: held(VK_LCONTROL, VK_RCONTROL),
+        alt: held(VK_LMENU, VK_RMENU),
+        shift: held(VK_LSHIFT, VK_RSHIFT),
+        win: held(VK_LWIN, VK_RWIN),
+    }
+}
+
+fn is_key_down(key: VIRTUAL_KEY) -> bool {
+    (unsafe { GetKeyState(key.0 as i32) } as u16 & 0x8000) != 0
+}
+
+fn toggle_rendering_action() {
+    log::info!("Rendering toggled.");
+    DEBUG_FEATURES.rendering_enabled.store(
+        !DEBUG_FEATURES.rendering_enabled.load(Ordering::Relaxed),
+        Ordering::Relaxed,
+    );
+}
+fn toggle_processing_action() {
+    log::info!("Processing toggled.");
+    DEBUG_FEATURES.processing_enabled.store(
+        !DEBUG_FEATURES.processing_enabled.load(Ordering::Relaxed),
+        Ordering::Relaxed,
+    );
+}
+fn toggle_debug_overlay() {
+    let old = DEBUG_FEATURES.debug_overlay_enabled.load(Ordering::Relaxed);
+    set_debug_overlay(!old);
+}
+fn set_debug_overlay(enabled: bool) {
+    DEBUG_FEATURES
+        .debug_overlay_enabled
+        .store(enabled, Ordering::Relaxed);
+    //Need to clear the overlay
+    if !enabled {
+        /*if let Some(buf) = FRAME_BUFFER.get() {
+            let mut frame = buf.lock().unwrap();
+            let width = frame.width;
+            clear_debug_overlay(&mut frame.pixels, width);
+        }*/
+        //TODO
+    } else {
+        refresh_overlay_buffer(None);
+    }
+    log::info!("Debug overlay: {}", enabled);
+}
+
+fn change_overlay_mode_to_log() {
+    OVERLAY_MODE.store(overlay_mode::LOG_MODE, Ordering::Relaxed);
+    refresh_overlay_buffer(None);
+}
+fn change_overlay_mode_to_statistics() {
+    OVERLAY_MODE.store(overlay_mode::STAT_MODE, Ordering::Relaxed);
+    refresh_overlay_buffer(None);
+}
+
+fn toggle_raw_input() {
+    let enabled = !INPUT_SETTINGS.raw_input_enabled.load(Ordering::Relaxed);
+    set_raw_input_enabled(enabled);
+    log::info!("Raw input forwarding: {}", enabled);
+}
+fn toggle_raw_input_mouse_look() {
+    let old = INPUT_SETTINGS
+        .raw_input_during_mouse_look
+        .load(Ordering::Relaxed);
+    INPUT_SETTINGS
+        .raw_input_during_mouse_look
+        .store(!old, Ordering::Relaxed);
+    log::info!("Raw input forwarding during mouse-look: {}", !old);
+}
+fn cycle_focus_policy() {
+    set_focus_policy(INPUT_SETTINGS.focus_policy().next());
+}
+fn set_focus_policy(policy: FocusPolicy) {
+    INPUT_SETTINGS
+        .focus_policy
+        .store(policy as u8, Ordering::Relaxed);
+    log::info!("Focus policy: {}", policy.name());
+}
+fn toggle_gamepad() {
+    let old = INPUT_SETTINGS.gamepad_enabled.load(Ordering::Relaxed);
+    set_gamepad(!old);
+}
+fn set_gamepad(enabled: bool) {
+    INPUT_SETTINGS
+        .gamepad_enabled
+        .store(enabled, Ordering::Relaxed);
+    log::info!("Gamepad overlay cursor: {}", enabled);
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+
+//When a keybind is allowed to fire, set with the scope of a keybind in overlay.toml:
+//"overlay_connected, game_not_typing". The keybind only fires while every listed
+//condition holds, otherwise the key goes to the game. No scope (or "always") means global.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Scope(u8);
+
+impl Scope {
+    pub const ALWAYS: Scope = Scope(0);
+    //Blish (or another overlay) is running and sharing its texture.
+    pub const OVERLAY_CONNECTED: Scope = Scope(1 << 0);
+    pub const DEBUG_OVERLAY_VISIBLE: Scope = Scope(1 << 1);
+    //The game doesn't have a text box focused (chat, search...).
+    pub const GAME_NOT_TYPING: Scope = Scope(1 << 2);
+
+    const NAMED: [(&'static str, Scope); 3] = [
+        ("overlay_connected", Scope::OVERLAY_CONNECTED),
+        ("debug_overlay_visible", Scope::DEBUG_OVERLAY_VISIBLE),
+        ("game_not_typing", Scope::GAME_NOT_TYPING),
+    ];
+
+    pub fn contains(self, other: Scope) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn with(self, other: Scope) -> Scope {
+        Scope(self.0 | other.0)
+    }
+
+    ///`state` holds the conditions that are true right now.
+    pub fn is_active(self, state: Scope) -> bool {
+        state.contains(self)
+    }
+
+    //More conditions means more specific.
+    fn specificity(self) -> u32 {
+        self.0.count_ones()
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if *self == Scope::ALWAYS {
+            return write!(f, "always");
+        }
+        let names = Scope::NAMED
+            .iter()
+            .filter(|(_, scope)| self.contains(*scope))
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>();
+        write!(f, "{}", names.join(", "))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScopeError {
+    Unknown(String),
+}
+
+impl fmt::Display for ScopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScopeError::Unknown(name) => write!(
+                f,
+                "unknown scope \"{}\", expected always, overlay_connected, debug_overlay_visible or game_not_typing",
+                name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScopeError {}
+
+///Parses a list of conditions, "overlay_connected, game_not_typing". Empty means always.
+pub fn parse_scope(text: &str) -> Result<Scope, ScopeError> {
+    let mut scope = Scope::ALWAYS;
+    for name in text.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        let name = name.to_ascii_lowercase();
+        if name == "always" {
+            continue;
+        }
+        let (_, named) = Scope::NAMED
+            .iter()
+            .find(|(n, _)| *n == name)
+            .ok_or_else(|| ScopeError::Unknown(name.clone()))?;
+        scope = scope.with(*named);
+    }
+    Ok(scope)
+}
+
+//What a keybind does in each scope it was bound in, most specific scope first.
+#[derive(Debug, Clone)]
+pub struct Scoped<B> {
+    entries: Vec<(Scope, B)>,
+}
+
+impl<B> Default for Scoped<B> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<B> Scoped<B> {
+    pub fn get_or_insert_default(&mut self, scope: Scope) -> &mut B
+    where
+        B: Default,
+    {
+        let index = match self.entries.iter().position(|(s, _)| *s == scope) {
+            Some(index) => index,
+            None => {
+                //Stable, so scopes as specific as each other keep the file order.
+                let index = self
+                    .entries
+                    .iter()
+                    .position(|(s, _)| s.specificity() < scope.specificity())
+                    .unwrap_or(self.entries.len());
+                self.entries.insert(index, (scope, B::default()));
+                index
+            }
+        };
+        &mut self.entries[index].1
+    }
+
+    ///The bindings of the most specific scope active in `state`.
+    pub fn active(&self, state: Scope) -> Option<&B> {
+        self.entries
+            .iter()
+            .find(|(scope, _)| scope.is_active(state))
+            .map(|(_, bindings)| bindings)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(Scope, B)> {
+        self.entries.iter()
+    }
+
+    pub fn map<C>(self, f: impl Fn(B) -> C) -> Scoped<C> {
+        Scoped {
+            entries: self
+                .entries
+                .into_iter()
+                .map(|(scope, bindings)| (scope, f(bindings)))
+                .collect(),
+        }
+    }
+}
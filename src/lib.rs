@@ -1,16 +1,22 @@
 use address_finder::AddressFinder;
-use chrono::Local;
-use controls::{initialize_controls, start_mouse_input_thread};
-use debug::{debug_overlay::add_to_debug_log_overlay, statistics::start_statistics_server};
-use fern::Dispatch;
+use config::{install_config, load_config};
+use controls::{
+    close_input_socket, gamepad::start_gamepad_thread, initialize_controls,
+    mumble_link::close_link, restore_controls, start_mouse_input_thread, stop_mouse_input_thread,
+};
+use debug::statistics::start_statistics_server;
+use globals::{Lifecycle, lifecycle, set_lifecycle};
 use hooks::present_hook;
-use keybinds::init_keybinds;
+use logging::enable_logging;
+use shutdown::{
+    HOOK_CALL_TIMEOUT, Teardown, WORKER_TIMEOUT, own_hook_calls, spawn_worker, stop_workers,
+    stopping, wait_for_hook_calls,
+};
 use std::{
-    fs::{OpenOptions, create_dir_all},
     mem,
-    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
 };
-use ui::mmf::start_mmf_thread;
+use ui::{mmf::start_mmf_thread, release_overlay};
 use utils::{get_base_addr_and_size, get_mainwindow_hwnd};
 #[cfg(not(feature = "nexus"))]
 use windows::Win32::System::SystemServices::{DLL_PROCESS_ATTACH, DLL_PROCESS_DETACH};
@@ -20,31 +26,42 @@ use windows::Win32::{Foundation::HINSTANCE, System::LibraryLoader::FreeLibraryAn
 use nexus::{self, AddonFlags};
 
 pub mod address_finder;
+pub mod config;
 pub mod controls;
 pub mod debug;
 pub mod globals;
 pub mod hooks;
 pub mod keybinds;
+pub mod logging;
+pub mod shutdown;
 pub mod ui;
 pub mod utils;
 
 static mut HANDLE_NO: u64 = 0;
+//Set once by eject(), a second keybind press or request is ignored.
+static EJECTING: AtomicBool = AtomicBool::new(false);
 
 /*
  *
  * This assumes that the DLL is loaded in a general way, such as LoadLibraryW. If other loading
  * methods need to be supported, simply call attach() and detatch() where appropriate.
  *
- * TODO: detatch() is poorly tested. It also definitely lacks some unloading stuff, like wnd_proc
+ * A null reserved pointer on DLL_PROCESS_DETACH means FreeLibrary, otherwise the process is exiting.
  *
  * */
 #[cfg(not(feature = "nexus"))]
 #[unsafe(no_mangle)]
 #[allow(unused_variables)]
-extern "system" fn DllMain(dll_module: HINSTANCE, call_reason: u32, _: *mut ()) -> bool {
+extern "system" fn DllMain(dll_module: HINSTANCE, call_reason: u32, reserved: *mut ()) -> bool {
     match call_reason {
         DLL_PROCESS_ATTACH => attach(dll_module),
-        DLL_PROCESS_DETACH => detatch(),
+        DLL_PROCESS_DETACH => {
+            detatch(if reserved.is_null() {
+                Teardown::LoaderLock
+            } else {
+                Teardown::ProcessExit
+            });
+        }
 
         _ => (),
     }
@@ -53,16 +70,24 @@ extern "system" fn DllMain(dll_module: HINSTANCE, call_reason: u32, _: *mut ())
 ///THE MAIN FUNCTION. It initializes everything needed.
 ///Ideally, all hooks are created here.
 fn attach(handle: HINSTANCE) {
-    std::thread::spawn(move || {
+    spawn_worker("attach", move || {
+        let config = load_config();
+        enable_logging(&config.config.logging);
         log::info!("Attaching to process");
-        enable_logging();
+        install_config(config);
 
         //Do this early - only needed for external overlay functionality
         start_mmf_thread();
 
+        set_lifecycle(Lifecycle::Hooking);
+
         let (base, size) = get_base_addr_and_size();
 
-        let mainwindow_hwnd = get_mainwindow_hwnd().expect("Could not get the game's window.");
+        let window_class = config::config()
+            .active_profile()
+            .and_then(|profile| profile.window_class.clone());
+        let mainwindow_hwnd =
+            get_mainwindow_hwnd(window_class.as_deref()).expect("Could not get the game's window.");
 
         if base == 0 || size == 0 {
             log::error!(
@@ -85,6 +110,12 @@ fn attach(handle: HINSTANCE) {
             unsafe { FreeLibraryAndExitThread(HINSTANCE { 0: handle.0 }, 0) };
         }
 
+        //detatch() waits for this thread, nothing may be hooked once it started.
+        if stopping() {
+            log::info!("Detaching, the hooks are not installed.");
+            return;
+        }
+
         unsafe {
             present_hook
                 .initialize(
@@ -99,90 +130,137 @@ fn attach(handle: HINSTANCE) {
         unsafe { HANDLE_NO = handle.0 as u64 };
 
         start_statistics_server();
-        init_keybinds();
+
+        if stopping() {
+            log::info!("Detaching, the controls are not initialized.");
+            return;
+        }
 
         //MUST BE CALLED IN THIS ORDER
         start_mouse_input_thread();
         initialize_controls(mainwindow_hwnd);
+        start_gamepad_thread();
+        set_lifecycle(Lifecycle::Running);
     });
 }
 
-fn detatch() {
-    log::info!("Detatching from process");
+///Undoes attach() so the DLL can be unloaded, in this order:
+///1. Stop the worker threads, the attach thread included, so nothing new gets hooked or started.
+///2. Disable the present hook and give the window back to the game.
+///3. Wait for the calls still inside present() or wnd_proc.
+///4. Release the D3D resources, the MMF and the input socket.
+///Every wait has a timeout. What didn't stop in time is logged, unloading is then unsafe.
+///Returns true if the DLL can be unloaded.
+fn detatch(teardown: Teardown) -> bool {
+    if matches!(lifecycle(), Lifecycle::Detaching | Lifecycle::Detached) {
+        return false;
+    }
+    set_lifecycle(Lifecycle::Detaching);
+    log::info!("Detatching from process ({:?})", teardown);
+    if teardown == Teardown::ProcessExit {
+        log::logger().flush();
+        return false;
+    }
+    let mut safe = true;
+
+    stop_mouse_input_thread();
+    let running = stop_workers(WORKER_TIMEOUT, teardown == Teardown::Unload);
+    if running.is_empty() {
+        log::info!("Stopped the worker threads.");
+    } else {
+        log::warn!(
+            "Still running after {:?}: {}",
+            WORKER_TIMEOUT,
+            running.join(", ")
+        );
+        safe = false;
+    }
+
     unsafe {
-        present_hook.disable().unwrap();
+        if present_hook.is_enabled() {
+            match present_hook.disable() {
+                Ok(()) => log::info!("Disabled the present hook."),
+                Err(e) => {
+                    log::error!("Could not disable the present hook: {}", e);
+                    safe = false;
+                }
+            }
+        }
+    }
+    safe &= restore_controls();
+
+    let returned = wait_for_hook_calls(HOOK_CALL_TIMEOUT);
+    if !returned {
+        log::warn!(
+            "Calls into present() or wnd_proc did not return within {:?}.",
+            HOOK_CALL_TIMEOUT
+        );
+    }
+    if own_hook_calls() > 0 {
+        log::warn!("Detaching from inside present() or wnd_proc.");
+    }
+    //A call or a worker still running may be using the overlay's resources (the mmf thread
+    //reads the mapped header), those are left alone then.
+    if returned && own_hook_calls() == 0 && running.is_empty() {
+        release_overlay();
+        //Keybind scopes read it from wnd_proc.
+        close_link();
+    } else {
+        safe = false;
+    }
+    close_input_socket();
+
+    set_lifecycle(Lifecycle::Detached);
+    if safe {
+        log::info!("Detached, the DLL can be unloaded.");
+    } else {
+        log::warn!("Detached, but unloading the DLL now may crash the game.");
     }
+    log::logger().flush();
+    safe
 }
-fn enable_logging() {
-    let file = {
-        let logs_dir = PathBuf::from("addons/LOADER_public/logs");
-
-        create_dir_all(&logs_dir).expect("Failed to create logs directory");
-
-        let filename = format!("overlay-{}.log", Local::now().format("%Y-%m-%d_%H-%M-%S"));
-        let filepath = logs_dir.join(filename);
-
-        OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(filepath)
-            .expect("Failed to open log file")
-    };
-
-    //Init Fern
-    Dispatch::new()
-        .level(log::LevelFilter::Debug)
-        .chain(std::io::stdout())
-        .chain(file)
-        .format(|out, message, record| {
-            let now = Local::now();
-            let format = if record.level() == log::Level::Error {
-                format_args!(
-                    "[{}] [external-dx11-overlay] [{}] [{}:{}] {}",
-                    now.format("%Y-%m-%d %H:%M:%S"),
-                    record.level(),
-                    record.file().unwrap_or("<unknown>"),
-                    record.line().unwrap_or(0),
-                    message
-                )
-            } else {
-                format_args!(
-                    "[{}] [external-dx11-overlay] [{}] {}",
-                    now.format("%Y-%m-%d %H:%M:%S"),
-                    record.level(),
-                    message
-                )
-            };
-            add_to_debug_log_overlay(format.to_string());
-            out.finish(format);
-        })
-        .apply()
-        .ok();
-
-    //Panic hook
-    std::panic::set_hook(Box::new(|panic_info| {
-        let payload = panic_info
-            .payload()
-            .downcast_ref::<&str>()
-            .copied()
-            .or_else(|| {
-                panic_info
-                    .payload()
-                    .downcast_ref::<String>()
-                    .map(|s| s.as_str())
-            })
-            .unwrap_or("Unknown panic");
-
-        let location = panic_info
-            .location()
-            .map(|l| format!("{}:{}", l.file(), l.line()))
-            .unwrap_or_else(|| "unknown location".to_string());
-        log::error!("PANIC at {}: {}", location, payload);
-    }));
-
-    log::info!(
-        "---------------------------------------- New Session ----------------------------------------------"
-    );
+
+///Tears everything down and frees the DLL, so a misbehaving overlay can go without restarting
+///the game. Runs on a helper thread, the caller (wnd_proc, the subscription thread) has to return
+///first. Returns false if the eject can't start.
+pub fn eject() -> bool {
+    //Nexus keeps calling into its addons, it has to be the one unloading them.
+    if cfg!(feature = "nexus") {
+        log::warn!("Eject: loaded by Nexus, unload the addon from Nexus instead.");
+        return false;
+    }
+    if lifecycle() != Lifecycle::Running {
+        log::warn!("Eject: not possible while {}.", lifecycle().name());
+        return false;
+    }
+    if EJECTING.swap(true, Ordering::Relaxed) {
+        log::warn!("Eject: already ejecting.");
+        return false;
+    }
+    let module = unsafe { HANDLE_NO };
+    if module == 0 {
+        log::warn!("Eject: the module handle is unknown.");
+        return false;
+    }
+    log::info!("Eject: starting.");
+    //Not a worker: it stops them, and never returns.
+    let spawned = std::thread::Builder::new()
+        .name("eject".to_string())
+        .spawn(move || {
+            log::info!("Eject: tearing down.");
+            if !detatch(Teardown::Unload) {
+                log::warn!("Eject: the DLL stays loaded, but does nothing anymore.");
+                return;
+            }
+            log::info!("Eject: freeing the library.");
+            log::logger().flush();
+            unsafe { FreeLibraryAndExitThread(HINSTANCE(module as isize), 0) };
+        });
+    if let Err(e) = spawned {
+        log::error!("Eject: could not start the eject thread: {}", e);
+        return false;
+    }
+    true
 }
 
 // ================================== Nexus export ================================
@@ -194,7 +272,9 @@ fn nexus_load_wrapper() {
 
 #[cfg(feature = "nexus")]
 fn nexus_unload_wrapper() {
-    nexus_integration::nexus_unload(detatch);
+    nexus_integration::nexus_unload(|| {
+        detatch(Teardown::Unload);
+    });
 }
 
 #[cfg(feature = "nexus")]
@@ -206,5 +286,4 @@ nexus::export! {
     flags: AddonFlags::None,
     provider: nexus::UpdateProvider::GitHub,
     update_link: "https://github.com/SorryQuick/external-dx11-overlay",
-    log_filter: "trace"
 }
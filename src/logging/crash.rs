@@ -0,0 +1,174 @@
+use std::{
+    backtrace::Backtrace,
+    collections::VecDeque,
+    fmt::Write,
+    fs,
+    panic::PanicHookInfo,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use chrono::Local;
+
+use super::log_session;
+use crate::{
+    config::try_config, globals::lifecycle, ui::mmf::try_is_overlay_connected,
+    utils::describe_platform,
+};
+
+//A panic writes crash-<session>.txt next to the log files of the session.
+pub const CRASH_PREFIX: &str = "crash-";
+pub const CRASH_EXTENSION: &str = ".txt";
+//Reports of older crashes are removed beyond that many.
+const KEEP_REPORTS: usize = 10;
+//Log lines copied into the report.
+const RECENT_LINES: usize = 200;
+
+//The last log lines, in the text format, oldest first.
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+pub fn remember_line(line: String) {
+    let mut recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+    if recent.len() == RECENT_LINES {
+        recent.pop_front();
+    }
+    recent.push_back(line);
+}
+
+pub fn crash_report_name(session: &str) -> String {
+    format!("{}{}{}", CRASH_PREFIX, session, CRASH_EXTENSION)
+}
+
+pub fn parse_crash_report_name(name: &str) -> Option<&str> {
+    name.strip_prefix(CRASH_PREFIX)?
+        .strip_suffix(CRASH_EXTENSION)
+        .filter(|session| !session.is_empty())
+}
+
+///Logs panics and writes a crash report for them next to the log of the session.
+pub fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|panic_info| {
+        let payload = panic_payload(panic_info);
+        let location = panic_info
+            .location()
+            .map(|l| format!("{}:{}", l.file(), l.line()))
+            .unwrap_or_else(|| "unknown location".to_string());
+        //The report first: the panic may come from inside the logger, with its lock held,
+        //and logging would then never return.
+        let written = log_session().and_then(|session| {
+            let path = session.dir.join(crash_report_name(&session.id));
+            //Several threads may panic, the first report is the interesting one.
+            if path.exists() {
+                return None;
+            }
+            Some(fs::write(&path, crash_report(&session.id, &location, payload)).map(|()| path))
+        });
+
+        log::error!("PANIC at {}: {}", location, payload);
+        match written {
+            Some(Ok(path)) => log::error!("Crash report written to {}", path.display()),
+            Some(Err(e)) => log::error!("Could not write the crash report: {}", e),
+            None => {}
+        }
+    }));
+}
+
+fn panic_payload<'a>(panic_info: &'a PanicHookInfo) -> &'a str {
+    panic_info
+        .payload()
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| {
+            panic_info
+                .payload()
+                .downcast_ref::<String>()
+                .map(|s| s.as_str())
+        })
+        .unwrap_or("Unknown panic")
+}
+
+//Everything is read without blocking: the panic may come from a thread holding a lock.
+fn crash_report(session: &str, location: &str, payload: &str) -> String {
+    let mut report = String::new();
+    let _ = writeln!(report, "External DX11 overlay crash report");
+    let _ = writeln!(report, "Version: {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(report, "Time: {}", Local::now().format("%Y-%m-%d %H:%M:%S"));
+    let _ = writeln!(report, "Session: {}", session);
+    let _ = writeln!(
+        report,
+        "Thread: {}",
+        std::thread::current().name().unwrap_or("<unnamed>")
+    );
+    let _ = writeln!(report, "Location: {}", location);
+    let _ = writeln!(report, "Message: {}", payload);
+    let _ = writeln!(report, "State: {}", lifecycle().name());
+    let connected = match try_is_overlay_connected() {
+        Some(connected) => connected.to_string(),
+        None => "unknown".to_string(),
+    };
+    let _ = writeln!(report, "Overlay connected: {}", connected);
+    let _ = writeln!(report, "Platform: {}", describe_platform());
+    let _ = writeln!(
+        report,
+        "Game: {}",
+        std::env::current_exe()
+            .map(|exe| exe.display().to_string())
+            .unwrap_or_else(|_| "<unknown>".to_string())
+    );
+
+    let _ = writeln!(report, "\n---------------- Backtrace ----------------");
+    let _ = writeln!(report, "{}", Backtrace::force_capture());
+
+    let _ = writeln!(report, "---------------- Configuration ----------------");
+    match try_config() {
+        Some(config) => {
+            let profile = config
+                .active_profile()
+                .map_or("none", |profile| profile.name.as_str());
+            let _ = writeln!(report, "Profile: {}\n", profile);
+            let _ = writeln!(report, "{}", config.to_toml());
+        }
+        None => {
+            let _ = writeln!(report, "<in use by the crashing thread>\n");
+        }
+    }
+
+    let _ = writeln!(report, "---------------- Last log lines ----------------");
+    match RECENT.try_lock() {
+        Ok(recent) => {
+            for line in recent.iter() {
+                let _ = writeln!(report, "{}", line);
+            }
+        }
+        Err(_) => {
+            let _ = writeln!(report, "<in use by the crashing thread>");
+        }
+    }
+    report
+}
+
+///The crash report of `session` among `reports`, if it crashed.
+pub fn find_crash_report(reports: &[PathBuf], session: &str) -> Option<PathBuf> {
+    reports
+        .iter()
+        .find(|path| report_session(path) == Some(session))
+        .cloned()
+}
+
+///The reports beyond the newest KEEP_REPORTS.
+pub fn expired_reports(reports: &[PathBuf]) -> Vec<PathBuf> {
+    let mut sessions: Vec<(&str, &PathBuf)> = reports
+        .iter()
+        .filter_map(|path| Some((report_session(path)?, path)))
+        .collect();
+    sessions.sort_unstable_by(|a, b| b.0.cmp(a.0));
+    sessions
+        .into_iter()
+        .skip(KEEP_REPORTS)
+        .map(|(_, path)| path.clone())
+        .collect()
+}
+
+pub fn report_session(path: &Path) -> Option<&str> {
+    parse_crash_report_name(path.file_name()?.to_str()?)
+}
@@ -0,0 +1,296 @@
+use std::{fmt, str::FromStr};
+
+use log::LevelFilter;
+
+//Level of the targets no directive matches, when the filter doesn't give one.
+pub const DEFAULT_LEVEL: LevelFilter = LevelFilter::Debug;
+
+///Which records get logged: a level for everything, and levels for some targets.
+///Written like "info,mmf=trace,controls=warn".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter {
+    default: LevelFilter,
+    directives: Vec<Directive>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Directive {
+    //Module path, or the end of one: "mmf", "ui::mmf", "nexus_integration"...
+    target: String,
+    level: LevelFilter,
+}
+
+impl LogFilter {
+    pub const fn new(level: LevelFilter) -> Self {
+        Self {
+            default: level,
+            directives: Vec::new(),
+        }
+    }
+
+    ///The level of a target. The directive with the longest matching path wins.
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.directives
+            .iter()
+            .filter(|directive| target_matches(&directive.target, target))
+            .max_by_key(|directive| directive.target.split("::").count())
+            .map_or(self.default, |directive| directive.level)
+    }
+
+    ///The most verbose level any target gets, for log::set_max_level().
+    pub fn max_level(&self) -> LevelFilter {
+        self.directives
+            .iter()
+            .map(|directive| directive.level)
+            .fold(self.default, Ord::max)
+    }
+
+    pub fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+}
+
+//"ui::mmf" matches "external_dx11_overlay::ui::mmf" and "external_dx11_overlay::ui::mmf::header",
+//not "external_dx11_overlay::ui::mmfx".
+fn target_matches(directive: &str, target: &str) -> bool {
+    let directive: Vec<&str> = directive.split("::").collect();
+    let target: Vec<&str> = target.split("::").collect();
+    target
+        .windows(directive.len())
+        .any(|window| window == directive)
+}
+
+impl FromStr for LogFilter {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut filter = LogFilter::new(DEFAULT_LEVEL);
+        let mut default = None;
+        for part in text
+            .split(',')
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+        {
+            match part.split_once('=') {
+                Some((target, level)) => {
+                    let target = target.trim();
+                    if target.is_empty() || target.split("::").any(str::is_empty) {
+                        return Err(format!("\"{}\" is not a module name", target));
+                    }
+                    let level = parse_level(level.trim())?;
+                    filter
+                        .directives
+                        .retain(|directive| directive.target != target);
+                    filter.directives.push(Directive {
+                        target: target.to_string(),
+                        level,
+                    });
+                }
+                None => {
+                    if default.is_some() {
+                        return Err(format!(
+                            "\"{}\": only one level can be given for everything",
+                            part
+                        ));
+                    }
+                    default = Some(parse_level(part)?);
+                }
+            }
+        }
+        if default.is_none() && filter.directives.is_empty() {
+            return Err("must not be empty".to_string());
+        }
+        filter.default = default.unwrap_or(DEFAULT_LEVEL);
+        Ok(filter)
+    }
+}
+
+fn parse_level(text: &str) -> Result<LevelFilter, String> {
+    text.parse().map_err(|_| {
+        format!(
+            "\"{}\" is not one of off, error, warn, info, debug, trace",
+            text
+        )
+    })
+}
+
+impl fmt::Display for LogFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.default.as_str().to_ascii_lowercase())?;
+        for directive in &self.directives {
+            write!(
+                f,
+                ",{}={}",
+                directive.target,
+                directive.level.as_str().to_ascii_lowercase()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use LevelFilter::{Debug, Error, Info, Off, Trace, Warn};
+
+    const CRATE: &str = "external_dx11_overlay";
+
+    fn parse(text: &str) -> LogFilter {
+        text.parse().unwrap_or_else(|e| panic!("{:?}: {}", text, e))
+    }
+
+    fn target(path: &str) -> String {
+        format!("{}::{}", CRATE, path)
+    }
+
+    #[test]
+    fn single_level() {
+        for (text, level) in [
+            ("off", Off),
+            ("error", Error),
+            ("WARN", Warn),
+            ("Info", Info),
+            ("debug", Debug),
+            ("trace", Trace),
+        ] {
+            let filter = parse(text);
+            assert_eq!(filter.level_for(CRATE), level);
+            assert_eq!(filter.level_for(&target("ui::mmf")), level);
+            assert_eq!(filter.max_level(), level);
+        }
+    }
+
+    #[test]
+    fn directives_without_a_level_use_the_default() {
+        let filter = parse("mmf=trace");
+        assert_eq!(filter.level_for(CRATE), DEFAULT_LEVEL);
+        assert_eq!(filter.level_for(&target("ui::mmf")), Trace);
+    }
+
+    #[test]
+    fn module_matches_its_submodules() {
+        let filter = parse("info,controls=warn");
+        assert_eq!(filter.level_for(&target("controls")), Warn);
+        assert_eq!(filter.level_for(&target("controls::gamepad")), Warn);
+        assert_eq!(filter.level_for(&target("ui")), Info);
+        //Whole components only.
+        assert_eq!(filter.level_for(&target("controlsx")), Info);
+        assert_eq!(filter.level_for(&target("my_controls")), Info);
+    }
+
+    #[test]
+    fn directives_match_anywhere_in_the_path() {
+        let filter = parse("info,mmf=trace,ui::swapchain=error");
+        assert_eq!(filter.level_for(&target("ui::mmf")), Trace);
+        assert_eq!(filter.level_for("mmf"), Trace);
+        assert_eq!(filter.level_for(&target("ui::swapchain::present")), Error);
+        assert_eq!(filter.level_for(&target("swapchain")), Info);
+        assert_eq!(filter.level_for(&target("ui")), Info);
+    }
+
+    #[test]
+    fn longest_match_wins() {
+        let filter = parse("info,ui=warn,ui::mmf=trace,ui::mmf::header=off");
+        assert_eq!(filter.level_for(&target("ui")), Warn);
+        assert_eq!(filter.level_for(&target("ui::swapchain")), Warn);
+        assert_eq!(filter.level_for(&target("ui::mmf")), Trace);
+        assert_eq!(filter.level_for(&target("ui::mmf::reader")), Trace);
+        assert_eq!(filter.level_for(&target("ui::mmf::header")), Off);
+        //The order in the text doesn't matter.
+        let reversed = parse("ui::mmf::header=off,ui::mmf=trace,ui=warn,info");
+        for path in ["ui", "ui::swapchain", "ui::mmf", "ui::mmf::header"] {
+            assert_eq!(
+                reversed.level_for(&target(path)),
+                filter.level_for(&target(path)),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn equally_long_matches_take_the_last_one() {
+        let filter = parse("info,ui=warn,mmf=trace");
+        assert_eq!(filter.level_for(&target("ui::mmf")), Trace);
+        let filter = parse("info,mmf=trace,ui=warn");
+        assert_eq!(filter.level_for(&target("ui::mmf")), Warn);
+    }
+
+    #[test]
+    fn repeated_module_keeps_the_last_level() {
+        let filter = parse("info,mmf=trace,ui=warn,mmf=error");
+        assert_eq!(filter.level_for(&target("ui::mmf")), Error);
+        assert_eq!(filter.to_string(), "info,ui=warn,mmf=error");
+    }
+
+    #[test]
+    fn max_level() {
+        assert_eq!(parse("info,mmf=trace").max_level(), Trace);
+        assert_eq!(parse("debug,mmf=error").max_level(), Debug);
+        assert_eq!(parse("off,mmf=warn").max_level(), Warn);
+    }
+
+    #[test]
+    fn enabled() {
+        let filter = parse("warn,mmf=debug");
+        let metadata = |level, target| log::Metadata::builder().level(level).target(target).build();
+        let mmf = target("ui::mmf");
+        assert!(filter.enabled(&metadata(log::Level::Debug, &mmf)));
+        assert!(!filter.enabled(&metadata(log::Level::Trace, &mmf)));
+        assert!(filter.enabled(&metadata(log::Level::Warn, CRATE)));
+        assert!(!filter.enabled(&metadata(log::Level::Info, CRATE)));
+    }
+
+    #[test]
+    fn canonical_text() {
+        let cases = [
+            ("info", "info"),
+            (" INFO , mmf = TRACE ", "info,mmf=trace"),
+            ("mmf=trace", "debug,mmf=trace"),
+            ("ui::mmf=warn,error", "error,ui::mmf=warn"),
+            ("info,,mmf=trace,", "info,mmf=trace"),
+        ];
+        for (text, canonical) in cases {
+            let filter = parse(text);
+            assert_eq!(filter.to_string(), canonical, "{:?}", text);
+            assert_eq!(parse(canonical), filter);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let cases = [
+            ("", "must not be empty"),
+            (" , ", "must not be empty"),
+            (
+                "loud",
+                "\"loud\" is not one of off, error, warn, info, debug, trace",
+            ),
+            (
+                "info,mmf=loud",
+                "\"loud\" is not one of off, error, warn, info, debug, trace",
+            ),
+            (
+                "mmf=",
+                "\"\" is not one of off, error, warn, info, debug, trace",
+            ),
+            ("=trace", "\"\" is not a module name"),
+            ("ui::=trace", "\"ui::\" is not a module name"),
+            ("::mmf=trace", "\"::mmf\" is not a module name"),
+            ("ui::::mmf=trace", "\"ui::::mmf\" is not a module name"),
+            (
+                "info,warn",
+                "\"warn\": only one level can be given for everything",
+            ),
+        ];
+        for (text, error) in cases {
+            assert_eq!(
+                text.parse::<LogFilter>(),
+                Err(error.to_string()),
+                "{:?}",
+                text
+            );
+        }
+    }
+}
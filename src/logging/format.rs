@@ -0,0 +1,291 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, Local, SecondsFormat};
+use log::{
+    Record,
+    kv::{self, Key, Value, VisitSource},
+};
+use serde::Serialize;
+use serde_json::{Map, Number};
+
+//How a sink writes records.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    //[date] [external-dx11-overlay] [LEVEL] message
+    Text = 0,
+    //One JSON object per line, see JsonRecord.
+    Json = 1,
+}
+
+impl LogFormat {
+    pub const ALL: [LogFormat; 2] = [LogFormat::Text, LogFormat::Json];
+
+    //Same order as ALL.
+    pub const NAMES: [&'static str; 2] = ["text", "json"];
+
+    pub fn name(self) -> &'static str {
+        Self::NAMES[self as usize]
+    }
+}
+
+///A record as written by `format`, without the line break.
+pub fn format_record(format: LogFormat, record: &Record, now: DateTime<Local>) -> String {
+    match format {
+        LogFormat::Text => text_line(record, now),
+        LogFormat::Json => json_line(record, now),
+    }
+}
+
+///The format of the log file from the start, with the key-value fields appended.
+pub fn text_line(record: &Record, now: DateTime<Local>) -> String {
+    let mut line = format!(
+        "[{}] [external-dx11-overlay] [{}] ",
+        now.format("%Y-%m-%d %H:%M:%S"),
+        record.level()
+    );
+    if record.level() == log::Level::Error {
+        let _ = write!(
+            line,
+            "[{}:{}] ",
+            record.file().unwrap_or("<unknown>"),
+            record.line().unwrap_or(0)
+        );
+    }
+    let _ = write!(line, "{}", record.args());
+    for (key, value) in fields(record) {
+        let _ = write!(line, " {}={}", key, value);
+    }
+    line
+}
+
+//Every key is always there, null when the record doesn't have it, so the lines can be
+//read without checking which ones exist. Add keys rather than changing existing ones.
+#[derive(Debug, Serialize)]
+pub struct JsonRecord<'a> {
+    //RFC 3339 with milliseconds and the local offset.
+    pub timestamp: String,
+    //ERROR, WARN, INFO, DEBUG or TRACE.
+    pub level: &'a str,
+    //Module the record comes from, "external_dx11_overlay::ui::mmf".
+    pub module: Option<&'a str>,
+    //Usually the module too, unless the record gave its own target.
+    pub target: &'a str,
+    pub file: Option<&'a str>,
+    pub line: Option<u32>,
+    //None for threads without a name.
+    pub thread: Option<String>,
+    pub message: String,
+    //Key-value pairs of the record: log::info!(port = 49152; "Bound").
+    pub fields: Map<String, serde_json::Value>,
+}
+
+impl<'a> JsonRecord<'a> {
+    pub fn new(record: &'a Record, now: DateTime<Local>) -> Self {
+        Self {
+            timestamp: now.to_rfc3339_opts(SecondsFormat::Millis, false),
+            level: record.level().as_str(),
+            module: record.module_path(),
+            target: record.target(),
+            file: record.file(),
+            line: record.line(),
+            thread: std::thread::current().name().map(str::to_string),
+            message: record.args().to_string(),
+            fields: fields(record)
+                .into_iter()
+                .map(|(key, value)| (key, json_value(&value)))
+                .collect(),
+        }
+    }
+}
+
+pub fn json_line(record: &Record, now: DateTime<Local>) -> String {
+    serde_json::to_string(&JsonRecord::new(record, now))
+        .unwrap_or_else(|e| format!("{{\"message\":\"Could not format record: {}\"}}", e))
+}
+
+//Numbers and booleans stay what they are, everything else is written as text.
+fn json_value(value: &Value) -> serde_json::Value {
+    if let Some(value) = value.to_bool() {
+        serde_json::Value::Bool(value)
+    } else if let Some(value) = value.to_i64() {
+        serde_json::Value::from(value)
+    } else if let Some(value) = value.to_u64() {
+        serde_json::Value::from(value)
+    } else if let Some(value) = value.to_f64().and_then(Number::from_f64) {
+        serde_json::Value::Number(value)
+    } else {
+        serde_json::Value::String(value.to_string())
+    }
+}
+
+fn fields<'a>(record: &'a Record) -> Vec<(String, Value<'a>)> {
+    struct Collect<'a>(Vec<(String, Value<'a>)>);
+    impl<'kvs> VisitSource<'kvs> for Collect<'kvs> {
+        fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+            self.0.push((key.to_string(), value));
+            Ok(())
+        }
+    }
+    let mut collect = Collect(Vec::new());
+    let _ = record.key_values().visit(&mut collect);
+    collect.0
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value as Json;
+
+    use super::*;
+
+    //Every key of a JSON line, and the JSON types it may have.
+    const SCHEMA: [(&str, &[&str]); 9] = [
+        ("timestamp", &["string"]),
+        ("level", &["string"]),
+        ("module", &["string", "null"]),
+        ("target", &["string"]),
+        ("file", &["string", "null"]),
+        ("line", &["number", "null"]),
+        ("thread", &["string", "null"]),
+        ("message", &["string"]),
+        ("fields", &["object"]),
+    ];
+
+    fn type_name(value: &Json) -> &'static str {
+        match value {
+            Json::Null => "null",
+            Json::Bool(_) => "bool",
+            Json::Number(_) => "number",
+            Json::String(_) => "string",
+            Json::Array(_) => "array",
+            Json::Object(_) => "object",
+        }
+    }
+
+    fn now() -> DateTime<Local> {
+        DateTime::parse_from_rfc3339("2026-10-19T12:34:56.789+02:00")
+            .unwrap()
+            .with_timezone(&Local)
+    }
+
+    //Checks the line against SCHEMA and returns it parsed.
+    fn check_schema(line: &str) -> Map<String, Json> {
+        assert!(!line.contains('\n'));
+        let Json::Object(object) = serde_json::from_str(line).unwrap() else {
+            panic!("not an object: {}", line);
+        };
+        let mut keys: Vec<&str> = object.keys().map(String::as_str).collect();
+        let mut expected: Vec<&str> = SCHEMA.iter().map(|(key, _)| *key).collect();
+        keys.sort();
+        expected.sort();
+        assert_eq!(keys, expected);
+        for (key, types) in SCHEMA {
+            let found = type_name(&object[key]);
+            assert!(types.contains(&found), "{} is {}: {}", key, found, line);
+        }
+        let level = object["level"].as_str().unwrap();
+        assert!(["ERROR", "WARN", "INFO", "DEBUG", "TRACE"].contains(&level));
+        let timestamp = object["timestamp"].as_str().unwrap();
+        assert_eq!(DateTime::parse_from_rfc3339(timestamp).unwrap(), now());
+        object
+    }
+
+    #[test]
+    fn full_record() {
+        let kvs: [(&str, Value); 5] = [
+            ("port", Value::from(49152u16)),
+            ("offset", Value::from(-3i32)),
+            ("ratio", Value::from(1.5f64)),
+            ("enabled", Value::from(true)),
+            ("name", Value::from("Blish HUD.exe")),
+        ];
+        let line = json_line(
+            &Record::builder()
+                .level(log::Level::Warn)
+                .target("mmf")
+                .module_path(Some("external_dx11_overlay::ui::mmf"))
+                .file(Some("src/ui/mmf.rs"))
+                .line(Some(42))
+                .args(format_args!("Bound to {}", "127.0.0.1"))
+                .key_values(&kvs)
+                .build(),
+            now(),
+        );
+        let object = check_schema(&line);
+        assert_eq!(object["level"], "WARN");
+        assert_eq!(object["module"], "external_dx11_overlay::ui::mmf");
+        assert_eq!(object["target"], "mmf");
+        assert_eq!(object["file"], "src/ui/mmf.rs");
+        assert_eq!(object["line"], 42);
+        assert_eq!(object["message"], "Bound to 127.0.0.1");
+        assert_eq!(
+            object["fields"],
+            serde_json::json!({
+                "port": 49152,
+                "offset": -3,
+                "ratio": 1.5,
+                "enabled": true,
+                "name": "Blish HUD.exe",
+            })
+        );
+    }
+
+    #[test]
+    fn missing_values_are_null() {
+        //A thread without a name.
+        let line = std::thread::spawn(|| {
+            json_line(
+                &Record::builder()
+                    .level(log::Level::Trace)
+                    .args(format_args!("quote \" and\nnewline"))
+                    .build(),
+                now(),
+            )
+        })
+        .join()
+        .unwrap();
+        let object = check_schema(&line);
+        for key in ["module", "file", "line", "thread"] {
+            assert_eq!(object[key], Json::Null, "{}", key);
+        }
+        assert_eq!(object["message"], "quote \" and\nnewline");
+        assert_eq!(object["fields"], serde_json::json!({}));
+    }
+
+    #[test]
+    fn every_level() {
+        for level in [
+            log::Level::Error,
+            log::Level::Warn,
+            log::Level::Info,
+            log::Level::Debug,
+            log::Level::Trace,
+        ] {
+            let record = Record::builder()
+                .level(level)
+                .args(format_args!("message"))
+                .build();
+            let object = check_schema(&format_record(LogFormat::Json, &record, now()));
+            assert_eq!(object["level"], level.as_str());
+        }
+    }
+
+    #[test]
+    fn text_lines() {
+        let kvs = [("port", 49152)];
+        let record = Record::builder()
+            .level(log::Level::Error)
+            .file(Some("src/lib.rs"))
+            .line(Some(7))
+            .args(format_args!("Failed"))
+            .key_values(&kvs)
+            .build();
+        assert_eq!(
+            format_record(LogFormat::Text, &record, now()),
+            format!(
+                "[{}] [external-dx11-overlay] [ERROR] [src/lib.rs:7] Failed port=49152",
+                now().format("%Y-%m-%d %H:%M:%S")
+            )
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use std::{
+    fs::{self, create_dir_all},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{LazyLock, OnceLock, RwLock},
+    time::SystemTime,
+};
+
+use chrono::Local;
+use fern::{Dispatch, Output};
+
+use crate::{
+    config::{Config, LoggingConfig, data_dir::resolve_path},
+    debug::debug_overlay::{add_to_debug_log_overlay, show_log_overlay},
+    shutdown::spawn_worker,
+    utils::describe_platform,
+};
+use crash::{
+    expired_reports, find_crash_report, install_panic_hook, remember_line, report_session,
+};
+pub use filter::LogFilter;
+pub use format::LogFormat;
+use format::{format_record, text_line};
+use retention::{LogFile, RetentionPolicy, expired, previous_session, to_compress};
+use rotation::{RotatingFile, compress_file};
+
+pub mod crash;
+pub mod filter;
+pub mod format;
+pub mod retention;
+pub mod rotation;
+
+//Replaces logging.level, eg. to trace a single module without editing overlay.toml.
+pub const LOG_FILTER_ENV: &str = "DX11_OVERLAY_LOG";
+
+//Checked for every record by every sink.
+static LOG_FILTER: LazyLock<RwLock<LogFilter>> =
+    LazyLock::new(|| RwLock::new(LogFilter::new(filter::DEFAULT_LEVEL)));
+
+///Changes which records are logged, from now on.
+pub fn set_log_filter(filter: LogFilter) {
+    log::set_max_level(filter.max_level());
+    *LOG_FILTER.write().unwrap() = filter;
+}
+
+pub fn log_filter() -> LogFilter {
+    LOG_FILTER.read().unwrap().clone()
+}
+
+///The filter from the environment variable if it is set and valid, `configured` otherwise.
+pub fn effective_filter(configured: &LogFilter) -> LogFilter {
+    match env_filter() {
+        Some(Ok(filter)) => filter,
+        _ => configured.clone(),
+    }
+}
+
+//Where this session logs, for whoever needs its files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogSession {
+    pub dir: PathBuf,
+    //Start time, the <date> in overlay-<date>.log.
+    pub id: String,
+}
+
+static LOG_SESSION: OnceLock<LogSession> = OnceLock::new();
+
+///None until logging is set up.
+pub fn log_session() -> Option<&'static LogSession> {
+    LOG_SESSION.get()
+}
+
+fn env_filter() -> Option<Result<LogFilter, String>> {
+    let text = std::env::var(LOG_FILTER_ENV).ok()?;
+    Some(text.parse())
+}
+
+///Sets up the log file of this session and cleans up the ones of older sessions.
+///Also installs the panic hook writing crash reports.
+pub fn enable_logging(config: &LoggingConfig) {
+    //Problems are reported once logging works, panicking here would take the game down.
+    let mut errors = Vec::new();
+    let fallback_dir = resolve_path(&Config::default().logging.directory);
+    let mut logs_dir = resolve_path(&config.directory);
+    if let Err(e) = create_dir_all(&logs_dir) {
+        errors.push(format!(
+            "Could not create {} ({}), logging to {} instead.",
+            logs_dir.display(),
+            e,
+            fallback_dir.display()
+        ));
+        logs_dir = fallback_dir.clone();
+        create_dir_all(&logs_dir).ok();
+    }
+
+    //Removed before the new file exists, reported once logging works.
+    let session = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+    let policy = RetentionPolicy {
+        keep_sessions: config.keep_sessions,
+        keep_days: config.keep_days,
+    };
+    let files = list_log_files(&logs_dir);
+    let reports: Vec<PathBuf> = files
+        .iter()
+        .filter(|file| report_session(&file.path).is_some())
+        .map(|file| file.path.clone())
+        .collect();
+    //Looked up before the previous session's files can be removed.
+    let crashed = previous_session(&files, &session)
+        .and_then(|previous| find_crash_report(&reports, previous));
+    let mut removed = 0;
+    let old_files = expired(&files, policy, &session, SystemTime::now());
+    for path in old_files.into_iter().chain(expired_reports(&reports)) {
+        match fs::remove_file(&path) {
+            Ok(()) => removed += 1,
+            Err(e) => errors.push(format!("Could not remove {}: {}", path.display(), e)),
+        }
+    }
+
+    let max_size =
+        (config.max_file_size_mb > 0).then(|| config.max_file_size_mb as u64 * 1024 * 1024);
+    let mut file = RotatingFile::create(&logs_dir, &session, max_size, config.compress);
+    if let Err(e) = &file
+        && logs_dir != fallback_dir
+    {
+        errors.push(format!(
+            "Could not write to {} ({}), logging to {} instead.",
+            logs_dir.display(),
+            e,
+            fallback_dir.display()
+        ));
+        logs_dir = fallback_dir.clone();
+        create_dir_all(&logs_dir).ok();
+        file = RotatingFile::create(&logs_dir, &session, max_size, config.compress);
+    }
+
+    //Init Fern. Every sink goes through LOG_FILTER, so the filter can change at runtime.
+    //The debug overlay and crash reports always get text, stdout and the file get their own format.
+    let mut dispatch = Dispatch::new()
+        .level(log::LevelFilter::Trace)
+        .filter(|metadata| LOG_FILTER.read().unwrap().enabled(metadata))
+        .chain(Output::call(|record| {
+            let line = text_line(record, Local::now());
+            remember_line(line.clone());
+            add_to_debug_log_overlay(line);
+        }));
+    match file {
+        Ok(file) => {
+            dispatch = dispatch.chain(formatted(config.file_format).chain(Output::writer(
+                Box::new(file) as Box<dyn Write + Send>,
+                "\n",
+            )));
+        }
+        Err(e) => errors.push(format!(
+            "Could not open a log file in {} ({}), logging without one.",
+            logs_dir.display(),
+            e
+        )),
+    }
+    if config.stdout {
+        dispatch = dispatch.chain(formatted(config.stdout_format).chain(std::io::stdout()));
+    }
+    #[cfg(feature = "nexus")]
+    let dispatch = dispatch.chain(Output::call(nexus_sink));
+    dispatch.apply().ok();
+    set_log_filter(effective_filter(&config.level));
+
+    LOG_SESSION
+        .set(LogSession {
+            dir: logs_dir.clone(),
+            id: session.clone(),
+        })
+        .ok();
+    install_panic_hook();
+
+    log::info!(
+        "---------------------------------------- New Session ----------------------------------------------"
+    );
+    log::info!(
+        "Version {}, running on {}.",
+        env!("CARGO_PKG_VERSION"),
+        describe_platform()
+    );
+    if let Some(report) = crashed {
+        log::warn!("The previous session crashed, see {}", report.display());
+        show_log_overlay();
+    }
+    if removed > 0 {
+        log::info!("Removed {} old log file(s).", removed);
+    }
+    for error in errors {
+        log::warn!("{}", error);
+    }
+    match env_filter() {
+        Some(Ok(filter)) => log::info!("Log filter from {}: {}", LOG_FILTER_ENV, filter),
+        Some(Err(e)) => log::warn!("{} ignored: {}", LOG_FILTER_ENV, e),
+        None => (),
+    }
+    if config.compress {
+        let old = to_compress(&list_log_files(&logs_dir), &session);
+        if !old.is_empty() {
+            spawn_worker("log compression", move || {
+                for path in old {
+                    if let Err(e) = compress_file(&path) {
+                        log::warn!("Could not compress {}: {}", path.display(), e);
+                    }
+                }
+            });
+        }
+    }
+}
+
+fn formatted(format: LogFormat) -> Dispatch {
+    Dispatch::new().format(move |out, _, record| {
+        out.finish(format_args!(
+            "{}",
+            format_record(format, record, Local::now())
+        ))
+    })
+}
+
+///Every file of `dir`, logs or not.
+pub fn list_log_files(dir: &Path) -> Vec<LogFile> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            metadata.is_file().then(|| LogFile {
+                path: entry.path(),
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            })
+        })
+        .collect()
+}
+
+//Forwards records to the Nexus log window, already filtered.
+#[cfg(feature = "nexus")]
+fn nexus_sink(record: &log::Record) {
+    use nexus::log::LogLevel;
+
+    let level = match record.level() {
+        log::Level::Error => LogLevel::Critical,
+        log::Level::Warn => LogLevel::Warning,
+        log::Level::Info => LogLevel::Info,
+        log::Level::Debug => LogLevel::Debug,
+        log::Level::Trace => LogLevel::Trace,
+    };
+    nexus::log::log(level, "External DX11 overlay", record.args().to_string());
+}
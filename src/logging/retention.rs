@@ -0,0 +1,369 @@
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+//Every session writes overlay-<session>.log, then overlay-<session>.<part>.log once it rolled over.
+//Old files may have been compressed to <name>.gz.
+pub const LOG_PREFIX: &str = "overlay-";
+pub const LOG_EXTENSION: &str = ".log";
+pub const COMPRESSED_EXTENSION: &str = ".gz";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFile {
+    pub path: PathBuf,
+    pub modified: SystemTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    //Sessions kept, the current one included. 0 keeps them all.
+    pub keep_sessions: u32,
+    //Sessions whose last write is older than that are removed. 0 keeps them whatever their age.
+    pub keep_days: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogName<'a> {
+    //Start time of the session, sorts chronologically.
+    pub session: &'a str,
+    //0 for the first file of the session.
+    pub part: u32,
+    pub compressed: bool,
+}
+
+///Splits the name of a log file, None for files that aren't ours.
+pub fn parse_log_name(name: &str) -> Option<LogName<'_>> {
+    let (name, compressed) = match name.strip_suffix(COMPRESSED_EXTENSION) {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+    let stem = name.strip_prefix(LOG_PREFIX)?.strip_suffix(LOG_EXTENSION)?;
+    let (session, part) = match stem.rsplit_once('.') {
+        Some((session, part)) => (session, part.parse().ok()?),
+        None => (stem, 0),
+    };
+    if session.is_empty() {
+        return None;
+    }
+    Some(LogName {
+        session,
+        part,
+        compressed,
+    })
+}
+
+pub fn log_file_name(session: &str, part: u32) -> String {
+    match part {
+        0 => format!("{}{}{}", LOG_PREFIX, session, LOG_EXTENSION),
+        _ => format!("{}{}.{}{}", LOG_PREFIX, session, part, LOG_EXTENSION),
+    }
+}
+
+///The log files to delete under `policy`. The current session is always kept,
+///files that don't look like logs are never touched.
+pub fn expired(
+    files: &[LogFile],
+    policy: RetentionPolicy,
+    current_session: &str,
+    now: SystemTime,
+) -> Vec<PathBuf> {
+    //Newest session first, with the files of each one and when it was last written to.
+    let mut sessions: BTreeMap<&str, (Vec<&LogFile>, SystemTime)> = BTreeMap::new();
+    for file in files {
+        let Some(name) = file_name(file).and_then(parse_log_name) else {
+            continue;
+        };
+        let (session_files, last_write) = sessions
+            .entry(name.session)
+            .or_insert((Vec::new(), SystemTime::UNIX_EPOCH));
+        session_files.push(file);
+        *last_write = (*last_write).max(file.modified);
+    }
+    let max_age = Duration::from_secs(policy.keep_days as u64 * 24 * 60 * 60);
+
+    let mut expired = Vec::new();
+    //The current session counts even before its first file is written.
+    let mut kept = if sessions.contains_key(current_session) {
+        0
+    } else {
+        1
+    };
+    for (session, (session_files, last_write)) in sessions.into_iter().rev() {
+        let keep = session == current_session
+            || ((policy.keep_sessions == 0 || kept < policy.keep_sessions)
+                && (policy.keep_days == 0
+                    || now.duration_since(last_write).unwrap_or_default() <= max_age));
+        if keep {
+            kept += 1;
+        } else {
+            expired.extend(session_files.iter().map(|file| file.path.clone()));
+        }
+    }
+    expired
+}
+
+///The last session before `current_session` that left log files.
+pub fn previous_session<'a>(files: &'a [LogFile], current_session: &str) -> Option<&'a str> {
+    files
+        .iter()
+        .filter_map(|file| Some(parse_log_name(file_name(file)?)?.session))
+        .filter(|session| *session < current_session)
+        .max()
+}
+
+///The uncompressed log files of the sessions before the current one.
+pub fn to_compress(files: &[LogFile], current_session: &str) -> Vec<PathBuf> {
+    files
+        .iter()
+        .filter(|file| {
+            file_name(file)
+                .and_then(parse_log_name)
+                .is_some_and(|name| !name.compressed && name.session != current_session)
+        })
+        .map(|file| file.path.clone())
+        .collect()
+}
+
+fn file_name(file: &LogFile) -> Option<&str> {
+    file.path.file_name()?.to_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+    const CURRENT: &str = "2026-10-19_12-00-00";
+
+    fn day(n: u32) -> SystemTime {
+        SystemTime::UNIX_EPOCH + DAY * 20_000 + DAY * n
+    }
+
+    fn file(name: &str, modified: SystemTime) -> LogFile {
+        LogFile {
+            path: PathBuf::from("logs").join(name),
+            modified,
+        }
+    }
+
+    fn names(paths: Vec<PathBuf>) -> Vec<String> {
+        let mut names: Vec<String> = paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn policy(keep_sessions: u32, keep_days: u32) -> RetentionPolicy {
+        RetentionPolicy {
+            keep_sessions,
+            keep_days,
+        }
+    }
+
+    //Five sessions a day apart, the oldest one rolled over and got compressed,
+    //plus files that aren't logs of ours.
+    fn listing() -> Vec<LogFile> {
+        vec![
+            file("overlay-2026-10-15_12-00-00.log.gz", day(15)),
+            file("overlay-2026-10-15_12-00-00.1.log.gz", day(15)),
+            file("overlay-2026-10-16_12-00-00.log", day(16)),
+            file("overlay-2026-10-17_12-00-00.log", day(17)),
+            file("overlay-2026-10-17_12-00-00.1.log", day(17)),
+            file("overlay-2026-10-18_12-00-00.log", day(18)),
+            file("overlay-2026-10-19_12-00-00.log", day(19)),
+            file("crash-2026-10-10.txt", day(0)),
+            file("overlay-.log", day(0)),
+            file("overlay-2026-10-01.x.log", day(0)),
+            file("blish.log", day(0)),
+            file("overlay-2026-10-01.log.zip", day(0)),
+        ]
+    }
+
+    #[test]
+    fn log_names() {
+        assert_eq!(
+            parse_log_name("overlay-2026-10-19_12-00-00.log"),
+            Some(LogName {
+                session: "2026-10-19_12-00-00",
+                part: 0,
+                compressed: false,
+            })
+        );
+        assert_eq!(
+            parse_log_name("overlay-2026-10-19_12-00-00.3.log.gz"),
+            Some(LogName {
+                session: "2026-10-19_12-00-00",
+                part: 3,
+                compressed: true,
+            })
+        );
+        for name in [
+            "overlay-.log",
+            "overlay-.2.log",
+            "overlay-a.b.log",
+            "overlay-a.log.zip",
+            "Overlay-a.log",
+            "overlay-a.txt",
+            "blish.log",
+        ] {
+            assert_eq!(parse_log_name(name), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn file_names_round_trip() {
+        for part in [0, 1, 12] {
+            let name = log_file_name(CURRENT, part);
+            let parsed = parse_log_name(&name).unwrap();
+            assert_eq!((parsed.session, parsed.part), (CURRENT, part));
+        }
+        assert_eq!(log_file_name(CURRENT, 0), "overlay-2026-10-19_12-00-00.log");
+        assert_eq!(
+            log_file_name(CURRENT, 2),
+            "overlay-2026-10-19_12-00-00.2.log"
+        );
+    }
+
+    #[test]
+    fn keeps_everything_without_limits() {
+        assert!(expired(&listing(), policy(0, 0), CURRENT, day(100)).is_empty());
+    }
+
+    #[test]
+    fn session_cap() {
+        assert_eq!(
+            names(expired(&listing(), policy(3, 0), CURRENT, day(19))),
+            [
+                "overlay-2026-10-15_12-00-00.1.log.gz",
+                "overlay-2026-10-15_12-00-00.log.gz",
+                "overlay-2026-10-16_12-00-00.log",
+            ]
+        );
+        //Whole sessions go, with all their parts.
+        assert_eq!(
+            names(expired(&listing(), policy(2, 0), CURRENT, day(19))),
+            [
+                "overlay-2026-10-15_12-00-00.1.log.gz",
+                "overlay-2026-10-15_12-00-00.log.gz",
+                "overlay-2026-10-16_12-00-00.log",
+                "overlay-2026-10-17_12-00-00.1.log",
+                "overlay-2026-10-17_12-00-00.log",
+            ]
+        );
+    }
+
+    #[test]
+    fn day_cap() {
+        assert_eq!(
+            names(expired(&listing(), policy(0, 2), CURRENT, day(19))),
+            [
+                "overlay-2026-10-15_12-00-00.1.log.gz",
+                "overlay-2026-10-15_12-00-00.log.gz",
+                "overlay-2026-10-16_12-00-00.log",
+            ]
+        );
+        //Exactly keep_days old is still kept.
+        let kept = expired(&listing(), policy(0, 4), CURRENT, day(19));
+        assert!(kept.is_empty());
+    }
+
+    #[test]
+    fn age_is_the_last_write_of_the_session() {
+        let files = [
+            file("overlay-2026-10-01_12-00-00.log", day(1)),
+            file("overlay-2026-10-01_12-00-00.1.log", day(9)),
+        ];
+        assert!(expired(&files, policy(0, 2), CURRENT, day(10)).is_empty());
+        assert_eq!(expired(&files, policy(0, 2), CURRENT, day(12)).len(), 2);
+    }
+
+    #[test]
+    fn both_caps() {
+        //The count keeps 4 sessions, the age only 3.
+        assert_eq!(
+            names(expired(&listing(), policy(4, 2), CURRENT, day(19))).len(),
+            3
+        );
+        //The age keeps every session, the count only 2.
+        assert_eq!(
+            names(expired(&listing(), policy(2, 30), CURRENT, day(19))).len(),
+            5
+        );
+    }
+
+    #[test]
+    fn current_session_is_always_kept() {
+        let files = [file("overlay-2026-10-19_12-00-00.log", day(1))];
+        assert!(expired(&files, policy(1, 1), CURRENT, day(100)).is_empty());
+        //Even when a newer session (the clock changed) takes the only place.
+        let files = [
+            file("overlay-2026-10-01_12-00-00.log", day(19)),
+            file("overlay-2026-10-19_12-00-00.log", day(19)),
+            file("overlay-2027-01-01_12-00-00.log", day(19)),
+        ];
+        assert_eq!(
+            names(expired(&files, policy(1, 0), CURRENT, day(19))),
+            ["overlay-2026-10-01_12-00-00.log"]
+        );
+    }
+
+    #[test]
+    fn current_session_counts_before_its_first_file() {
+        let files: Vec<LogFile> = listing()
+            .into_iter()
+            .filter(|file| !file.path.ends_with("overlay-2026-10-19_12-00-00.log"))
+            .collect();
+        //The current session and 18th, 17th.
+        assert_eq!(
+            names(expired(&files, policy(3, 0), CURRENT, day(19))),
+            [
+                "overlay-2026-10-15_12-00-00.1.log.gz",
+                "overlay-2026-10-15_12-00-00.log.gz",
+                "overlay-2026-10-16_12-00-00.log",
+            ]
+        );
+    }
+
+    #[test]
+    fn foreign_files_are_never_touched() {
+        let expired = names(expired(&listing(), policy(1, 1), CURRENT, day(1000)));
+        assert_eq!(expired.len(), 6);
+        assert!(expired.iter().all(|name| parse_log_name(name).is_some()));
+        assert!(
+            names(to_compress(&listing(), CURRENT))
+                .iter()
+                .all(|name| parse_log_name(name).is_some())
+        );
+    }
+
+    #[test]
+    fn compresses_the_previous_sessions() {
+        assert_eq!(
+            names(to_compress(&listing(), CURRENT)),
+            [
+                "overlay-2026-10-16_12-00-00.log",
+                "overlay-2026-10-17_12-00-00.1.log",
+                "overlay-2026-10-17_12-00-00.log",
+                "overlay-2026-10-18_12-00-00.log",
+            ]
+        );
+    }
+
+    #[test]
+    fn previous_sessions() {
+        let files = listing();
+        assert_eq!(
+            previous_session(&files, CURRENT),
+            Some("2026-10-18_12-00-00")
+        );
+        assert_eq!(
+            previous_session(&files, "2026-10-16_12-00-00"),
+            Some("2026-10-15_12-00-00")
+        );
+        assert_eq!(previous_session(&files, "2026-10-15_12-00-00"), None);
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{Compression, write::GzEncoder};
+
+use super::retention::{COMPRESSED_EXTENSION, log_file_name};
+use crate::shutdown::spawn_worker;
+
+///The log file of this session. Once it reaches the size cap, the next record goes to a new part.
+pub struct RotatingFile {
+    dir: PathBuf,
+    session: String,
+    part: u32,
+    file: File,
+    written: u64,
+    //None for no cap.
+    max_size: Option<u64>,
+    //Compress the parts that are done.
+    compress: bool,
+}
+
+impl RotatingFile {
+    pub fn create(
+        dir: &Path,
+        session: &str,
+        max_size: Option<u64>,
+        compress: bool,
+    ) -> io::Result<RotatingFile> {
+        let file = open_part(dir, session, 0)?;
+        Ok(RotatingFile {
+            dir: dir.to_path_buf(),
+            session: session.to_string(),
+            part: 0,
+            written: file.metadata()?.len(),
+            file,
+            max_size,
+            compress,
+        })
+    }
+
+    fn roll_over(&mut self) -> io::Result<()> {
+        let next = open_part(&self.dir, &self.session, self.part + 1)?;
+        let done = self.dir.join(log_file_name(&self.session, self.part));
+        self.file = next;
+        self.part += 1;
+        self.written = 0;
+        if self.compress {
+            spawn_worker("log compression", move || {
+                if let Err(e) = compress_file(&done) {
+                    log::warn!("Could not compress {}: {}", done.display(), e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    //Called after every record, so records are never split between parts.
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_size.is_some_and(|max| self.written >= max) {
+            self.roll_over()?;
+        }
+        Ok(())
+    }
+}
+
+fn open_part(dir: &Path, session: &str, part: u32) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(log_file_name(session, part)))
+}
+
+///Replaces a file with <name>.gz.
+pub fn compress_file(path: &Path) -> io::Result<()> {
+    let mut compressed = path.as_os_str().to_owned();
+    compressed.push(COMPRESSED_EXTENSION);
+    let mut input = BufReader::new(File::open(path)?);
+    let mut encoder = GzEncoder::new(
+        BufWriter::new(File::create(&compressed)?),
+        Compression::default(),
+    );
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?.flush()?;
+    fs::remove_file(path)
+}
@@ -0,0 +1,151 @@
+use std::{
+    cell::Cell,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use windows::{Win32::System::Diagnostics::Debug::OutputDebugStringW, core::PCWSTR};
+
+//What detatch() needs to leave nothing of ours running: the worker threads, which are told to
+//stop and waited for, and the calls into our hooks that haven't returned yet.
+
+//How long detatch() waits for the workers, and then for the hook calls.
+pub const WORKER_TIMEOUT: Duration = Duration::from_secs(2);
+pub const HOOK_CALL_TIMEOUT: Duration = Duration::from_secs(1);
+//Workers sleeping for longer wake up this often to check stopping().
+pub const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+//Who asked for the teardown, which decides what can be done safely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Teardown {
+    //Nexus unloading the addon, or the DLL ejecting itself. Threads are joined.
+    Unload,
+    //DllMain with the loader lock held. Threads are waited for but can't be joined,
+    //they need the lock to exit.
+    LoaderLock,
+    //The process is exiting. Windows already killed the other threads, nothing to stop.
+    ProcessExit,
+}
+
+static STOP: AtomicBool = AtomicBool::new(false);
+static WORKERS: Mutex<Vec<(&'static str, JoinHandle<()>)>> = Mutex::new(Vec::new());
+
+///Runs `f` on a new thread named `name`, which detatch() waits for. Nothing is started once
+///the DLL is detaching. Long running workers must return soon after stopping() turns true.
+pub fn spawn_worker<F>(name: &'static str, f: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    //No logging here, log rotation starts workers while holding the log file.
+    if stopping() {
+        return;
+    }
+    match thread::Builder::new().name(name.to_string()).spawn(f) {
+        Ok(handle) => {
+            let mut workers = WORKERS.lock().unwrap_or_else(|e| e.into_inner());
+            //Short lived workers would otherwise pile up.
+            workers.retain(|(_, handle)| !handle.is_finished());
+            workers.push((name, handle));
+        }
+        //Not through log either, rotation calls us from inside the log writer.
+        Err(e) => debug_output(&format!("Could not start {}: {}\n", name, e)),
+    }
+}
+
+//Shows up in a debugger or DebugView.
+fn debug_output(message: &str) {
+    let wide: Vec<u16> = message.encode_utf16().chain(Some(0)).collect();
+    unsafe { OutputDebugStringW(PCWSTR(wide.as_ptr())) };
+}
+
+pub fn stopping() -> bool {
+    STOP.load(Ordering::Relaxed)
+}
+
+///Sleeps for `duration` unless the workers are told to stop meanwhile.
+///Returns false if they are.
+pub fn sleep_unless_stopping(duration: Duration) -> bool {
+    let started = Instant::now();
+    while !stopping() {
+        let left = duration.saturating_sub(started.elapsed());
+        if left.is_zero() {
+            return true;
+        }
+        thread::sleep(left.min(STOP_CHECK_INTERVAL));
+    }
+    false
+}
+
+///Tells every worker to stop and waits up to `timeout` for them, joining them if `join`.
+///Returns the names of those still running, which are left alone.
+pub fn stop_workers(timeout: Duration, join: bool) -> Vec<&'static str> {
+    STOP.store(true, Ordering::Relaxed);
+    let workers = std::mem::take(&mut *WORKERS.lock().unwrap_or_else(|e| e.into_inner()));
+    let current = thread::current().id();
+    let deadline = Instant::now() + timeout;
+    let mut running = Vec::new();
+    for (name, handle) in workers {
+        //A worker can detach the DLL itself (attach failing), it can't wait for itself.
+        if handle.thread().id() == current {
+            continue;
+        }
+        while !handle.is_finished() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        if !handle.is_finished() {
+            running.push(name);
+        } else if join {
+            //A panic was already logged by the panic hook.
+            handle.join().ok();
+        }
+    }
+    running
+}
+
+//Calls into present() and wnd_proc that haven't returned, on any thread.
+static HOOK_CALLS: AtomicUsize = AtomicUsize::new(0);
+thread_local! {
+    //The part of HOOK_CALLS made on this thread.
+    static OWN_HOOK_CALLS: Cell<usize> = const { Cell::new(0) };
+}
+
+///Held for the whole duration of a hooked call.
+pub struct HookCall(());
+
+impl HookCall {
+    pub fn enter() -> Self {
+        HOOK_CALLS.fetch_add(1, Ordering::AcqRel);
+        OWN_HOOK_CALLS.with(|own| own.set(own.get() + 1));
+        HookCall(())
+    }
+}
+
+impl Drop for HookCall {
+    fn drop(&mut self) {
+        OWN_HOOK_CALLS.with(|own| own.set(own.get() - 1));
+        HOOK_CALLS.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+///Waits up to `timeout` for the hook calls of other threads to return.
+///The hooks must be removed already, or new calls keep coming.
+pub fn wait_for_hook_calls(timeout: Duration) -> bool {
+    let own = own_hook_calls();
+    let started = Instant::now();
+    while HOOK_CALLS.load(Ordering::Acquire) > own {
+        if started.elapsed() >= timeout {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    true
+}
+
+///Hook calls the current thread is in. Unloading from inside one returns into freed code.
+pub fn own_hook_calls() -> usize {
+    OWN_HOOK_CALLS.with(Cell::get)
+}
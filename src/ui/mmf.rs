@@ -21,7 +21,11 @@ use windows::{
     core::PCWSTR,
 };
 
-use super::{HEADER_NAME, HEADER_SIZE, MMF_DATA, OVERLAY_STATE};
+use super::{HEADER_SIZE, MMF_DATA, OVERLAY_STATE};
+use crate::{
+    config::config,
+    shutdown::{spawn_worker, stopping},
+};
 
 #[derive(Debug)]
 pub struct MMFData {
@@ -37,14 +41,14 @@ pub struct MMFData {
 unsafe impl Send for MMFData {}
 unsafe impl Sync for MMFData {}
 
-///This thread runs forever, updating the MMF data so as to not block present()
+///This thread runs until the DLL detaches, updating the MMF data so as to not block present()
 ///With this current method, it takes 0-500 nanoseconds to get the lock in present().
 ///The performance impact is therefore unnoticable. However, it's important that the
 ///write lock is ONLY KEPT ALIVE AS LITTLE AS POSSIBLE. In other words, it should only be
 ///locked when directly reading or writing from MMFData, no other functions should be called
 ///while the lock is held. If more speed is required, use double buffering.
 pub fn start_mmf_thread() {
-    std::thread::spawn(|| {
+    spawn_worker("mmf", || {
         if MMF_DATA.get().is_none() {
             MMF_DATA
                 .set(Arc::new(RwLock::new(MMFData {
@@ -60,7 +64,7 @@ pub fn start_mmf_thread() {
                 .ok();
         }
 
-        loop {
+        while !stopping() {
             //Get data locally so we can drop the lock
             let mut mmfdata = MMF_DATA.get().unwrap().write().unwrap();
             let mut blish_alive = mmfdata.is_blish_alive;
@@ -115,9 +119,24 @@ pub fn start_mmf_thread() {
     });
 }
 
+///True while blish is running, as last seen by the MMF thread.
+pub fn is_overlay_connected() -> bool {
+    MMF_DATA
+        .get()
+        .is_some_and(|data| data.read().unwrap().is_blish_alive)
+}
+
+///is_overlay_connected(), or None instead of waiting for the MMF thread.
+pub fn try_is_overlay_connected() -> Option<bool> {
+    let data = MMF_DATA.get()?.try_read().ok()?;
+    Some(data.is_blish_alive)
+}
+
 //Simply pings the mutex in the blish fork, to check if it's still up and hasn't crashed.
 pub fn is_blish_alive() -> bool {
-    let name: Vec<u16> = "Global\\blish_isalive_mutex"
+    let name: Vec<u16> = config()
+        .overlay
+        .alive_mutex
         .encode_utf16()
         .chain(Some(0))
         .collect();
@@ -144,14 +163,31 @@ pub fn is_blish_alive() -> bool {
     }
 }
 
+///Blish is gone: clears the header it left behind so stale textures aren't opened again,
+///then closes the MMF and shuts the overlay down.
 pub fn cleanup_shutdown() {
     if let Some(mmfdata) = MMF_DATA.get() {
-        let mut mmfdata = mmfdata.write().unwrap();
+        let mmfdata = mmfdata.write().unwrap();
         if let (Some(view), Some(_)) = (mmfdata.header, mmfdata.file_mapping) {
             unsafe {
                 std::ptr::write_bytes(view.Value, 0, HEADER_SIZE);
             }
         }
+    }
+    close_mmf();
+    if let Some(state) = OVERLAY_STATE.get() {
+        let mut lock = state.lock().unwrap();
+        let state = lock.as_mut();
+        if let Some(state) = state {
+            state.shutdown();
+        }
+    }
+}
+
+///Unmaps the header and closes the mapping, leaving its content to Blish.
+pub fn close_mmf() {
+    if let Some(mmfdata) = MMF_DATA.get() {
+        let mut mmfdata = mmfdata.write().unwrap_or_else(|e| e.into_inner());
         if let Some(view) = mmfdata.header.take() {
             unsafe {
                 UnmapViewOfFile(view).ok();
@@ -169,18 +205,12 @@ pub fn cleanup_shutdown() {
         mmfdata.addr1 = 0;
         mmfdata.addr2 = 0;
     }
-    if let Some(state) = OVERLAY_STATE.get() {
-        let mut lock = state.lock().unwrap();
-        let state = lock.as_mut();
-        if let Some(state) = state {
-            state.shutdown();
-        }
-    }
 }
 
 fn open_header_mmf() -> Result<(MEMORY_MAPPED_VIEW_ADDRESS, HANDLE), ()> {
     unsafe {
-        let wide_name: Vec<u16> = OsStr::new(HEADER_NAME)
+        //Handle to MMFs. Named "HEADER" because the previous version used a body as well
+        let wide_name: Vec<u16> = OsStr::new(&config().overlay.texture_mapping)
             .encode_wide()
             .chain(std::iter::once(0))
             .collect();
@@ -1,4 +1,4 @@
-use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::sync::{Arc, Mutex, OnceLock, RwLock, atomic::AtomicBool};
 
 use mmf::MMFData;
 use rendering::{OverlayState, detoured_present};
@@ -7,11 +7,45 @@ use windows::{Win32::Graphics::Dxgi::IDXGISwapChain, core::HRESULT};
 pub static MMF_DATA: OnceLock<Arc<RwLock<MMFData>>> = OnceLock::new();
 pub static OVERLAY_STATE: OnceLock<Mutex<Option<OverlayState>>> = OnceLock::new();
 
+//Set by the overlay through the control channel. Hidden means present() draws nothing.
+pub static OVERLAY_HIDDEN: AtomicBool = AtomicBool::new(false);
+//Set by the overlay through the control channel. The shared textures get reopened on the next frame.
+pub static REDRAW_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+//Where the overlay currently ends up in the backbuffer. Written by present(), read by controls
+//to translate mouse coordinates into overlay texture coordinates.
+pub static OVERLAY_GEOMETRY: RwLock<OverlayGeometry> = RwLock::new(OverlayGeometry::EMPTY);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OverlayGeometry {
+    pub backbuffer_width: u32,
+    pub backbuffer_height: u32,
+    //Area of the backbuffer the overlay texture is stretched over.
+    pub viewport_x: f32,
+    pub viewport_y: f32,
+    pub viewport_width: f32,
+    pub viewport_height: f32,
+    //Size of the shared texture the overlay renders into.
+    pub texture_width: u32,
+    pub texture_height: u32,
+}
+
+impl OverlayGeometry {
+    pub const EMPTY: OverlayGeometry = OverlayGeometry {
+        backbuffer_width: 0,
+        backbuffer_height: 0,
+        viewport_x: 0.0,
+        viewport_y: 0.0,
+        viewport_width: 0.0,
+        viewport_height: 0.0,
+        texture_width: 0,
+        texture_height: 0,
+    };
+}
+
 pub mod mmf;
 mod rendering;
-
-//Handle to MMFs. Currently named "HEADER" because the previous version used a body as well
-static HEADER_NAME: &str = "BlishHUD_Header";
+pub mod swapchain;
 
 //See C# for description
 const HEADER_SIZE: usize = 28;
@@ -19,3 +53,19 @@ const HEADER_SIZE: usize = 28;
 pub fn get_detoured_present() -> impl Fn(IDXGISwapChain, u32, u32) -> HRESULT {
     detoured_present
 }
+
+///Closes the MMF, leaving what Blish wrote there alone, and releases every D3D resource of
+///the overlay, device and context included.
+///The present hook must be disabled and its calls returned.
+pub fn release_overlay() {
+    mmf::close_mmf();
+    if let Some(state) = OVERLAY_STATE.get()
+        && state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+            .is_some()
+    {
+        log::info!("Released the overlay's D3D resources.");
+    }
+}
@@ -29,7 +29,11 @@ use crate::{
         statistics::{self, send_statistic},
     },
     hooks::present_hook,
-    ui::{MMF_DATA, mmf::cleanup_shutdown},
+    shutdown::HookCall,
+    ui::{
+        MMF_DATA, OVERLAY_GEOMETRY, OVERLAY_HIDDEN, OverlayGeometry, REDRAW_REQUESTED,
+        mmf::cleanup_shutdown, swapchain,
+    },
 };
 
 use super::OVERLAY_STATE;
@@ -93,11 +97,27 @@ impl OverlayState {
         };
 
         self.blend_factor = [0.0; 4];
+
+        *OVERLAY_GEOMETRY.write().unwrap() = OverlayGeometry::EMPTY;
+    }
+    //Lets controls know where the overlay texture is drawn.
+    fn publish_geometry(&self, texture_width: u32, texture_height: u32) {
+        *OVERLAY_GEOMETRY.write().unwrap() = OverlayGeometry {
+            backbuffer_width: self.width,
+            backbuffer_height: self.height,
+            viewport_x: self.viewport.TopLeftX,
+            viewport_y: self.viewport.TopLeftY,
+            viewport_width: self.viewport.Width,
+            viewport_height: self.viewport.Height,
+            texture_width,
+            texture_height,
+        };
     }
 }
 
 ///This is our big present hook. Draws shared textures as an overlay.
 pub fn detoured_present(swapchain: IDXGISwapChain, sync_interval: u32, flags: u32) -> HRESULT {
+    let _call = HookCall::enter();
     let start = Instant::now();
     //Macro to make it less ugly to return early.
     macro_rules! return_present {
@@ -105,7 +125,10 @@ pub fn detoured_present(swapchain: IDXGISwapChain, sync_interval: u32, flags: u3
             return present_hook.call(swapchain, sync_interval, flags)
         };
     }
-    if !DEBUG_FEATURES.rendering_enabled.load(Ordering::Relaxed) {
+    if !DEBUG_FEATURES.rendering_enabled.load(Ordering::Relaxed)
+        || OVERLAY_HIDDEN.load(Ordering::Relaxed)
+        || !is_target_swapchain(&swapchain)
+    {
         unsafe { return_present!() }
     }
     unsafe {
@@ -147,9 +170,13 @@ pub fn detoured_present(swapchain: IDXGISwapChain, sync_interval: u32, flags: u3
             return_present!();
         }
 
-        //Resize occured
-        if state.height != mmfdata.height || state.width != mmfdata.width {
+        //Resize occured, or the overlay asked for its textures to be reopened
+        if REDRAW_REQUESTED.swap(false, Ordering::Relaxed)
+            || state.height != mmfdata.height
+            || state.width != mmfdata.width
+        {
             state.resize(&swapchain);
+            state.publish_geometry(mmfdata.width, mmfdata.height);
             if update_textures(&mut state, [mmfdata.addr1, mmfdata.addr2]).is_err() {
                 state.context.PSSetShaderResources(0, Some(&[None]));
                 drop(mmfdata);
@@ -0,0 +1,63 @@
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+use windows::{
+    Win32::Graphics::Dxgi::{DXGI_SWAP_CHAIN_DESC, IDXGISwapChain},
+    core::Interface,
+};
+
+use crate::globals::GAME_HWND;
+
+//Which swapchains present() draws the overlay on.
+//Games, launchers and other overlays can present several, only one should get the overlay.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapchainTarget {
+    //Only the swapchain presenting to the game's main window.
+    GameWindow = 0,
+    //Every swapchain, like before there was a choice.
+    Any = 1,
+}
+
+impl SwapchainTarget {
+    pub const ALL: [SwapchainTarget; 2] = [SwapchainTarget::GameWindow, SwapchainTarget::Any];
+
+    //Same order as ALL.
+    pub const NAMES: [&'static str; 2] = ["game_window", "any"];
+
+    pub fn from_u8(value: u8) -> Option<SwapchainTarget> {
+        Self::ALL.get(value as usize).copied()
+    }
+
+    pub fn name(self) -> &'static str {
+        Self::NAMES[self as usize]
+    }
+}
+
+pub static SWAPCHAIN_TARGET: AtomicU8 = AtomicU8::new(SwapchainTarget::GameWindow as u8);
+
+//Last swapchain found to present to the game window, so its window isn't looked up every frame.
+static GAME_SWAPCHAIN: AtomicUsize = AtomicUsize::new(0);
+
+///Whether present() should draw the overlay on this swapchain.
+pub fn is_target_swapchain(swapchain: &IDXGISwapChain) -> bool {
+    let target = SwapchainTarget::from_u8(SWAPCHAIN_TARGET.load(Ordering::Relaxed))
+        .unwrap_or(SwapchainTarget::GameWindow);
+    if target == SwapchainTarget::Any {
+        return true;
+    }
+    let raw = swapchain.as_raw() as usize;
+    if GAME_SWAPCHAIN.load(Ordering::Relaxed) == raw {
+        return true;
+    }
+    //Nothing is drawn until controls found the game's window.
+    let Some(hwnd) = GAME_HWND.get() else {
+        return false;
+    };
+    let mut desc = DXGI_SWAP_CHAIN_DESC::default();
+    if unsafe { swapchain.GetDesc(&mut desc) }.is_err() || desc.OutputWindow != *hwnd {
+        return false;
+    }
+    GAME_SWAPCHAIN.store(raw, Ordering::Relaxed);
+    log::info!("Drawing on the swapchain of the game window ({:#x}).", raw);
+    true
+}
@@ -1,26 +1,31 @@
 use std::{
-    ffi::OsStr,
+    ffi::{CStr, OsStr, OsString, c_char},
     mem::{self, size_of},
-    os::windows::ffi::OsStrExt,
+    os::windows::ffi::{OsStrExt, OsStringExt},
+    path::PathBuf,
 };
 use windows::{
     Win32::{
-        Foundation::{BOOL, HWND, LPARAM},
+        Foundation::{BOOL, HMODULE, HWND, LPARAM},
         System::{
-            LibraryLoader::GetModuleHandleA,
+            LibraryLoader::{
+                GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS,
+                GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT, GetModuleFileNameW, GetModuleHandleA,
+                GetModuleHandleExW, GetProcAddress,
+            },
             ProcessStatus::{GetModuleInformation, MODULEINFO},
             Threading::{GetCurrentProcess, GetCurrentProcessId},
         },
         UI::{
             Input::KeyboardAndMouse::IsWindowEnabled,
             WindowsAndMessaging::{
-                EnumWindows, FindWindowW, GW_OWNER, GWL_EXSTYLE, GetParent, GetWindow,
-                GetWindowLongPtrW, GetWindowTextW, GetWindowThreadProcessId, IsWindow,
+                EnumWindows, FindWindowW, GW_OWNER, GWL_EXSTYLE, GetClassNameW, GetParent,
+                GetWindow, GetWindowLongPtrW, GetWindowTextW, GetWindowThreadProcessId, IsWindow,
                 IsWindowVisible, WS_EX_TOOLWINDOW,
             },
         },
     },
-    core::PCWSTR,
+    core::{PCWSTR, s},
 };
 
 ///Returns the base address of the process memory.
@@ -47,48 +52,160 @@ pub fn get_base_addr_and_size() -> (usize, usize) {
         (modinfo.lpBaseOfDll as usize, modinfo.SizeOfImage as usize)
     }
 }
+///Full path of this DLL, whichever loader loaded it.
+pub fn get_dll_path() -> Option<PathBuf> {
+    let mut module = HMODULE::default();
+    unsafe {
+        //Any address inside the DLL will do, this function is one.
+        GetModuleHandleExW(
+            GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS | GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+            PCWSTR(get_dll_path as *const u16),
+            &mut module,
+        )
+        .ok()?;
+        let mut buf = [0u16; 1024];
+        let len = GetModuleFileNameW(module, &mut buf) as usize;
+        if len == 0 || len >= buf.len() {
+            return None;
+        }
+        Some(PathBuf::from(OsString::from_wide(&buf[..len])))
+    }
+}
+
+///"Windows", or the Wine version and the system it runs on, eg. "Wine 9.0 on Linux 6.8.0 (Proton)".
+pub fn describe_platform() -> String {
+    let Some(wine) = wine_version() else {
+        return "Windows".to_string();
+    };
+    let mut platform = format!("Wine {}", wine);
+    if let Some((system, release)) = wine_host() {
+        platform.push_str(&format!(" on {} {}", system, release));
+    }
+    //Set by Steam for the games it starts through Proton.
+    if std::env::var_os("STEAM_COMPAT_DATA_PATH").is_some() {
+        platform.push_str(" (Proton)");
+    }
+    platform
+}
+
+//ntdll exports these under Wine only.
+fn wine_version() -> Option<String> {
+    unsafe {
+        let ntdll = GetModuleHandleA(s!("ntdll.dll")).ok()?;
+        let get_version = GetProcAddress(ntdll, s!("wine_get_version"))?;
+        let get_version: unsafe extern "C" fn() -> *const c_char = mem::transmute(get_version);
+        let version = get_version();
+        (!version.is_null()).then(|| CStr::from_ptr(version).to_string_lossy().into_owned())
+    }
+}
+
+fn wine_host() -> Option<(String, String)> {
+    unsafe {
+        let ntdll = GetModuleHandleA(s!("ntdll.dll")).ok()?;
+        let get_host = GetProcAddress(ntdll, s!("wine_get_host_version"))?;
+        let get_host: unsafe extern "C" fn(*mut *const c_char, *mut *const c_char) =
+            mem::transmute(get_host);
+        let mut system = std::ptr::null();
+        let mut release = std::ptr::null();
+        get_host(&mut system, &mut release);
+        if system.is_null() || release.is_null() {
+            return None;
+        }
+        Some((
+            CStr::from_ptr(system).to_string_lossy().into_owned(),
+            CStr::from_ptr(release).to_string_lossy().into_owned(),
+        ))
+    }
+}
+
 ///Gets the HWND of the window this DLL is attached to.
+///If `window_class` is given, only a window of that class is accepted.
 ///There very well may be a better way to do this.
-pub fn get_mainwindow_hwnd() -> Option<HWND> {
+pub fn get_mainwindow_hwnd(window_class: Option<&str>) -> Option<HWND> {
+    struct Search<'a> {
+        window_class: Option<&'a str>,
+        found: HWND,
+    }
+    unsafe extern "system" fn enum_windows_proc(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        unsafe {
+            let search = &mut *(lparam.0 as *mut Search);
+            if !is_main_window(hwnd) {
+                return true.into();
+            }
+            if let Some(class) = search.window_class
+                && !get_window_class(hwnd).eq_ignore_ascii_case(class)
+            {
+                return true.into();
+            }
+            search.found = hwnd;
+        }
+        false.into()
+    }
+
+    let mut search = Search {
+        window_class,
+        found: HWND(0),
+    };
+    unsafe {
+        EnumWindows(
+            Some(enum_windows_proc),
+            LPARAM(&mut search as *mut _ as isize),
+        )
+        .ok();
+    }
+    if search.found.0 != 0 {
+        Some(search.found)
+    } else {
+        None
+    }
+}
+
+///Classes of the windows get_mainwindow_hwnd() could pick, to match profiles against.
+pub fn get_mainwindow_classes() -> Vec<String> {
     unsafe extern "system" fn enum_windows_proc(hwnd: HWND, lparam: LPARAM) -> BOOL {
         unsafe {
-            let mut pid = 0;
-            GetWindowThreadProcessId(hwnd, Some(&mut pid));
-            let target_pid = GetCurrentProcessId();
-
-            if pid == target_pid {
-                let is_visible = IsWindowVisible(hwnd).as_bool();
-                let is_enabled = IsWindowEnabled(hwnd).as_bool();
-                let owner = GetWindow(hwnd, GW_OWNER);
-                let parent = GetParent(hwnd);
-
-                let ex_style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE) as u32;
-
-                if !is_visible || !is_enabled || (ex_style & WS_EX_TOOLWINDOW.0) != 0 {
-                    return true.into();
-                }
-
-                if owner.0 == 0 && parent.0 == 0 {
-                    *(lparam.0 as *mut HWND) = hwnd;
-                    return false.into();
-                }
+            if is_main_window(hwnd) {
+                (*(lparam.0 as *mut Vec<String>)).push(get_window_class(hwnd));
             }
         }
         true.into()
     }
 
+    let mut classes: Vec<String> = Vec::new();
     unsafe {
-        let mut hwnd: HWND = HWND(0);
         EnumWindows(
             Some(enum_windows_proc),
-            LPARAM(&mut hwnd as *mut _ as isize),
+            LPARAM(&mut classes as *mut _ as isize),
         )
         .ok();
+    }
+    classes
+}
 
-        if hwnd.0 != 0 { Some(hwnd) } else { None }
+//Visible, enabled, top-level window of this process that isn't a tool window.
+unsafe fn is_main_window(hwnd: HWND) -> bool {
+    unsafe {
+        let mut pid = 0;
+        GetWindowThreadProcessId(hwnd, Some(&mut pid));
+        if pid != GetCurrentProcessId() {
+            return false;
+        }
+        let is_visible = IsWindowVisible(hwnd).as_bool();
+        let is_enabled = IsWindowEnabled(hwnd).as_bool();
+        let ex_style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE) as u32;
+        if !is_visible || !is_enabled || (ex_style & WS_EX_TOOLWINDOW.0) != 0 {
+            return false;
+        }
+        GetWindow(hwnd, GW_OWNER).0 == 0 && GetParent(hwnd).0 == 0
     }
 }
 
+pub fn get_window_class(hwnd: HWND) -> String {
+    let mut buf = [0u16; 256];
+    let len = unsafe { GetClassNameW(hwnd, &mut buf) };
+    String::from_utf16_lossy(&buf[..len.max(0) as usize])
+}
+
 fn to_pcwstr(s: &str) -> PCWSTR {
     //Create null-terminated u16 array as is the wide character standard.
     let wide: Vec<u16> = OsStr::new(s).encode_wide().chain(Some(0)).collect();
@@ -108,8 +225,16 @@ pub fn find_hwnd_by_title(title: &str) -> Option<HWND> {
 ///For debugging purposes. Lists all windows and their titles.
 ///Helps to know what to pass to find_hwnd_by_title()
 pub fn dump_all_window_titles() {
-    unsafe extern "system" fn enum_windows_proc(hwnd: HWND, _lparam: LPARAM) -> BOOL {
+    for (hwnd, _, title) in list_windows() {
+        println!("HWND: {:?} - Title: {}", hwnd, title);
+    }
+}
+
+///The visible windows with a title: handle, class and title.
+pub fn list_windows() -> Vec<(HWND, String, String)> {
+    unsafe extern "system" fn enum_windows_proc(hwnd: HWND, lparam: LPARAM) -> BOOL {
         unsafe {
+            let windows = &mut *(lparam.0 as *mut Vec<(HWND, String, String)>);
             if !IsWindow(hwnd).as_bool() || !IsWindowVisible(hwnd).as_bool() {
                 return true.into();
             }
@@ -117,15 +242,21 @@ pub fn dump_all_window_titles() {
             let len = GetWindowTextW(hwnd, &mut buf);
             if len > 0 {
                 let title = String::from_utf16_lossy(&buf[..len as usize]);
-                println!("HWND: {:?} - Title: {}", hwnd, title);
+                windows.push((hwnd, get_window_class(hwnd), title));
             }
             true.into()
         }
     }
 
+    let mut windows = Vec::new();
     unsafe {
-        EnumWindows(Some(enum_windows_proc), LPARAM(0)).ok();
+        EnumWindows(
+            Some(enum_windows_proc),
+            LPARAM(&mut windows as *mut _ as isize),
+        )
+        .ok();
     }
+    windows
 }
 
 ///Takes a pointer to some area in memory and derefences it into T